@@ -52,5 +52,6 @@ macro_rules! define_chararr_id {
     };
 }
 
-// NB: there used to be two different types of these ids, hence the macro
 define_chararr_id!(GameId, 16);
+// secret that allows a player to reconnect to their seat
+define_chararr_id!(SessionToken, 24);
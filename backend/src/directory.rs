@@ -7,14 +7,20 @@
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 use tokio::sync::oneshot;
+use tokio::time::Instant;
 
-use core::srvcli;
+use core::{srvcli, TurnTimer, TimeoutPolicy};
 use crate::{
-    game::{GameId, GameConfig, spawn_game_task},
+    game::{GameId, GameConfig, GameSummary, GameTimeouts, spawn_game_task, spawn_restored_game_task},
     game_task::{GameTaskTx},
     directory_task::{DirReq, DirTaskRx, DirTaskTx},
+    store::Store,
+    stats::{Counters, ServerStats},
 };
 
+/// Time per turn in quick-match games, so that players who leave do not stall the game
+const QUICK_MATCH_TURN_SECS: u64 = 60;
+
 /**
  * Directory structures
  */
@@ -22,30 +28,66 @@ use crate::{
 struct Directory {
     /// ht: maps game ids to the game task's mpsc tx channel
     ht: HashMap<GameId, GameTaskTx>,
+    /// public games that wait for players (set by the game tasks)
+    listings: HashMap<GameId, srvcli::GameListing>,
+    /// quick-match queue: players waiting for a game, by number of players
+    queue: HashMap<u8, Vec<oneshot::Sender<(GameId, GameTaskTx)>>>,
+    /// summaries of the running games (set by the game tasks)
+    summaries: HashMap<GameId, GameSummary>,
+    /// counters of the games that are no longer running
+    counters: Counters,
+    started: Instant,
     self_rx: DirTaskRx,
     self_tx: DirTaskTx,
+    /// persistent storage for games
+    store: Store,
+    /// idle timeouts for games
+    timeouts: GameTimeouts,
 }
 
 impl Directory {
-    pub fn new(rx: DirTaskRx, tx: DirTaskTx) -> Directory {
+    pub fn new(rx: DirTaskRx, tx: DirTaskTx, store: Store, timeouts: GameTimeouts) -> Directory {
         Directory {
             ht: HashMap::new(),
+            listings: HashMap::new(),
+            queue: HashMap::new(),
+            summaries: HashMap::new(),
+            counters: Counters::default(),
+            started: Instant::now(),
             self_rx: rx,
             self_tx: tx,
+            store: store,
+            timeouts: timeouts,
+        }
+    }
+
+    // restore the games that were running when the backend stopped
+    pub fn restore_games(&mut self) {
+        self.store.prune();
+        for (gid, snap) in self.store.load_running() {
+            match spawn_restored_game_task(gid, snap, self.self_tx.clone(), self.store.clone(), self.timeouts) {
+                Ok(game_tx) => {
+                    self.ht.insert(gid, game_tx);
+                },
+                Err(x) => log::error!("Failed to restore game {}: {}", gid.to_string(), x),
+            }
         }
+        log::info!("Restored {} games", self.ht.len());
     }
 
     // create a new game:
     //  - add an entry to the directory
     //  - spawn a task for the game with a mpsc channel, and keep the tx end in the table
-    pub fn new_game(&mut self, cfg: GameConfig, rep_tx: oneshot::Sender<srvcli::CreateRep>) {
+    // On error, rep_tx is dropped.
+    pub fn new_game(&mut self, cfg: GameConfig, rep_tx: Option<oneshot::Sender<srvcli::CreateRep>>) -> Result<(GameId, GameTaskTx), String> {
         loop {
             let gid = GameId::new_random();
             match self.ht.entry(gid) {
                 Entry::Occupied(_) => continue, // retry
                 Entry::Vacant(e) => {
-                    let game_tx = spawn_game_task(gid, cfg, self.self_tx.clone(), rep_tx);
-                    e.insert(game_tx);
+                    let game_tx = spawn_game_task(gid, cfg, self.self_tx.clone(), self.store.clone(), self.timeouts, rep_tx)?;
+                    e.insert(game_tx.clone());
+                    self.counters.games_created += 1;
                     /*
                     let (game_tx, game_rx) = mpsc::channel::<GameReq>(1024);
                     let game = Game::new(gid, cfg, game_rx, self.self_tx.clone());
@@ -53,7 +95,7 @@ impl Directory {
                     let _game_task = tokio::spawn(game.task(rep_tx));
                     e.insert(game_tx);
                     */
-                    return;
+                    return Ok((gid, game_tx));
                 }
             }
         }
@@ -63,6 +105,78 @@ impl Directory {
         let ret = self.ht.remove(&gid);
         if ret.is_none() {
             log::warn!("Request to drop nonexistent game ({}) ignored", gid.to_string());
+        } else {
+            self.counters.games_finished += 1;
+        }
+        if let Some(summary) = self.summaries.remove(&gid) {
+            self.counters.actions_applied += summary.actions_applied;
+            self.counters.invalid_actions += summary.invalid_actions;
+        }
+        self.listings.remove(&gid);
+        self.store.prune();
+    }
+
+    pub fn set_listing(&mut self, gid: GameId, listing: Option<srvcli::GameListing>) {
+        match listing {
+            Some(x) if self.ht.contains_key(&gid) => { self.listings.insert(gid, x); },
+            _ => { self.listings.remove(&gid); },
+        }
+    }
+
+    pub fn list_games(&self, rep_tx: oneshot::Sender<Vec<srvcli::GameListing>>) {
+        let mut rep : Vec<srvcli::GameListing> = self.listings.values().cloned().collect();
+        rep.sort_by(|a, b| a.game_id.cmp(&b.game_id));
+        if let Err(_x) = rep_tx.send(rep) {
+            log::error!("Error sending game listings")
+        }
+    }
+
+    pub fn set_summary(&mut self, gid: GameId, summary: GameSummary) {
+        if self.ht.contains_key(&gid) {
+            self.summaries.insert(gid, summary);
+        }
+    }
+
+    pub fn stats(&self, rep_tx: oneshot::Sender<ServerStats>) {
+        let rep = ServerStats::new(self.started, &self.counters, &self.summaries);
+        if let Err(_x) = rep_tx.send(rep) {
+            log::error!("Error sending server statistics")
+        }
+    }
+
+    // add a player to the quick-match queue. Once there are enough players, create a game for
+    // them and send them its handle. Players that gave up waiting are dropped from the queue.
+    pub fn quick_match(&mut self, nplayers: u8, rep_tx: oneshot::Sender<(GameId, GameTaskTx)>) {
+        let waiting = self.queue.entry(nplayers).or_insert(vec![]);
+        waiting.retain(|tx| !tx.is_closed());
+        waiting.push(rep_tx);
+        if waiting.len() < (nplayers as usize) {
+            return;
+        }
+
+        let players = std::mem::take(waiting);
+        let cfg = GameConfig {
+            nplayers: nplayers,
+            target_score: Some(core::game::DEFAULT_TARGET_SCORE),
+            rules: core::RuleSet::default(),
+            turn_timer: Some(TurnTimer { secs: QUICK_MATCH_TURN_SECS, policy: TimeoutPolicy::AutoPlay }),
+            // NB: if a player does not show up, others can take the seat
+            public: true,
+            autostart: true,
+            debug: None,
+        };
+        let (gid, game_tx) = match self.new_game(cfg, None) {
+            Ok(x) => x,
+            Err(x) => {
+                log::error!("Quick match: failed to create game: {}", x);
+                return;
+            }
+        };
+        log::info!("Quick match: created game {} for {} players", gid.to_string(), nplayers);
+        for tx in players {
+            if let Err(_x) = tx.send((gid, game_tx.clone())) {
+                log::info!("Quick-match player left before joining game {}", gid.to_string());
+            }
         }
     }
 
@@ -79,7 +193,9 @@ impl Directory {
         while let Some(cmd) = self.self_rx.recv().await {
             match cmd {
                 DirReq::CreateGame(cfg, rep_tx) => {
-                    self.new_game(cfg, rep_tx);
+                    if let Err(x) = self.new_game(cfg, Some(rep_tx)) {
+                        log::error!("Failed to create game: {}", x);
+                    }
                 }
 
                 DirReq::GetGameHandle(gid, rep_tx) => {
@@ -89,15 +205,40 @@ impl Directory {
                 DirReq::DropGame(gid) => {
                     self.drop_game(gid);
                 }
+
+                DirReq::SetListing(gid, listing) => {
+                    self.set_listing(gid, listing);
+                }
+
+                DirReq::ListGames(rep_tx) => {
+                    self.list_games(rep_tx);
+                }
+
+                DirReq::QuickMatch(nplayers, rep_tx) => {
+                    self.quick_match(nplayers, rep_tx);
+                }
+
+                DirReq::SetSummary(gid, summary) => {
+                    self.set_summary(gid, summary);
+                }
+
+                DirReq::WsError => {
+                    self.counters.websocket_errors += 1;
+                }
+
+                DirReq::Stats(rep_tx) => {
+                    self.stats(rep_tx);
+                }
             }
         }
     }
 }
 
 
-pub fn spawn_directory_task() -> DirTaskTx {
+pub fn spawn_directory_task(store: Store, timeouts: GameTimeouts) -> DirTaskTx {
     let (dir_tx, dir_rx) = tokio::sync::mpsc::channel::<DirReq>(1024);
-    let dir = Directory::new(dir_rx, dir_tx.clone());
+    let mut dir = Directory::new(dir_rx, dir_tx.clone(), store, timeouts);
+    dir.restore_games();
     let _dir_task = tokio::spawn(dir.task());
     dir_tx
 }
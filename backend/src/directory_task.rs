@@ -8,7 +8,8 @@ use tokio::sync::{oneshot, mpsc};
 
 use core::srvcli;
 
-use crate::game::{GameConfig, GameId};
+use crate::game::{GameConfig, GameId, GameSummary};
+use crate::stats::ServerStats;
 use crate::game_task::GameTaskTx;
 
 
@@ -21,6 +22,19 @@ pub enum DirReq {
     GetGameHandle(GameId, oneshot::Sender<Option<GameTaskTx>>),
     /// Drop the game
     DropGame(GameId),
+    /// Set (or remove, if None) the public listing of a game
+    SetListing(GameId, Option<srvcli::GameListing>),
+    /// Request the listings of the public games
+    ListGames(oneshot::Sender<Vec<srvcli::GameListing>>),
+    /// Wait in the quick-match queue for a game with the given number of players. The reply is
+    /// sent once enough players are waiting.
+    QuickMatch(u8, oneshot::Sender<(GameId, GameTaskTx)>),
+    /// Set the summary of a game (see stats.rs)
+    SetSummary(GameId, GameSummary),
+    /// A player task had a websocket error
+    WsError,
+    /// Request the server statistics
+    Stats(oneshot::Sender<ServerStats>),
 }
 
 /// A channel to send requests to the directory task
@@ -10,38 +10,131 @@
 // PlayerId is the id that identifies a player in the LobbyInfo structure. If the id for a player
 // changes, we need to send LobbyUpdate.
 //
-// As players are registered, we add them to a vector. For now, players cannot be removed, only
-// disconnected. The index of a player in the vector is their PlayerId. If we choose to remove
-// players, we need to update lobby info.
+// As players are registered, we add them to a vector. Human players cannot be removed, only
+// disconnected. The index of a player in the vector is their PlayerId. Bots can be removed while
+// in the lobby, in which case we update the lobby info.
+//
+// Bots are players without a task. They are always connected. Their moves are computed in a
+// separate task while the game task keeps handling requests (see schedule_bot_move()).
+//
+// Human players get a session token when they register. A disconnected player can use it to
+// reconnect to their seat, in which case they get a new PlayerTaskId. If all human players are
+// disconnected, the game waits for one of them to reconnect before terminating.
+//
+// Spectators are kept in a separate vector: they do not have a seat, and are not considered when
+// checking whether players are ready or connected. They get SpectatorUpdate messages whenever the
+// players get a LobbyUpdate or a GameUpdate, and any requests they send are ignored.
+//
+// If the game has a turn timer, the game task keeps the deadline of the current turn (see
+// TurnDeadline), and applies the timeout policy if the player does not play in time. Bots are not
+// timed.
+//
+// Chat messages are relayed to all connected players. We keep the last CHAT_SCROLLBACK of them,
+// and replay them to players when they (re)connect.
+//
+// Public games are listed in the directory while they wait for players (see Game::listing()). The
+// game task updates its listing whenever it changes. Quick-match games (see directory.rs) start on
+// their own once all seats are taken (autostart).
+//
+// The game task reports a GameSummary to the directory whenever it changes, for the server
+// statistics (see stats.rs).
+//
+// Games that are idle terminate on their own (see GameTimeouts and Game::deadline()). Connected
+// players and spectators get a websocket close with the reason.
+//
+// When the game changes, the game task saves a GameSnapshot to the store, so that it can be restored
+// if the backend restarts (see store.rs and Game::save_snapshot()). Restored games start with all
+// players disconnected.
 
 use std::collections::{VecDeque};
 
 use tokio::sync::{oneshot, mpsc};
+use tokio::time::{Duration, Instant};
 use core::srvcli;
 
 use crate::{
     game_task::{GameReq, GameTaskRx, GameTaskTx, PlayerTaskId},
     player_task::{PlayerTaskMsg, PlayerTaskTx},
     directory_task::{DirTaskTx, DirReq},
+    store::Store,
 };
 use rand::SeedableRng;
 type Rng = rand_pcg::Pcg64;
 
+/// Time a bot waits before playing, so that humans can follow the game
+const BOT_DELAY: Duration = Duration::from_millis(750);
+
+/// Number of chat messages we keep for players that (re)connect
+const CHAT_SCROLLBACK: usize = 64;
+
+/// Idle timeouts, after which a game terminates
+#[derive(Debug, Clone, Copy)]
+pub struct GameTimeouts {
+    /// game was created, but no player ever joined
+    pub unjoined: Duration,
+    /// no requests from the players in the lobby
+    pub lobby_idle: Duration,
+    /// no actions played during a game (e.g., a stalled turn). Chat does not count.
+    pub game_idle: Duration,
+    /// all players are disconnected, and nobody reconnected (e.g., after reloading the page)
+    pub reconnect: Duration,
+}
+
+impl Default for GameTimeouts {
+    fn default() -> Self {
+        GameTimeouts {
+            unjoined: Duration::from_secs(10*60),
+            lobby_idle: Duration::from_secs(30*60),
+            game_idle: Duration::from_secs(60*60),
+            reconnect: Duration::from_secs(60),
+        }
+    }
+}
+
+impl GameTimeouts {
+    /// Configure the timeouts from the environment (values in seconds):
+    ///  DILOTI_UNJOINED_TIMEOUT, DILOTI_LOBBY_IDLE_TIMEOUT, DILOTI_GAME_IDLE_TIMEOUT,
+    ///  DILOTI_RECONNECT_TIMEOUT
+    pub fn from_env() -> Result<GameTimeouts, String> {
+        fn get(var: &str, default: Duration) -> Result<Duration, String> {
+            match std::env::var(var) {
+                Err(_) => Ok(default),
+                Ok(x) => x.parse::<u64>()
+                    .map(Duration::from_secs)
+                    .map_err(|e| format!("invalid {} ({}): {}", var, x, e)),
+            }
+        }
+
+        let def = GameTimeouts::default();
+        Ok(GameTimeouts {
+            unjoined: get("DILOTI_UNJOINED_TIMEOUT", def.unjoined)?,
+            lobby_idle: get("DILOTI_LOBBY_IDLE_TIMEOUT", def.lobby_idle)?,
+            game_idle: get("DILOTI_GAME_IDLE_TIMEOUT", def.game_idle)?,
+            reconnect: get("DILOTI_RECONNECT_TIMEOUT", def.reconnect)?,
+        })
+    }
+}
+
 /**
  * Backend-side game structures
  */
 
-pub use crate::chararr_id::GameId;
+pub use crate::chararr_id::{GameId, SessionToken};
 
 #[derive(Debug, Clone)]
 pub struct GameDebug {
-    hand: core::Deck,
-    table: core::Table,
+    position: core::repr::GameRepr,
 }
 
 #[derive(Debug, Clone)]
 pub struct GameConfig {
     pub nplayers: u8,
+    pub target_score: Option<usize>,
+    pub rules: core::RuleSet,
+    pub turn_timer: Option<core::TurnTimer>,
+    pub public: bool,
+    /// start the game as soon as all the players are connected
+    pub autostart: bool,
     pub debug: Option<GameDebug>,
 }
 
@@ -50,55 +143,308 @@ struct Player {
     tpos: srvcli::PlayerTpos,
     name: String,
     task: Option<(PlayerTaskId, PlayerTaskTx)>, // disconnected player have None
+    token: Option<SessionToken>, // bots have None
+    bot: Option<Box<dyn core::Bot + Send>>,
+    bot_kind: Option<core::BotKind>, // so that we can re-create the bot when restoring
 }
 
-#[derive(Debug, Clone)]
-enum State {
+struct Spectator {
+    name: String,
+    task: (PlayerTaskId, PlayerTaskTx),
+    reveal: bool, // reveal the hands of the players when a game is done
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct ChatEntry {
+    from: srvcli::PlayerId,
+    text: String,
+    ts: u64,
+}
+
+impl ChatEntry {
+    fn to_msg(&self) -> srvcli::ServerMsg {
+        srvcli::ServerMsg::Chat { from: self.from, text: self.text.clone(), ts: self.ts }
+    }
+}
+
+/// Bot move that is being computed: the task returns the bot and its action (see
+/// Game::schedule_bot_move()). The bot is None if its task failed.
+struct BotMove {
+    tpos: srvcli::PlayerTpos,
+    task: tokio::task::JoinHandle<Option<(Box<dyn core::Bot + Send>, Option<core::PlayerAction>)>>,
+}
+
+/// What the game task waits for
+enum TaskEvent {
+    Req(GameReq),
+    BotMove(Option<(Box<dyn core::Bot + Send>, Option<core::PlayerAction>)>),
+    TurnTimeout,
+    Idle,
+    Closed,
+}
+
+/// Deadline of the current turn
+struct TurnDeadline {
+    turn: (usize, usize), // (game, action) index, so that we notice when the turn changes
+    at: Instant,
+    ts_ms: u64, // milliseconds since the UNIX epoch, for the clients
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum State {
     InLobby,
     InGame,
 }
 
+/// Summary of a game for the server statistics
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSummary {
+    pub state: State,
+    pub nplayers: u8,
+    pub players_connected: usize, // connected human players
+    pub spectators: usize,
+    pub actions_applied: u64, // since the game task started
+    pub invalid_actions: u64,
+    pub created: Instant,
+}
+
+/// Persistent state of a player
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct PlayerSnapshot {
+    tpos: srvcli::PlayerTpos,
+    name: String,
+    token: Option<String>,
+    bot_kind: Option<core::BotKind>,
+}
+
+/// Persistent state of a game: everything needed to restore it after a restart
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GameSnapshot {
+    nplayers: u8,
+    state: State,
+    players: Vec<PlayerSnapshot>, // in PlayerId order
+    game: core::Game<Rng>,
+    #[serde(default)]
+    chat: VecDeque<ChatEntry>,
+    #[serde(default)]
+    turn_timer: Option<core::TurnTimer>,
+    #[serde(default)]
+    public: bool,
+    #[serde(default)]
+    autostart: bool,
+}
+
+/// What identifies a change in the persistent state of a game (see Game::save_snapshot())
+#[derive(Debug, Clone, PartialEq)]
+struct SnapshotKey {
+    state: State,
+    players: Vec<PlayerSnapshot>,
+    turn_timer: Option<core::TurnTimer>,
+    public: bool,
+    autostart: bool,
+    // (games in the history, first player of the current game, actions in the current game)
+    position: (usize, Option<srvcli::PlayerTpos>, usize),
+}
+
 struct Game {
     players: Vec<Player>, // player players[0] is admin
+    spectators: Vec<Spectator>,
+    chat: VecDeque<ChatEntry>, // recent chat messages
     self_rx: GameTaskRx,
     dir_tx: DirTaskTx,
     gid: GameId,
     state: State,
     curr_game: core::Game<Rng>,
     nplayers: u8,
+    store: Store,
+    timeouts: GameTimeouts,
+    turn_timer: Option<core::TurnTimer>,
+    turn_deadline: Option<TurnDeadline>,
+    bot_move: Option<BotMove>,
+    public: bool,
+    autostart: bool,
+    listing: Option<srvcli::GameListing>, // last listing sent to the directory
+    summary: Option<GameSummary>, // last summary sent to the directory
+    saved: Option<SnapshotKey>, // key of the last snapshot saved to the store
+    actions_applied: u64,
+    invalid_actions: u64,
+    created: Instant,
+    last_activity: Instant, // last request from a player (including disconnects)
+    last_action: Instant, // last action applied to the game (or start of the game)
 
     next_player_task_id: usize,
     available_tpos: VecDeque<srvcli::PlayerTpos>,
 }
 
 impl Game {
-    pub fn new(gid: GameId, cfg: GameConfig, self_rx: GameTaskRx, dir_tx: DirTaskTx) -> Game {
+    pub fn new(
+        gid: GameId,
+        cfg: GameConfig,
+        self_rx: GameTaskRx,
+        dir_tx: DirTaskTx,
+        store: Store,
+        timeouts: GameTimeouts,
+    ) -> Result<Game, String> {
 
         let rng = Rng::from_rng(rand::rngs::OsRng).expect("unable to initalize RNG");
         let nplayers = cfg.nplayers;
-        let game = match (cfg.nplayers, cfg.debug) {
-            (1, None)       => core::Game::new_1p(rng),
-            (2, None)       => core::Game::new_2p(rng),
-            (4, None)       => core::Game::new_4p(rng),
-            (x, None)       => panic!("Incorrect number of players: {:?}", x),
-            (1, Some(dbg))  => core::Game::new_1p_debug(rng, dbg.table, dbg.hand),
-            (_x, _)          => panic!("Debugging mode allowed only for single player (for now)."),
+        let mut game = match (cfg.nplayers, cfg.debug) {
+            (1, None) | (2, None) | (4, None) => core::Game::new_with_rules(nplayers as usize, cfg.rules, rng),
+            (x, None)       => return Err(format!("Incorrect number of players: {}", x)),
+            (_, Some(dbg))  => {
+                let mut game = dbg.position.parse(cfg.rules, rng).map_err(|x| format!("Invalid debug position: {}", x))?;
+                // every new game starts from the same position
+                game.fix_init();
+                game
+            },
         };
+        game.set_target_score(cfg.target_score);
 
-        Game {
+        Ok(Game {
             gid: gid,
             players: vec![],
+            spectators: vec![],
+            chat: VecDeque::new(),
             self_rx: self_rx,
             dir_tx: dir_tx,
             state: State::InLobby,
             curr_game: game,
             nplayers: nplayers,
+            store: store,
+            timeouts: timeouts,
+            turn_timer: cfg.turn_timer,
+            turn_deadline: None,
+            bot_move: None,
+            public: cfg.public,
+            autostart: cfg.autostart,
+            listing: None,
+            summary: None,
+            saved: None,
+            actions_applied: 0,
+            invalid_actions: 0,
+            created: Instant::now(),
+            last_activity: Instant::now(),
+            last_action: Instant::now(),
             next_player_task_id: 0,
             available_tpos: (0..nplayers).map(|x| srvcli::PlayerTpos(x)).collect(),
 
+        })
+    }
+
+    /// Restore a game from a snapshot. All human players are disconnected.
+    pub fn restore(
+        gid: GameId,
+        snap: GameSnapshot,
+        self_rx: GameTaskRx,
+        dir_tx: DirTaskTx,
+        store: Store,
+        timeouts: GameTimeouts,
+    ) -> Result<Game, String> {
+        let nplayers = snap.nplayers;
+        if snap.game.nplayers() != (nplayers as usize) || snap.players.len() > (nplayers as usize) {
+            return Err("invalid number of players".to_string());
+        }
+        snap.game.check_invariants()?;
+
+        let mut available_tpos: VecDeque<srvcli::PlayerTpos> = (0..nplayers).map(|x| srvcli::PlayerTpos(x)).collect();
+        let mut players = vec![];
+        for ps in snap.players {
+            let pos = available_tpos.iter().position(|x| *x == ps.tpos).ok_or("invalid player position")?;
+            available_tpos.remove(pos);
+            let token = match ps.token {
+                Some(t) => Some(SessionToken::from_string(&t).ok_or("invalid session token")?),
+                None => None,
+            };
+            players.push(Player {
+                tpos: ps.tpos,
+                name: ps.name,
+                task: None,
+                token: token,
+                bot: ps.bot_kind.map(|kind| kind.mk_bot(rand::random())),
+                bot_kind: ps.bot_kind,
+            });
+        }
+
+        Ok(Game {
+            gid: gid,
+            players: players,
+            spectators: vec![],
+            chat: snap.chat,
+            self_rx: self_rx,
+            dir_tx: dir_tx,
+            state: snap.state,
+            curr_game: snap.game,
+            nplayers: nplayers,
+            store: store,
+            timeouts: timeouts,
+            turn_timer: snap.turn_timer,
+            turn_deadline: None,
+            bot_move: None,
+            public: snap.public,
+            autostart: snap.autostart,
+            listing: None,
+            summary: None,
+            saved: None,
+            actions_applied: 0,
+            invalid_actions: 0,
+            created: Instant::now(),
+            last_activity: Instant::now(),
+            last_action: Instant::now(),
+            next_player_task_id: 0,
+            available_tpos: available_tpos,
+        })
+    }
+
+    fn player_snapshots(&self) -> Vec<PlayerSnapshot> {
+        self.players.iter().map(|p| PlayerSnapshot {
+            tpos: p.tpos,
+            name: p.name.clone(),
+            token: p.token.map(|t| t.to_string()),
+            bot_kind: p.bot_kind,
+        }).collect()
+    }
+
+    fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            nplayers: self.nplayers,
+            state: self.state.clone(),
+            players: self.player_snapshots(),
+            game: self.curr_game.clone(),
+            chat: self.chat.clone(),
+            turn_timer: self.turn_timer,
+            public: self.public,
+            autostart: self.autostart,
         }
     }
 
+    fn snapshot_key(&self) -> SnapshotKey {
+        let history = self.curr_game.history();
+        let position = (
+            self.curr_game.games_played(),
+            history.last().map(|r| r.first_player),
+            history.last().map_or(0, |r| r.actions.len()),
+        );
+        SnapshotKey {
+            state: self.state.clone(),
+            players: self.player_snapshots(),
+            turn_timer: self.turn_timer,
+            public: self.public,
+            autostart: self.autostart,
+            position: position,
+        }
+    }
+
+    /// Save a snapshot to the store, if the game changed since the last one. Chat messages alone
+    /// do not count as a change: they are saved with the next snapshot.
+    fn save_snapshot(&mut self) {
+        let key = self.snapshot_key();
+        if self.saved.as_ref() == Some(&key) {
+            return;
+        }
+        self.store.save(&self.gid, self.snapshot());
+        self.saved = Some(key);
+    }
+
     #[allow(dead_code)]
     fn ndisconnected(&self) -> usize {
         self.players.iter().filter(|p| p.is_disconnected()).count()
@@ -108,8 +454,30 @@ impl Game {
         self.players.iter().filter(|p| p.is_connected()).count()
     }
 
+    /// Are all the human players disconnected?
     fn all_disconnected(&self) -> bool {
-        self.players.iter().all(|p| p.is_disconnected())
+        self.players.iter().filter(|p| !p.is_bot()).all(|p| p.is_disconnected())
+    }
+
+    /// Did any human player register?
+    fn has_humans(&self) -> bool {
+        self.players.iter().any(|p| !p.is_bot())
+    }
+
+    /// When the game will terminate if it gets no requests, and why
+    fn deadline(&self) -> (Instant, &'static str) {
+        let t = &self.timeouts;
+        if !self.has_humans() {
+            (self.created + t.unjoined, "nobody joined the game")
+        } else if self.all_disconnected() {
+            (self.last_activity + t.reconnect, "all players are disconnected")
+        } else {
+            match self.state {
+                State::InLobby => (self.last_activity + t.lobby_idle, "lobby was idle for too long"),
+                // NB: chat and other requests do not keep a game alive, only actions do
+                State::InGame => (self.last_action + t.game_idle, "game was idle for too long"),
+            }
+        }
     }
 
     pub fn new_ptid(&mut self) -> PlayerTaskId {
@@ -118,30 +486,72 @@ impl Game {
         ret
     }
 
+    fn unique_name(&self, mut player_name: String) -> String {
+        while self.players.iter().find(|p| p.name == player_name).is_some() {
+            player_name.push('_')
+        }
+        player_name
+    }
+
     /// Add a new player, and return its task id
     fn new_player(
         &mut self,
         ptx: PlayerTaskTx,
-        mut player_name: String
+        player_name: String
     ) -> PlayerTaskId {
 
-        while self.players.iter().find(|p| p.name == player_name).is_some() {
-            player_name.push('_')
-        }
-
-        // TODO: modify player_name to be unique if it is not
+        let player_name = self.unique_name(player_name);
         let ptid = self.new_ptid();
         let tpos = self.available_tpos.pop_front().expect("Available tpos");
         let player = Player {
             tpos: tpos,
             name: player_name,
             task: Some((ptid.clone(), ptx)),
+            token: Some(SessionToken::new_random()),
+            bot: None,
+            bot_kind: None,
         };
         self.players.push(player);
 
        ptid
     }
 
+    fn add_bot(&mut self, kind: core::BotKind) -> Result<(), String> {
+        if self.players.len() >= (self.nplayers as usize) {
+            return Err("No free positions".to_string());
+        }
+
+        let name = self.unique_name(kind.to_string());
+        let tpos = self.available_tpos.pop_front().expect("Available tpos");
+        let player = Player {
+            tpos: tpos,
+            name: name,
+            task: None,
+            token: None,
+            bot: Some(kind.mk_bot(rand::random())),
+            bot_kind: Some(kind),
+        };
+        self.players.push(player);
+        Ok(())
+    }
+
+    fn remove_bot(&mut self, tpos: srvcli::PlayerTpos) -> Result<(), String> {
+        let idx = self.players.iter().position(|p| p.tpos == tpos).ok_or("Failed to find tpos")?;
+        if !self.players[idx].is_bot() {
+            return Err("Player is not a bot".to_string());
+        }
+
+        self.players.remove(idx);
+        // PlayerIds after idx have changed
+        for entry in self.chat.iter_mut().filter(|e| e.from.0 > idx) {
+            entry.from.0 -= 1;
+        }
+        // keep available positions sorted
+        let pos = self.available_tpos.iter().position(|x| x.0 > tpos.0).unwrap_or(self.available_tpos.len());
+        self.available_tpos.insert(pos, tpos);
+        Ok(())
+    }
+
     fn player_by_ptid(&self, arg: PlayerTaskId) -> Option<&Player> {
         self.players.iter().position(|p: &Player|
             p.task.as_ref().map_or(false, |(ptid, _tx)| *ptid == arg)
@@ -158,6 +568,14 @@ impl Game {
         )
     }
 
+    fn is_spectator(&self, arg: &PlayerTaskId) -> bool {
+        self.spectators.iter().any(|s| s.task.0 == *arg)
+    }
+
+    fn spectator_names(&self) -> Vec<String> {
+        self.spectators.iter().map(|s| s.name.clone()).collect()
+    }
+
     fn player_pids_iter(&self) -> impl Iterator<Item=srvcli::PlayerId> {
         (0..self.players.len()).map(|i| srvcli::PlayerId(i))
     }
@@ -187,6 +605,7 @@ impl Game {
                     tpos: p.tpos.clone(),
                     name: p.name.clone(),
                     connected: p.is_connected(),
+                    bot: p.is_bot(),
                 }
             }).collect()
     }
@@ -195,6 +614,7 @@ impl Game {
         'outer: loop {
             let players = self.mk_players_info();
             let nplayers = self.nplayers;
+            let spectators = self.spectator_names();
             'inner: for pid in self.player_pids_iter() {
                 let player = self.player_by_pid_mut(pid);
                 if player.is_disconnected() {
@@ -204,6 +624,7 @@ impl Game {
                     nplayers: nplayers,
                     players: players.clone(),
                     self_id: pid,
+                    spectators: spectators.clone(),
                 };
 
                 // If we fail to sent, it means that another player was disconnected. So we restart
@@ -218,27 +639,199 @@ impl Game {
             // sent all updates successfully without a disconnect, we are done
             break 'outer;
         }
+
+        self.send_spectator_updates().await;
+    }
+
+    /// Send the current state to all spectators. Spectators that we fail to reach are dropped.
+    async fn send_spectator_updates(&mut self) {
+        let players = self.mk_players_info();
+        let names = self.spectator_names();
+        let mut failed = vec![];
+        for (i, spectator) in self.spectators.iter_mut().enumerate() {
+            let game = match self.state {
+                State::InLobby => None,
+                State::InGame => {
+                    let mut view = self.curr_game.get_spectator_game_view(spectator.reveal);
+                    view.turn_deadline = self.turn_deadline.as_ref().map(|d| d.ts_ms);
+                    Some(view)
+                },
+            };
+            let info = srvcli::SpectatorInfo {
+                players: players.clone(),
+                nplayers: self.nplayers,
+                spectators: names.clone(),
+                game: game,
+            };
+            let msg = PlayerTaskMsg::ForwardToClient(srvcli::ServerMsg::SpectatorUpdate(info));
+            if let Err(x) = spectator.task.1.send(msg).await {
+                log::warn!("Error sending msg to spectator task {:?}: {:?}", spectator.task.0, x);
+                failed.push(i);
+            }
+        }
+
+        for i in failed.into_iter().rev() {
+            self.spectators.remove(i);
+        }
     }
 
     async fn send_game_update_to_players(&mut self) -> Result<(), ()> {
+        self.update_turn_deadline();
         let mut ret = Ok(());
         // NB: can issue them concurrently and await on all of them just once?
-        for player in self.players.iter_mut().filter(|p| p.is_connected()) {
-            let tpos = player.tpos;
-            let view = self.curr_game.get_player_game_view(tpos);
+        let views: Vec<_> = self.players.iter().map(|p| self.player_game_view(p.tpos)).collect();
+        for (player, view) in self.players.iter_mut().zip(views).filter(|(p, _)| p.is_connected()) {
             let msg = srvcli::ServerMsg::GameUpdate(view);
             if let Err(()) = player.send_cli_or_disconnect(msg).await {
                 ret = Err(());
             }
         }
 
+        self.send_spectator_updates().await;
         ret
     }
 
+    fn player_game_view(&self, tpos: srvcli::PlayerTpos) -> core::PlayerGameView {
+        let mut view = self.curr_game.get_player_game_view(tpos);
+        view.turn_deadline = self.turn_deadline.as_ref().map(|d| d.ts_ms);
+        view
+    }
+
+    /// Start the clock if a new turn of a human player started, or stop it if there is no such
+    /// turn
+    fn update_turn_deadline(&mut self) {
+        let timer = match (&self.state, self.turn_timer) {
+            (State::InGame, Some(x)) => x,
+            _ => {
+                self.turn_deadline = None;
+                return;
+            }
+        };
+
+        let human_turn = match self.curr_game.state() {
+            core::GameState::NextTurn(tpos) => self.players.iter().any(|p| p.tpos == *tpos && !p.is_bot()),
+            _ => false,
+        };
+        if !human_turn {
+            self.turn_deadline = None;
+            return;
+        }
+
+        let history = self.curr_game.history();
+        let turn = (self.curr_game.games_played(), history.last().map_or(0, |r| r.actions.len()));
+        if self.turn_deadline.as_ref().map_or(false, |d| d.turn == turn) {
+            return;
+        }
+
+        let dur = Duration::from_secs(timer.secs);
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        // NB: timers are validated when the game is created, but restored snapshots are not
+        self.turn_deadline = Instant::now().checked_add(dur).map(|at| TurnDeadline {
+            turn: turn,
+            at: at,
+            ts_ms: now_ms.saturating_add(timer.secs.saturating_mul(1000)),
+        });
+    }
+
+    /// The player whose turn it is did not play in time
+    async fn turn_timed_out(&mut self) -> Result<(), ()> {
+        self.turn_deadline = None;
+        let (tpos, policy) = match (self.curr_game.state(), self.turn_timer) {
+            (core::GameState::NextTurn(tpos), Some(timer)) => (*tpos, timer.policy),
+            _ => return Ok(()),
+        };
+
+        log::info!("Game {}: {} ran out of time ({:?})", self.gid.to_string(), tpos, policy);
+        if let Err(err) = self.curr_game.turn_timeout(tpos, policy) {
+            log::error!("Failed to apply turn timeout: {}", err);
+            return Ok(());
+        }
+
+        self.action_applied();
+        self.send_game_update_to_players().await
+    }
+
     fn players_ready(&self) -> bool {
         self.nconnected() == (self.nplayers as usize)
     }
 
+    /// Start an autostart game if all the players are there
+    async fn maybe_autostart(&mut self) {
+        if !self.autostart || !self.players_ready() {
+            return;
+        }
+
+        if let State::InLobby = self.state {
+            log::info!("Game {}: all players are here, starting", self.gid.to_string());
+            self.autostart = false;
+            self.state = State::InGame;
+            self.last_action = Instant::now();
+            if let Err(()) = self.send_game_update_to_players().await {
+                self.send_lobby_update_to_players().await;
+            }
+        }
+    }
+
+    /// Listing of the game in the public directory: public games that wait for players, and have
+    /// at least one connected player.
+    fn listing(&self) -> Option<srvcli::GameListing> {
+        let free_seats = (self.nplayers as usize) - self.players.len();
+        match self.state {
+            State::InLobby if self.public && free_seats > 0 && self.has_humans() && !self.all_disconnected() => (),
+            _ => return None,
+        }
+
+        Some(srvcli::GameListing {
+            game_id: self.gid.to_string(),
+            admin: self.players[0].name.clone(),
+            nplayers: self.nplayers,
+            free_seats: free_seats as u8,
+            target_score: self.curr_game.target_score(),
+            rules: self.curr_game.rules().clone(),
+            turn_timer: self.turn_timer,
+        })
+    }
+
+    fn summary(&self) -> GameSummary {
+        GameSummary {
+            state: self.state.clone(),
+            nplayers: self.nplayers,
+            players_connected: self.players.iter().filter(|p| !p.is_bot() && p.is_connected()).count(),
+            spectators: self.spectators.len(),
+            actions_applied: self.actions_applied,
+            invalid_actions: self.invalid_actions,
+            created: self.created,
+        }
+    }
+
+    /// Update the summary of the game in the directory, if it changed
+    async fn update_summary(&mut self) {
+        let summary = self.summary();
+        if Some(&summary) == self.summary.as_ref() {
+            return;
+        }
+
+        self.summary = Some(summary.clone());
+        if let Err(x) = self.dir_tx.send(DirReq::SetSummary(self.gid, summary)).await {
+            log::error!("Error updating game summary: {:?}", x);
+        }
+    }
+
+    /// Update the listing of the game in the directory, if it changed
+    async fn update_listing(&mut self) {
+        let listing = self.listing();
+        if listing == self.listing {
+            return;
+        }
+
+        self.listing = listing.clone();
+        if let Err(x) = self.dir_tx.send(DirReq::SetListing(self.gid, listing)).await {
+            log::error!("Error updating game listing: {:?}", x);
+        }
+    }
+
     fn swap_player_tpos(&mut self, tpos1: srvcli::PlayerTpos, tpos2: srvcli::PlayerTpos) -> Result<(), String> {
         let p1 = self.players.iter().position(|x| x.tpos == tpos1).ok_or("Failed to find tpos1")?;
         let p2 = self.players.iter().position(|x| x.tpos == tpos2).ok_or("Failed to find tpos2")?;
@@ -280,51 +873,232 @@ impl Game {
         };
 
         // success!
-        self.player_by_ptid_mut(ptid.clone())
-            .expect("valid ptid")
-            .send_task_or_disconnect(RegistrationResult(Ok(ptid.clone())))
-            .await
-            .map_or((), |_| ()); // ignore error
+        let player = self.player_by_ptid_mut(ptid.clone()).expect("valid ptid");
+        let token = player.token.expect("human player has a token").to_string();
+        let _ = player.send_task_or_disconnect(RegistrationResult(Ok(ptid.clone()))).await
+            .and(player.send_cli_or_disconnect(srvcli::ServerMsg::SessionToken(token)).await);
 
         self.send_lobby_update_to_players().await;
+        let idx = self.players.len() - 1;
+        self.send_chat_scrollback(idx).await;
     }
 
-    async fn apply_action(&mut self, tpid: PlayerTaskId, action: core::PlayerAction) -> Result<(), ()> {
-        let tpos = self.player_by_ptid(tpid.clone()).expect("valid tpid").tpos.clone();
-        let pview = self.curr_game.get_player_game_view(tpos);
+    /// Send the chat scrollback to a player that just (re)connected
+    async fn send_chat_scrollback(&mut self, idx: usize) {
+        let msgs: Vec<srvcli::ServerMsg> = self.chat.iter().map(|e| e.to_msg()).collect();
+        let player = &mut self.players[idx];
+        for msg in msgs {
+            if let Err(()) = player.send_cli_or_disconnect(msg).await {
+                break;
+            }
+        }
+    }
+
+    async fn chat(&mut self, ptid: PlayerTaskId, text: String) -> Result<(), ()> {
+        let idx = match self.players.iter().position(|p| p.task.as_ref().map_or(false, |t| t.0 == ptid)) {
+            Some(x) => x,
+            None => {
+                log::error!("Chat message from unknown player task {:?}. Ignoring.", ptid);
+                return Ok(());
+            }
+        };
+
+        let text: String = text.trim().chars().take(srvcli::CHAT_MAX_LEN).collect();
+        if text.len() == 0 {
+            return Ok(());
+        }
+
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let entry = ChatEntry {
+            from: srvcli::PlayerId(idx),
+            text: text,
+            ts: ts,
+        };
+
+        let mut ret = Ok(());
+        for player in self.players.iter_mut().filter(|p| p.is_connected()) {
+            if let Err(()) = player.send_cli_or_disconnect(entry.to_msg()).await {
+                ret = Err(());
+            }
+        }
+
+        self.chat.push_back(entry);
+        if self.chat.len() > CHAT_SCROLLBACK {
+            self.chat.pop_front();
+        }
+        ret
+    }
+
+    async fn register_spectator(&mut self, mut tx: PlayerTaskTx, name: String, reveal: bool) {
+        use PlayerTaskMsg::RegistrationResult;
+
+        let ptid = self.new_ptid();
+        if let Err(_x) = tx.send(RegistrationResult(Ok(ptid.clone()))).await {
+            log::warn!("Error sending registration result to spectator task");
+            return;
+        }
+
+        log::info!("Game {}: {} is watching", self.gid.to_string(), name);
+        self.spectators.push(Spectator {
+            name: name,
+            task: (ptid, tx),
+            reveal: reveal,
+        });
+        // NB: this also sends an update to the spectators
+        self.send_lobby_update_to_players().await;
+    }
+
+    async fn reconnect_player(&mut self, mut pl_tx: PlayerTaskTx, token_s: String) {
+        use PlayerTaskMsg::RegistrationResult;
 
-        // validate action
-        if let Err(errmsg) = action.validate(&pview) {
-            let player = self.player_by_ptid_mut(tpid).expect("valid tpid");
-            let msg = srvcli::ServerMsg::InvalidAction(errmsg);
-            return player.send_cli_or_disconnect(msg).await;
+        let token = SessionToken::from_string(&token_s);
+        let pos = self.players.iter().position(|p| token.is_some() && p.token == token);
+        let idx = match pos {
+            Some(idx) => idx,
+            None => {
+                let err = "Invalid session.".to_string();
+                if let Err(_x) = pl_tx.send(RegistrationResult(Err(err))).await {
+                    log::warn!("Error sending erroneous registration result to player task")
+                }
+                return;
+            }
+        };
+
+        // NB: if the player is still connected (e.g., the old connection has not timed out yet),
+        // we close the old connection. Its PlayerTaskTerminated message will be ignored.
+        let ptid = self.new_ptid();
+        let player = &mut self.players[idx];
+        log::info!("Game {}: player {} reconnected", self.gid.to_string(), player.name);
+        if let Some((old_ptid, mut old_tx)) = player.task.replace((ptid.clone(), pl_tx)) {
+            if let Err(x) = old_tx.send(PlayerTaskMsg::Close("Reconnected elsewhere".to_string())).await {
+                log::warn!("Error sending msg to old player task {:?}: {:?}", old_ptid, x);
+            }
         }
+        let _ = player.send_task_or_disconnect(RegistrationResult(Ok(ptid))).await;
+
+        self.send_lobby_update_to_players().await;
+        self.send_chat_scrollback(idx).await;
+        if let State::InGame = self.state {
+            let view = self.player_game_view(self.players[idx].tpos);
+            let player = &mut self.players[idx];
+            let _ = player.send_cli_or_disconnect(srvcli::ServerMsg::GameUpdate(view)).await;
+        }
+    }
+
+    async fn apply_action(&mut self, tpid: PlayerTaskId, action: core::PlayerAction) -> Result<(), ()> {
+        let tpos = match self.player_by_ptid(tpid.clone()) {
+            Some(p) => p.tpos,
+            None => {
+                log::error!("Action from unknown player task {:?}. Ignoring.", tpid);
+                return Ok(());
+            }
+        };
 
-        // apply action
-        let res = self.curr_game.apply_action(tpos, action);
-        if let Err(errmsg) = res {
-            log::error!("Applying action failed (but validation succeeded): {}", errmsg);
-            let player = self.player_by_ptid_mut(tpid).expect("valid tpid");
-            let msg = srvcli::ServerMsg::InvalidAction(errmsg);
-            return player.send_cli_or_disconnect(msg).await;
+        // validate and apply action
+        log::debug!("Game {}: {} plays {}", self.gid.to_string(), tpos, action);
+        if let Err(err) = self.curr_game.apply_action_mut(tpos, action) {
+            self.invalid_actions += 1;
+            let msg = srvcli::ServerMsg::InvalidAction(err);
+            return match self.player_by_ptid_mut(tpid) {
+                Some(player) => player.send_cli_or_disconnect(msg).await,
+                None => Ok(()),
+            };
         }
 
-        // action was applied successfully
-        let newgame = res.unwrap();
-        self.curr_game = newgame;
+        self.action_applied();
+        self.send_game_update_to_players().await
+    }
 
+    fn action_applied(&mut self) {
+        self.actions_applied += 1;
+        self.last_action = Instant::now();
         match self.curr_game.state() {
             core::GameState::NextTurn(_) => (),
             core::GameState::GameDone(_) => (),
+            core::GameState::MatchDone(_, _) => (),
             core::GameState::RoundDone => self.curr_game.new_round(),
         }
+    }
+
+    /// If it is a bot's turn, start computing its move. Bots (e.g., MCTS) might take a while to
+    /// decide, so they run in a blocking task and the game task keeps handling requests. The
+    /// result is handled in bot_moved().
+    fn schedule_bot_move(&mut self) {
+        if self.bot_move.is_some() {
+            return;
+        }
+
+        let tpos = match (&self.state, self.curr_game.state()) {
+            (State::InGame, core::GameState::NextTurn(tpos)) => *tpos,
+            _ => return,
+        };
+
+        let view = self.curr_game.get_player_game_view(tpos);
+        let player = self.players.iter_mut().find(|p| p.tpos == tpos).expect("player at tpos");
+        let mut bot = match player.bot.take() {
+            Some(bot) => bot,
+            None => return,
+        };
+
+        let task = tokio::spawn(async move {
+            let start = Instant::now();
+            let res = tokio::task::spawn_blocking(move || {
+                let action = bot.choose_action(&view);
+                (bot, action)
+            }).await;
+            // so that humans can follow the game
+            tokio::time::delay_until(start + BOT_DELAY).await;
+            res.ok()
+        });
+        self.bot_move = Some(BotMove { tpos: tpos, task: task });
+    }
+
+    /// A bot move was computed: put the bot back in its seat, and play its move. If the bot failed,
+    /// we re-create it and play for it.
+    async fn bot_moved(&mut self, res: Option<(Box<dyn core::Bot + Send>, Option<core::PlayerAction>)>) -> Result<(), ()> {
+        let tpos = self.bot_move.take().expect("pending bot move").tpos;
+        let player = self.players.iter_mut().find(|p| p.tpos == tpos).expect("player at tpos");
+        let action = match res {
+            Some((bot, action)) => {
+                player.bot = Some(bot);
+                action
+            },
+            None => {
+                log::error!("Bot at position {} failed. Re-creating it.", tpos);
+                player.bot = player.bot_kind.map(|kind| kind.mk_bot(rand::random()));
+                None
+            },
+        };
 
+        match self.curr_game.state() {
+            core::GameState::NextTurn(x) if *x == tpos => (),
+            _ => return Ok(()),
+        }
+
+        let action = match action {
+            Some(x) => x,
+            None => {
+                log::error!("Bot at position {} did not choose an action. Playing for it.", tpos);
+                self.curr_game.auto_action().expect("a legal action")
+            },
+        };
+
+        log::debug!("Game {}: {} (bot) plays {}", self.gid.to_string(), tpos, action);
+        if let Err(err) = self.curr_game.apply_action_mut(tpos, action) {
+            log::error!("Bot at position {} attempted an invalid action: {}. Playing for it.", tpos, err);
+            let action = self.curr_game.auto_action().expect("a legal action");
+            self.curr_game.apply_action_mut(tpos, action).expect("legal action");
+        }
+
+        self.action_applied();
         self.send_game_update_to_players().await
     }
 
     async fn handle_clireq(&mut self, ptid: PlayerTaskId, climsg: srvcli::ClientMsg) -> Result<(), ()> {
         use State::{InLobby, InGame};
-        use srvcli::ClientMsg::{StartGame, SwapTpos, PlayerAction};
+        use srvcli::ClientMsg::{StartGame, SwapTpos, PlayerAction, AddBot, RemoveBot, Chat};
 
         match (self.state.clone(), climsg) {
             (InLobby, SwapTpos(tpos1, tpos2)) => {
@@ -346,6 +1120,32 @@ impl Game {
                 Ok(())
             },
 
+            (InLobby, AddBot(kind)) => {
+                if !self.is_player_admin(ptid) {
+                    log::error!("Non-admin player attempted to add a bot. Ignoring.");
+                    return Ok(());
+                }
+
+                if let Err(x) = self.add_bot(kind) {
+                    log::error!("Failed to add bot {:?}: {}", kind, x);
+                }
+                self.send_lobby_update_to_players().await;
+                Ok(())
+            },
+
+            (InLobby, RemoveBot(tpos)) => {
+                if !self.is_player_admin(ptid) {
+                    log::error!("Non-admin player attempted to remove a bot. Ignoring.");
+                    return Ok(());
+                }
+
+                if let Err(x) = self.remove_bot(tpos) {
+                    log::error!("Failed to remove bot at tpos:{:?}: {}", tpos, x);
+                }
+                self.send_lobby_update_to_players().await;
+                Ok(())
+            },
+
             (st, StartGame) => {
                 if !self.is_player_admin(ptid) {
                     log::error!("Non-admin player attempted to start game. Ignoring.");
@@ -357,6 +1157,7 @@ impl Game {
                     return Ok(());
                 }
 
+                self.last_action = Instant::now();
                 match st {
                     InLobby => {
                         self.state = InGame;
@@ -368,6 +1169,12 @@ impl Game {
                         self.send_game_update_to_players().await
                     },
 
+                    // The match is over: start a new one
+                    InGame if self.curr_game.state().is_match_done() => {
+                        self.curr_game.next_match();
+                        self.send_game_update_to_players().await
+                    },
+
                     InGame => {
                         log::error!("Trying to start game but game not done. Ignoring.");
                         Ok(())
@@ -380,6 +1187,10 @@ impl Game {
                 self.apply_action(ptid, action).await
             },
 
+            (_, Chat(text)) => {
+                self.chat(ptid, text).await
+            },
+
             (st, msg) => {
                 log::error!("Received message: {:?} from client while state is {:?}. Ignoring.", msg, st);
                 Ok(())
@@ -387,15 +1198,90 @@ impl Game {
         }
     }
 
-    async fn task(mut self, rep_tx: oneshot::Sender<srvcli::CreateRep>) {
-        self.task_init(rep_tx).await;
+    /// Close the connections of all players and spectators, giving them the reason
+    async fn close_connections(&mut self, reason: &str) {
+        for p in self.players.iter_mut().filter(|p| p.task.is_some()) {
+            p.send_task_or_disconnect(PlayerTaskMsg::Close(reason.to_string())).await.unwrap_or(());
+        }
+        for s in self.spectators.iter_mut() {
+            if let Err(x) = s.task.1.send(PlayerTaskMsg::Close(reason.to_string())).await {
+                log::warn!("Error sending msg to spectator task {:?}: {:?}", s.task.0, x);
+            }
+        }
+    }
+
+    /// Game task. rep_tx is None for restored and quick-match games.
+    async fn task(mut self, rep_tx: Option<oneshot::Sender<srvcli::CreateRep>>) {
+        if let Some(rep_tx) = rep_tx {
+            self.task_init(rep_tx).await;
+        }
+        self.update_summary().await;
+        self.save_snapshot();
+
+        loop {
+            self.update_turn_deadline();
+            self.schedule_bot_move();
+            let (deadline, reason) = self.deadline();
+            let turn_deadline = self.turn_deadline.as_ref().map(|d| d.at).filter(|t| *t < deadline);
+
+            let ev = {
+                let rx = &mut self.self_rx;
+                let bot_pending = self.bot_move.is_some();
+                let bot_move = &mut self.bot_move;
+                tokio::select! {
+                    cmd = rx.recv() => cmd.map_or(TaskEvent::Closed, TaskEvent::Req),
+                    res = async { (&mut bot_move.as_mut().expect("pending bot move").task).await }, if bot_pending => {
+                        TaskEvent::BotMove(res.ok().flatten())
+                    },
+                    _ = tokio::time::delay_until(turn_deadline.unwrap_or(deadline)), if turn_deadline.is_some() => TaskEvent::TurnTimeout,
+                    _ = tokio::time::delay_until(deadline) => TaskEvent::Idle,
+                }
+            };
+
+            let cmd = match ev {
+                TaskEvent::Req(cmd) => cmd,
+                TaskEvent::Closed => break,
+                TaskEvent::Idle => {
+                    log::info!("Game {}: terminating: {}", self.gid.to_string(), reason);
+                    self.close_connections(reason).await;
+                    break
+                },
+                TaskEvent::TurnTimeout => {
+                    if let Err(()) = self.turn_timed_out().await {
+                        self.send_lobby_update_to_players().await;
+                    }
+                    self.update_summary().await;
+                    self.save_snapshot();
+                    continue;
+                },
+                TaskEvent::BotMove(res) => {
+                    if let Err(()) = self.bot_moved(res).await {
+                        self.send_lobby_update_to_players().await;
+                    }
+                    self.update_summary().await;
+                    self.save_snapshot();
+                    continue;
+                },
+            };
+
+            // spectators do not keep the game alive
+            match &cmd {
+                GameReq::ClientReq(ptid, _) |
+                GameReq::PlayerTaskTerminated(ptid) if self.is_spectator(ptid) => (),
+                GameReq::RegisterSpectator(..) => (),
+                _ => self.last_activity = Instant::now(),
+            }
 
-        while let Some(cmd) = self.self_rx.recv().await {
             match cmd {
                 GameReq::RegisterPlayer(pl_tx, name) => {
                     self.register_player(pl_tx, name).await;
+                    self.maybe_autostart().await;
                 },
 
+                GameReq::ClientReq(ptid, climsg) if self.is_spectator(&ptid) => {
+                    log::warn!("Received message: {:?} from spectator. Ignoring.", climsg);
+                }
+
                 GameReq::ClientReq(ptid, climsg) => {
                     if let Err(()) = self.handle_clireq(ptid, climsg).await {
                         // There was a sent error and the latest LobbyInfo structure sent to the
@@ -404,24 +1290,36 @@ impl Game {
                     }
                 }
 
-                GameReq::PlayerTaskTerminated(ptid) => {
-                    let p = self.player_by_ptid_mut(ptid).expect("valid ptid");
-                    p.task = None;
+                GameReq::PlayerTaskTerminated(ptid) if self.is_spectator(&ptid) => {
+                    self.spectators.retain(|s| s.task.0 != ptid);
                     self.send_lobby_update_to_players().await;
                 }
 
-                GameReq::ReconnectPlayer(_pl_tx, _name) => {
-                    unimplemented!()
+                GameReq::PlayerTaskTerminated(ptid) => {
+                    // NB: the player might have reconnected with a new task in the meantime
+                    if let Some(p) = self.player_by_ptid_mut(ptid) {
+                        p.task = None;
+                        self.send_lobby_update_to_players().await;
+                    }
+                }
+
+                GameReq::ReconnectPlayer(pl_tx, token) => {
+                    self.reconnect_player(pl_tx, token).await;
+                    self.maybe_autostart().await;
+                }
+
+                GameReq::RegisterSpectator(tx, name, reveal) => {
+                    self.register_spectator(tx, name, reveal).await;
                 }
             };
 
-            // check if we have to terminate the game
-            // NB: we also need a timeout here for created games that nobody ever joined
-            if self.nplayers > 0 && self.all_disconnected() {
-                break
-            }
+            self.update_listing().await;
+            self.update_summary().await;
+            self.save_snapshot();
         }
 
+        self.store.finish(&self.gid, self.snapshot());
+
         // try to drop game if possible
         if let Err(_x) = self.dir_tx.send(DirReq::DropGame(self.gid)).await {
             log::error!("Error dropping game");
@@ -445,21 +1343,15 @@ impl Game {
 
 impl From<srvcli::CreateReq> for GameConfig {
     fn from(req: srvcli::CreateReq) -> Self {
-        let debug_hand = req.get_debug_hand();
-        let debug_table = req.get_debug_table();
-        if debug_hand.is_none() || debug_table.is_none() {
-            return GameConfig {
-                nplayers: req.nplayers,
-                debug: None,
-            }
-        }
-
+        let debug = req.get_debug_game().map(|position| GameDebug { position: position });
         GameConfig {
             nplayers: req.nplayers,
-            debug: Some(GameDebug {
-                hand: debug_hand.unwrap(),
-                table: debug_table.unwrap(),
-            }),
+            target_score: req.target_score,
+            rules: req.rules,
+            turn_timer: req.turn_timer,
+            public: req.public,
+            autostart: false,
+            debug: debug,
         }
     }
 }
@@ -468,14 +1360,30 @@ pub fn spawn_game_task(
     gid: GameId,
     cfg: GameConfig,
     dir_tx: DirTaskTx,
-    rep_tx: oneshot::Sender<srvcli::CreateRep>,
-) -> GameTaskTx {
+    store: Store,
+    timeouts: GameTimeouts,
+    rep_tx: Option<oneshot::Sender<srvcli::CreateRep>>,
+) -> Result<GameTaskTx, String> {
     let (game_tx, game_rx) = mpsc::channel::<GameReq>(1024);
+    let game = Game::new(gid, cfg, game_rx, dir_tx, store, timeouts)?;
     log::info!("Spawning game task {}", gid.to_string());
-    let game = Game::new(gid, cfg, game_rx, dir_tx);
     // NB: we are detaching the game task by dropping its handle
     let _game_task = tokio::spawn(game.task(rep_tx));
-    game_tx
+    Ok(game_tx)
+}
+
+pub fn spawn_restored_game_task(
+    gid: GameId,
+    snap: GameSnapshot,
+    dir_tx: DirTaskTx,
+    store: Store,
+    timeouts: GameTimeouts,
+) -> Result<GameTaskTx, String> {
+    let (game_tx, game_rx) = mpsc::channel::<GameReq>(1024);
+    let game = Game::restore(gid, snap, game_rx, dir_tx, store, timeouts)?;
+    log::info!("Spawning restored game task {}", gid.to_string());
+    let _game_task = tokio::spawn(game.task(None));
+    Ok(game_tx)
 }
 
 impl Player {
@@ -495,15 +1403,24 @@ impl Player {
     }
 
     async fn send_cli_or_disconnect(&mut self, srvmsg: srvcli::ServerMsg) -> Result<(), ()> {
+        // bots have access to the game directly, no need to send them anything
+        if self.is_bot() {
+            return Ok(());
+        }
+
         let msg = PlayerTaskMsg::ForwardToClient(srvmsg);
         self.send_task_or_disconnect(msg).await
     }
 
+    fn is_bot(&self) -> bool {
+        self.bot_kind.is_some()
+    }
+
     fn is_connected(&self) -> bool {
-        self.task.is_some()
+        self.task.is_some() || self.is_bot()
     }
 
     fn is_disconnected(&self) -> bool {
-        self.task.is_none()
+        !self.is_connected()
     }
 }
@@ -24,10 +24,11 @@ pub enum GameReq {
     /// Notify the server that the player task for handling the websocket connection has terminated
     /// (typically due to user disconnect or an error).
     PlayerTaskTerminated(PlayerTaskId),
-    /// Re-join a player to the game (tx, name), and name has to match an existing disconnected
-    /// player
-    #[allow(dead_code)] // TODO
+    /// Re-join a player to the game (tx, session token). The token has to match the one given to
+    /// the player when they registered (see srvcli::ServerMsg::SessionToken).
     ReconnectPlayer(PlayerTaskTx, String),
+    /// Register a spectator to the game (tx, name, reveal hands after each game)
+    RegisterSpectator(PlayerTaskTx, String, bool),
 }
 
 /// Channel for {<player_tasks>, ???} -> <game_task> communication
@@ -13,6 +13,8 @@ mod directory;
 mod player;
 mod game;
 mod chararr_id;
+mod store;
+mod stats;
 
 use percent_encoding::percent_decode_str;
 
@@ -29,17 +31,23 @@ use core::srvcli;
 // There is the directory actor that controls a mapping from id -> games
 // There is a game actor which manages the state for every game
 // There is a players actor that manages the state for every client (player)
+// Games are persisted in a data directory, so that they survive restarts (see store.rs)
 //
 // URLs:
 // create game: /create_game -> <game_id>
 // connect to game: /ws/<game_id>/<player_name>
+// reconnect to game: /ws/<game_id>/<player_name>?token=<session token>
+// watch game: /ws/<game_id>/watch/<spectator_name>[?reveal=true]
+// list public games: /games
+// quick match: /ws/quickmatch/<nplayers>/<player_name>
+// metrics (prometheus): /metrics
+// server status (JSON): /admin/status (with "Authorization: Bearer <DILOTI_ADMIN_TOKEN>")
 
 fn rep_with_internal_error<T: warp::Reply>(reply: T) -> warp::reply::WithStatus<T> {
     let code = warp::http::StatusCode::INTERNAL_SERVER_ERROR;
     return warp::reply::with_status(reply, code);
 }
 
-#[allow(dead_code)]
 fn rep_with_unauthorized<T: warp::Reply>(reply: T) -> warp::reply::WithStatus<T> {
     let code = warp::http::StatusCode::UNAUTHORIZED;
     return warp::reply::with_status(reply, code);
@@ -50,6 +58,11 @@ fn rep_with_ok<T: warp::Reply>(reply: T) -> warp::reply::WithStatus<T> {
     return warp::reply::with_status(reply, code);
 }
 
+fn rep_with_bad_request<T: warp::Reply>(reply: T) -> warp::reply::WithStatus<T> {
+    let code = warp::http::StatusCode::BAD_REQUEST;
+    return warp::reply::with_status(reply, code);
+}
+
 #[allow(dead_code)]
 fn rep_with_conflict<T: warp::Reply>(reply: T) -> warp::reply::WithStatus<T> {
     let code = warp::http::StatusCode::CONFLICT;
@@ -59,6 +72,21 @@ fn rep_with_conflict<T: warp::Reply>(reply: T) -> warp::reply::WithStatus<T> {
 async fn create_game(req: srvcli::CreateReq, mut dir_tx: mpsc::Sender<directory_task::DirReq>)
 -> Result<impl warp::Reply, std::convert::Infallible> {
 
+    if let Err(x) = req.rules.validate(req.nplayers as usize) {
+        log::info!("Invalid rules in create game request: {}", x);
+        return Ok(rep_with_bad_request(x))
+    }
+
+    if let Some(Err(x)) = req.turn_timer.map(|t| t.validate(req.nplayers as usize)) {
+        log::info!("Invalid turn timer in create game request: {}", x);
+        return Ok(rep_with_bad_request(x))
+    }
+
+    if let Err(x) = req.check_debug_game() {
+        log::info!("Invalid debug position in create game request: {}", x);
+        return Ok(rep_with_bad_request(x))
+    }
+
     let cnf : game::GameConfig = req.into();
 
     // contact directory task to create a new game
@@ -77,6 +105,84 @@ async fn create_game(req: srvcli::CreateReq, mut dir_tx: mpsc::Sender<directory_
     }
 }
 
+async fn list_games(mut dir_tx: mpsc::Sender<directory_task::DirReq>)
+-> Result<impl warp::Reply, std::convert::Infallible> {
+
+    let (tx, rx) = oneshot::channel::<Vec<srvcli::GameListing>>();
+    if let Err(x) = dir_tx.send(directory_task::DirReq::ListGames(tx)).await {
+        log::error!("Error sending ListGames request: {:?}", x);
+        return Ok(rep_with_internal_error(String::from("")))
+    }
+
+    if let Ok(ret) = rx.await {
+        Ok(rep_with_ok(serde_json::to_string(&ret).unwrap()))
+    } else {
+        log::error!("Error receiving result from directory");
+        Ok(rep_with_internal_error(String::from("")))
+    }
+}
+
+/// Contact the directory task to get the server statistics
+async fn get_stats(dir_tx: &mut mpsc::Sender<directory_task::DirReq>) -> Option<stats::ServerStats> {
+    let (tx, rx) = oneshot::channel::<stats::ServerStats>();
+    if let Err(x) = dir_tx.send(directory_task::DirReq::Stats(tx)).await {
+        log::error!("Error sending Stats request: {:?}", x);
+        return None
+    }
+
+    match rx.await {
+        Ok(x) => Some(x),
+        Err(_) => {
+            log::error!("Error receiving result from directory");
+            None
+        },
+    }
+}
+
+async fn metrics(mut dir_tx: mpsc::Sender<directory_task::DirReq>)
+-> Result<impl warp::Reply, std::convert::Infallible> {
+    let rep = match get_stats(&mut dir_tx).await {
+        Some(stats) => rep_with_ok(stats.to_prometheus()),
+        None => rep_with_internal_error(String::from("")),
+    };
+    Ok(warp::reply::with_header(rep, "content-type", "text/plain; version=0.0.4"))
+}
+
+async fn admin_status(
+    auth: Option<String>,
+    token: Option<String>,
+    mut dir_tx: mpsc::Sender<directory_task::DirReq>,
+) -> Result<impl warp::Reply, std::convert::Infallible> {
+
+    let authorized = match (&auth, &token) {
+        (Some(auth), Some(token)) => *auth == format!("Bearer {}", token),
+        _ => false,
+    };
+    if !authorized {
+        log::info!("Unauthorized request for admin status");
+        return Ok(rep_with_unauthorized(String::from("")))
+    }
+
+    match get_stats(&mut dir_tx).await {
+        Some(stats) => Ok(rep_with_ok(serde_json::to_string(&stats).unwrap())),
+        None => Ok(rep_with_internal_error(String::from(""))),
+    }
+}
+
+/// Query of the websocket connection
+#[derive(Debug, serde::Deserialize)]
+struct ConnectQuery {
+    /// session token of a player that reconnects
+    token: Option<String>,
+}
+
+/// Query of the spectator websocket connection
+#[derive(Debug, serde::Deserialize)]
+struct WatchQuery {
+    /// reveal the hands of the players after each game
+    #[serde(default)]
+    reveal: bool,
+}
 
 // game handler
 
@@ -85,8 +191,29 @@ async fn main() {
     env_logger::init();
     let log = warp::log("dilotionline::backend");
 
+    let store = match store::Store::from_env() {
+        Ok(x) => x,
+        Err(x) => {
+            log::error!("Failed to initialize game store: {}", x);
+            return;
+        }
+    };
+
+    let timeouts = match game::GameTimeouts::from_env() {
+        Ok(x) => x,
+        Err(x) => {
+            log::error!("Failed to configure game timeouts: {}", x);
+            return;
+        }
+    };
+
+    let admin_token = stats::admin_token_from_env();
+    if admin_token.is_none() {
+        log::info!("DILOTI_ADMIN_TOKEN is not set: /admin/status is disabled");
+    }
+
     // channel to directory task
-    let dir_tx = directory::spawn_directory_task();
+    let dir_tx = directory::spawn_directory_task(store, timeouts);
 
     // route: /
     let index_r = warp::get()
@@ -129,20 +256,93 @@ async fn main() {
             .and_then(move |req| { create_game(req, dir_tx_.clone()) })
     };
 
+    // route: /games
+    let games_r = {
+        let dir_tx_ = dir_tx.clone();
+        warp::path("games")
+            .and(warp::get())
+            .and(warp::path::end())
+            .and_then(move || { list_games(dir_tx_.clone()) })
+    };
+
+    // route: /metrics
+    let metrics_r = {
+        let dir_tx_ = dir_tx.clone();
+        warp::path("metrics")
+            .and(warp::get())
+            .and(warp::path::end())
+            .and_then(move || { metrics(dir_tx_.clone()) })
+    };
+
+    // route: /admin/status
+    let status_r = {
+        let dir_tx_ = dir_tx.clone();
+        warp::path("admin")
+            .and(warp::path("status"))
+            .and(warp::get())
+            .and(warp::path::end())
+            .and(warp::header::optional::<String>("authorization"))
+            .and_then(move |auth| { admin_status(auth, admin_token.clone(), dir_tx_.clone()) })
+    };
+
     // /ingame is an internal thing used by the frontend. If we get a request for it (e.g., because
     // the user reloaded the page) just redirect them to /.
     let ingame_r = warp::path("ingame")
         .map(|| { warp::redirect(warp::http::Uri::from_static("/")) });
 
-    // GET /ws/:game_id:/:player_name:/ -> websocket for joining the game
+    // GET /ws/quickmatch/:nplayers:/:player_name: -> websocket for joining a game with other
+    // players waiting for the same number of players
+    let quickmatch_r = {
+        let dir_tx_ = dir_tx.clone();
+        warp::path("ws")
+            .and(warp::path("quickmatch"))
+            .and(warp::path::param())
+            .and(warp::path::param())
+            .and(warp::path::end())
+            .and(warp::ws())
+            .and_then(
+                move |nplayers: u8, player_name: String, ws| {
+                    let pname = percent_decode_str(&player_name).decode_utf8_lossy().to_string();
+                    let join = player::JoinKind::QuickMatch(nplayers);
+                    player::player_setup("quickmatch".to_string(), ws, pname, join, dir_tx_.clone())
+                }
+            )
+    };
+
+    // GET /ws/:game_id:/watch/:name:/[?reveal=true] -> websocket for watching the game
+    let watch_r = {
+        let dir_tx_ = dir_tx.clone();
+        warp::path("ws")
+            .and(warp::path::param())
+            .and(warp::path("watch"))
+            .and(warp::path::param())
+            .and(warp::path::end())
+            .and(warp::query::<WatchQuery>())
+            .and(warp::ws())
+            .and_then(
+                move |game_id, name: String, query: WatchQuery, ws| {
+                    let name = percent_decode_str(&name).decode_utf8_lossy().to_string();
+                    let join = player::JoinKind::Watch(query.reveal);
+                    player::player_setup(game_id, ws, name, join, dir_tx_.clone())
+                }
+            )
+    };
+
+    // GET /ws/:game_id:/:player_name:/[?token=:token:] -> websocket for joining the game
     let connect_r = warp::path("ws")
         .and(warp::path::param())
         .and(warp::path::param())
+        .and(warp::path::end())
+        .and(warp::query::<ConnectQuery>())
         .and(warp::ws()) // prepare the websocket handshake
         .and_then(
-            move |game_id, player_name: String, ws| {
+            move |game_id, player_name: String, query: ConnectQuery, ws| {
                 let pname = percent_decode_str(&player_name).decode_utf8_lossy().to_string();
-                player::player_setup(game_id, ws, pname, dir_tx.clone())
+                let join = match query.token {
+                    Some(token) => player::JoinKind::Reconnect(token),
+                    None => player::JoinKind::Register,
+                };
+                player::player_setup(game_id, ws, pname, join, dir_tx.clone())
             }
         );
 
@@ -151,6 +351,11 @@ async fn main() {
         .or(ingame_r)
         .or(pkg_r)
         .or(create_r)
+        .or(games_r)
+        .or(metrics_r)
+        .or(status_r)
+        .or(quickmatch_r)
+        .or(watch_r)
         .or(connect_r)
         .with(log);
     let sockaddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
@@ -22,7 +22,7 @@ use crate::{
         PlayerTaskMsg,
         PlayerTaskRx,
         PlayerTaskTx,
-        PlayerTaskMsg::{RegistrationResult, ForwardToClient}
+        PlayerTaskMsg::{RegistrationResult, ForwardToClient, Close}
     },
 };
 
@@ -31,15 +31,26 @@ use futures::{SinkExt,StreamExt};
 type WsTx = futures::stream::SplitSink<ws::WebSocket, ws::Message>;
 type WsRx = futures::stream::SplitStream<ws::WebSocket>;
 
+/// How long quick-match players wait in the queue for other players
+const QUICK_MATCH_WAIT: Duration = Duration::from_secs(5*60);
+
 struct PlayerTask {
     pub ws_tx: WsTx,
     pub ws_rx: WsRx,
     pub self_rx: PlayerTaskRx,
     pub game_tx: GameTaskTx,
     pub pid: PlayerTaskId,
+    pub dir_tx: directory_task::DirTaskTx,
 }
 
 impl PlayerTask {
+    /// Count a websocket error in the server statistics
+    async fn report_ws_error(&mut self) {
+        if let Err(x) = self.dir_tx.send(directory_task::DirReq::WsError).await {
+            log::error!("Error reporting websocket error: {:?}", x);
+        }
+    }
+
     async fn do_handle_climsg(&mut self, climsg: ws::Message) -> Result<(), ()> {
         // Close(Some(CloseFrame { code: Away, reason: "" }))
         if climsg.is_close() {
@@ -75,6 +86,7 @@ impl PlayerTask {
 
             Some(Err(x)) => {
                 log::error!("Error in client websocket: {:?}. Failing.", x);
+                self.report_ws_error().await;
                 Err(())
             },
 
@@ -92,11 +104,17 @@ impl PlayerTask {
                 let msg = ws::Message::text(json);
                 if let Err(x) = self.ws_tx.send(msg).await {
                     log::error!("Error forwarding message to client: {:?}", x);
+                    self.report_ws_error().await;
                     Err(())
                 } else {
                     Ok(())
                 }
             },
+            Some(Close(reason)) => {
+                let msg = ws::Message::close_with(4444u16, reason);
+                self.ws_tx.send(msg).await.unwrap_or(());
+                Err(())
+            },
             Some(RegistrationResult(x)) => {
                 log::error!("Received unexpected registration result: {:?}", x);
                 Err(())
@@ -136,14 +154,83 @@ async fn get_game_tx(
     }
 }
 
-/// Contact game task to register the player
+async fn send_to_client(ws_tx: &mut WsTx, msg: srvcli::ServerMsg) -> Result<(), String> {
+    let json = serde_json::to_string(&msg).expect("serialization failed");
+    ws_tx.send(ws::Message::text(json)).await.map_err(|x| {
+        log::error!("Error sending message to client: {:?}", x);
+        "Failed to contact client".to_string()
+    })
+}
+
+/// Contact directory task to wait in the quick-match queue until we get a game. The client is
+/// told that they are waiting, and the wait ends if they go away or after QUICK_MATCH_WAIT.
+async fn get_quick_match(
+    dir_tx: &mut directory_task::DirTaskTx,
+    nplayers: u8,
+    ws_tx: &mut WsTx,
+    ws_rx: &mut WsRx,
+) -> Result<GameTaskTx, String> {
+
+    if nplayers != 2 && nplayers != 4 {
+        return Err("Quick match is for 2 or 4 players".to_string());
+    }
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<(game::GameId, GameTaskTx)>();
+    if let Err(x) = dir_tx.send(directory_task::DirReq::QuickMatch(nplayers, tx)).await {
+        log::error!("Error sending QuickMatch request: {:?}", x);
+        return Err("Failed to find a game".to_string());
+    }
+
+    let waiting = srvcli::ServerMsg::QuickMatchWaiting { timeout_secs: QUICK_MATCH_WAIT.as_secs() };
+    send_to_client(ws_tx, waiting).await?;
+
+    // NB: if we give up, rx is dropped and the directory removes us from the queue
+    let (gid, game_tx) = tokio::select! {
+        res = tokio::time::timeout(QUICK_MATCH_WAIT, rx) => match res {
+            Ok(Ok(x)) => x,
+            Ok(Err(e)) => {
+                log::error!("Failed to get quick match from directory: {:?}", e);
+                return Err("Failed to find a game".to_string());
+            },
+            Err(_) => return Err("No other players showed up for a quick match. Try again later.".to_string()),
+        },
+        _ = ws_rx.next() => {
+            log::info!("Quick-match player left the queue");
+            return Err("Left the quick-match queue".to_string());
+        },
+    };
+
+    // let quick-match players know their game, before anything else from the game task
+    send_to_client(ws_tx, srvcli::ServerMsg::QuickMatch(gid.to_string())).await?;
+    Ok(game_tx)
+}
+
+/// How a client joins the game
+#[derive(Debug, Clone)]
+pub enum JoinKind {
+    /// Join as a new player
+    Register,
+    /// Get back to a player's seat (session token)
+    Reconnect(String),
+    /// Watch the game (reveal hands after each game)
+    Watch(bool),
+    /// Join as a new player in a game picked by the quick-match queue (number of players)
+    QuickMatch(u8),
+}
+
+/// Contact game task to register the player (or reconnect them, if they have a session token)
 async fn register_player(
     player_name: String,
+    join: JoinKind,
     game_tx: &mut GameTaskTx,
     player_tx: PlayerTaskTx,
     player_rx: &mut PlayerTaskRx,
 ) -> Result<PlayerTaskId, String> {
-    let req = GameReq::RegisterPlayer(player_tx, player_name);
+    let req = match join {
+        JoinKind::Register | JoinKind::QuickMatch(_) => GameReq::RegisterPlayer(player_tx, player_name),
+        JoinKind::Reconnect(token) => GameReq::ReconnectPlayer(player_tx, token),
+        JoinKind::Watch(reveal) => GameReq::RegisterSpectator(player_tx, player_name, reveal),
+    };
     if let Err(x) = game_tx.send(req).await {
         log::error!("Error sending RegisterPlayer request: {:?}", x);
         return Err("Failed to register player to game".to_string())
@@ -170,12 +257,19 @@ struct PlayerTaskArg {
 async fn do_player_setup(
     game_id_s: &str,
     player_name: &str,
+    join: JoinKind,
     mut dir_tx: &mut directory_task::DirTaskTx,
+    ws_tx: &mut WsTx,
+    ws_rx: &mut WsRx,
 ) -> Result<PlayerTaskArg, String> {
-    let mut game_tx = get_game_tx(&mut dir_tx, game_id_s).await?;
+    let mut game_tx = match join {
+        JoinKind::QuickMatch(nplayers) => get_quick_match(&mut dir_tx, nplayers, ws_tx, ws_rx).await?,
+        _ => get_game_tx(&mut dir_tx, game_id_s).await?,
+    };
     let (player_tx, mut player_rx) = tokio::sync::mpsc::channel::<PlayerTaskMsg>(1024);
     let player_id = register_player(
         player_name.to_string(),
+        join,
         &mut game_tx,
         player_tx,
         &mut player_rx).await?;
@@ -192,32 +286,35 @@ pub async fn player_setup(
     game_id_s: String,
     ws: warp::ws::Ws,
     player_name: String,
+    join: JoinKind,
     mut dir_tx: directory_task::DirTaskTx,
 ) -> Result<impl warp::Reply, std::convert::Infallible> {
 
-    // create player task channel and perform the neccessary setup
-    let ws_arg = do_player_setup(&game_id_s, &player_name, &mut dir_tx).await;
-
     // Here we define what will happen at a later point in time (when the protocol upgrade happens)
     // and we return rep which is a reply that will execute the upgrade and spawn a task with our
-    // defined closure.
-    let rep = ws.on_upgrade(move |mut websocket: warp::filters::ws::WebSocket| async move {
+    // defined closure. The setup happens after the upgrade, so that we can talk to the client
+    // while it takes place (e.g., while waiting for a quick match).
+    let rep = ws.on_upgrade(move |websocket: warp::filters::ws::WebSocket| async move {
+        let (mut ws_tx, mut ws_rx) : (WsTx, WsRx) = websocket.split();
+
+        // create player task channel and perform the neccessary setup
+        let ws_arg = do_player_setup(&game_id_s, &player_name, join, &mut dir_tx, &mut ws_tx, &mut ws_rx).await;
         let mut task = match ws_arg {
             Err(x) => {
                 // Send the error message with a custom code and return
                 log::info!("game:{}/pname:{} failed to setup player: {}.", &game_id_s, &player_name, &x);
                 let msg = ws::Message::close_with(4444u16, x);
-                websocket.send(msg).await.unwrap_or(());
+                ws_tx.send(msg).await.unwrap_or(());
                 return;
             },
             Ok(arg) => {
-                let (ws_tx, ws_rx) : (WsTx, WsRx) = websocket.split();
                 PlayerTask {
                     ws_tx: ws_tx,
                     ws_rx: ws_rx,
                     self_rx: arg.self_rx,
                     game_tx: arg.game_tx,
-                    pid: arg.pid
+                    pid: arg.pid,
+                    dir_tx: dir_tx,
                 }
             }
         };
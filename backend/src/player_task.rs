@@ -18,6 +18,8 @@ pub enum PlayerTaskMsg {
     /// If ther registration is successful, it includes a player id that will never change.
     RegistrationResult(Result<PlayerTaskId, String>),
     ForwardToClient(srvcli::ServerMsg),
+    /// Close the connection, giving the reason to the client (e.g., the game timed out)
+    Close(String),
 }
 
 /// Channel for <game_task> -> <player_task> communication
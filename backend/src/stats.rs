@@ -0,0 +1,126 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Server statistics, exported in the Prometheus text format (/metrics) and as JSON for
+// administrators (/admin/status, if DILOTI_ADMIN_TOKEN is set).
+//
+// The directory keeps the statistics: game tasks report a GameSummary whenever it changes (see
+// Game::update_summary()), player tasks report websocket errors, and the counters of a game are
+// added to the totals of finished games when the game is dropped. Counters start from zero when
+// the backend starts.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use tokio::time::Instant;
+
+use crate::game::{GameId, GameSummary, State};
+
+/// Counters that only go up
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct Counters {
+    pub games_created: u64,
+    pub games_finished: u64,
+    pub actions_applied: u64,
+    pub invalid_actions: u64,
+    pub websocket_errors: u64,
+}
+
+/// Status of a running game
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GameStatus {
+    pub game_id: String,
+    pub state: State,
+    pub nplayers: u8,
+    pub players_connected: usize,
+    pub spectators: usize,
+    /// seconds since the game task started
+    pub age_secs: u64,
+    pub actions_applied: u64,
+    pub invalid_actions: u64,
+}
+
+/// Server statistics (see DirReq::Stats)
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ServerStats {
+    pub uptime_secs: u64,
+    /// totals, including the running games
+    #[serde(flatten)]
+    pub counters: Counters,
+    pub games: Vec<GameStatus>,
+}
+
+/// Token for /admin/status (None: the endpoint is disabled)
+pub fn admin_token_from_env() -> Option<String> {
+    std::env::var("DILOTI_ADMIN_TOKEN").ok().filter(|t| t.len() > 0)
+}
+
+impl ServerStats {
+    /// finished: counters of the games that are no longer running
+    pub fn new(started: Instant, finished: &Counters, summaries: &HashMap<GameId, GameSummary>) -> ServerStats {
+        let now = Instant::now();
+        let mut counters = *finished;
+        let mut games = vec![];
+        for (gid, s) in summaries.iter() {
+            counters.actions_applied += s.actions_applied;
+            counters.invalid_actions += s.invalid_actions;
+            games.push(GameStatus {
+                game_id: gid.to_string(),
+                state: s.state.clone(),
+                nplayers: s.nplayers,
+                players_connected: s.players_connected,
+                spectators: s.spectators,
+                age_secs: now.duration_since(s.created).as_secs(),
+                actions_applied: s.actions_applied,
+                invalid_actions: s.invalid_actions,
+            });
+        }
+        games.sort_by(|a, b| a.game_id.cmp(&b.game_id));
+
+        ServerStats {
+            uptime_secs: now.duration_since(started).as_secs(),
+            counters: counters,
+            games: games,
+        }
+    }
+
+    /// Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut ret = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, u64)>| {
+            writeln!(ret, "# HELP {} {}", name, help).unwrap();
+            writeln!(ret, "# TYPE {} {}", name, kind).unwrap();
+            for (labels, val) in samples {
+                writeln!(ret, "{}{} {}", name, labels, val).unwrap();
+            }
+        };
+
+        let c = &self.counters;
+        let nstate = |st: State| {
+            let labels = format!("{{state=\"{:?}\"}}", st);
+            (labels, self.games.iter().filter(|g| g.state == st).count() as u64)
+        };
+        let players = self.games.iter().map(|g| g.players_connected as u64).sum();
+        let spectators = self.games.iter().map(|g| g.spectators as u64).sum();
+        // NB: game ids are what players need to join a game, so we only export aggregates here.
+        // Per-game details are in the (authenticated) admin status.
+        let max_age = |st: State| {
+            let labels = format!("{{state=\"{:?}\"}}", st);
+            (labels, self.games.iter().filter(|g| g.state == st).map(|g| g.age_secs).max().unwrap_or(0))
+        };
+
+        metric("diloti_uptime_seconds", "gauge", "Seconds since the server started", vec![("".to_string(), self.uptime_secs)]);
+        metric("diloti_games", "gauge", "Running games by state", vec![nstate(State::InLobby), nstate(State::InGame)]);
+        metric("diloti_players_connected", "gauge", "Connected players", vec![("".to_string(), players)]);
+        metric("diloti_spectators_connected", "gauge", "Connected spectators", vec![("".to_string(), spectators)]);
+        metric("diloti_games_created_total", "counter", "Games created", vec![("".to_string(), c.games_created)]);
+        metric("diloti_games_finished_total", "counter", "Games that terminated", vec![("".to_string(), c.games_finished)]);
+        metric("diloti_actions_applied_total", "counter", "Player actions applied", vec![("".to_string(), c.actions_applied)]);
+        metric("diloti_invalid_actions_total", "counter", "Invalid player actions rejected", vec![("".to_string(), c.invalid_actions)]);
+        metric("diloti_websocket_errors_total", "counter", "Websocket errors", vec![("".to_string(), c.websocket_errors)]);
+        metric("diloti_game_age_max_seconds", "gauge", "Age of the oldest running game by state", vec![max_age(State::InLobby), max_age(State::InGame)]);
+        ret
+    }
+}
@@ -0,0 +1,225 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Persistent storage for games, so that they survive backend restarts.
+//
+// Every game is stored as a JSON snapshot in the data directory: <gid>.json while the game is
+// running, and <gid>.done.json after its task terminated. On startup, the directory restores the
+// running games (see Directory::restore_games()). Snapshots of finished games are kept for the
+// retention period, and then removed.
+//
+// Snapshots are serialized and written by a dedicated writer thread, so that the game tasks never
+// block on I/O. Writes are done in the order they were requested. Game tasks only save snapshots
+// when the game changes (see Game::save_snapshot()), and snapshots are bounded: the game history
+// only keeps the current match, and the chat only the last messages.
+//
+// Reading the snapshots on startup is done directly, before the games are restored.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use crate::game::{GameId, GameSnapshot};
+
+/// Default directory for game snapshots
+const DEFAULT_DATA_DIR: &str = "data";
+/// Default number of days we keep the snapshots of finished games
+const DEFAULT_RETENTION_DAYS: u64 = 7;
+
+const SUFFIX_RUNNING: &str = ".json";
+const SUFFIX_DONE: &str = ".done.json";
+
+/// Requests to the writer thread
+#[derive(Debug)]
+enum WriteReq {
+    Save(GameId, GameSnapshot),
+    Finish(GameId, GameSnapshot),
+    Prune,
+}
+
+/// Handle to the data directory
+#[derive(Debug, Clone)]
+struct Disk {
+    dir: PathBuf,
+    retention: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Store {
+    disk: Disk,
+    writer_tx: mpsc::Sender<WriteReq>,
+}
+
+impl Store {
+    pub fn new(dir: PathBuf, retention: Duration) -> Result<Store, String> {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("failed to create data directory {}: {}", dir.display(), e))?;
+        let disk = Disk {
+            dir: dir,
+            retention: retention,
+        };
+
+        let (writer_tx, writer_rx) = mpsc::channel::<WriteReq>();
+        let writer_disk = disk.clone();
+        std::thread::Builder::new()
+            .name("store-writer".to_string())
+            .spawn(move || writer_disk.writer(writer_rx))
+            .map_err(|e| format!("failed to start store writer: {}", e))?;
+
+        Ok(Store {
+            disk: disk,
+            writer_tx: writer_tx,
+        })
+    }
+
+    /// Configure the store from the environment:
+    ///  DILOTI_DATA_DIR: directory for the snapshots
+    ///  DILOTI_RETENTION_DAYS: how long to keep the snapshots of finished games
+    pub fn from_env() -> Result<Store, String> {
+        let dir = std::env::var("DILOTI_DATA_DIR").unwrap_or(DEFAULT_DATA_DIR.to_string());
+        let days = match std::env::var("DILOTI_RETENTION_DAYS") {
+            Err(_) => DEFAULT_RETENTION_DAYS,
+            Ok(x) => x.parse::<u64>().map_err(|e| format!("invalid DILOTI_RETENTION_DAYS ({}): {}", x, e))?,
+        };
+        Store::new(PathBuf::from(dir), Duration::from_secs(days*24*60*60))
+    }
+
+    fn send(&self, req: WriteReq) {
+        if let Err(x) = self.writer_tx.send(req) {
+            log::error!("Failed to send request to the store writer: {}", x);
+        }
+    }
+
+    /// Store the snapshot of a running game
+    pub fn save(&self, gid: &GameId, snap: GameSnapshot) {
+        self.send(WriteReq::Save(*gid, snap));
+    }
+
+    /// Store the final snapshot of a game, which will not be restored
+    pub fn finish(&self, gid: &GameId, snap: GameSnapshot) {
+        self.send(WriteReq::Finish(*gid, snap));
+    }
+
+    /// Remove the snapshots of finished games that are older than the retention period
+    pub fn prune(&self) {
+        self.send(WriteReq::Prune);
+    }
+
+    /// Load the snapshots of all running games
+    pub fn load_running(&self) -> Vec<(GameId, GameSnapshot)> {
+        self.disk.load_running()
+    }
+}
+
+impl Disk {
+    fn writer(&self, rx: mpsc::Receiver<WriteReq>) {
+        while let Ok(req) = rx.recv() {
+            match req {
+                WriteReq::Save(gid, snap) => self.save(&gid, &snap),
+                WriteReq::Finish(gid, snap) => self.finish(&gid, &snap),
+                WriteReq::Prune => self.prune(),
+            }
+        }
+    }
+
+    fn path(&self, gid: &GameId, suffix: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", gid.to_string(), suffix))
+    }
+
+    fn write(&self, path: PathBuf, snap: &GameSnapshot) -> Result<(), String> {
+        let data = serde_json::to_vec(snap).map_err(|e| e.to_string())?;
+        // write to a temporary file first, so that we never end up with a partial snapshot
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, data).map_err(|e| format!("{}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, &path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn save(&self, gid: &GameId, snap: &GameSnapshot) {
+        if let Err(x) = self.write(self.path(gid, SUFFIX_RUNNING), snap) {
+            log::error!("Game {}: failed to save snapshot: {}", gid.to_string(), x);
+        }
+    }
+
+    fn finish(&self, gid: &GameId, snap: &GameSnapshot) {
+        if let Err(x) = self.write(self.path(gid, SUFFIX_DONE), snap) {
+            log::error!("Game {}: failed to save final snapshot: {}", gid.to_string(), x);
+        }
+        let path = self.path(gid, SUFFIX_RUNNING);
+        if let Err(x) = std::fs::remove_file(&path) {
+            if x.kind() != std::io::ErrorKind::NotFound {
+                log::error!("Game {}: failed to remove {}: {}", gid.to_string(), path.display(), x);
+            }
+        }
+    }
+
+    fn read(&self, path: &PathBuf) -> Result<GameSnapshot, String> {
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        serde_json::from_slice(&data).map_err(|e| e.to_string())
+    }
+
+    fn load_running(&self) -> Vec<(GameId, GameSnapshot)> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(x) => x,
+            Err(x) => {
+                log::error!("Failed to read data directory {}: {}", self.dir.display(), x);
+                return vec![];
+            }
+        };
+
+        let mut ret = vec![];
+        for entry in entries.filter_map(|e| e.ok()) {
+            let fname = entry.file_name().to_string_lossy().to_string();
+            if !fname.ends_with(SUFFIX_RUNNING) || fname.ends_with(SUFFIX_DONE) {
+                continue;
+            }
+
+            let gid_s = &fname[..fname.len() - SUFFIX_RUNNING.len()];
+            let gid = match GameId::from_string(gid_s) {
+                Some(x) => x,
+                None => {
+                    log::warn!("Ignoring unexpected file in data directory: {}", fname);
+                    continue;
+                }
+            };
+
+            match self.read(&entry.path()) {
+                Ok(snap) => ret.push((gid, snap)),
+                Err(x) => log::error!("Game {}: failed to load snapshot: {}", gid_s, x),
+            }
+        }
+
+        ret
+    }
+
+    fn prune(&self) {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(x) => x,
+            Err(x) => {
+                log::error!("Failed to read data directory {}: {}", self.dir.display(), x);
+                return;
+            }
+        };
+
+        let now = SystemTime::now();
+        for entry in entries.filter_map(|e| e.ok()) {
+            if !entry.file_name().to_string_lossy().ends_with(SUFFIX_DONE) {
+                continue;
+            }
+
+            let age = entry.metadata()
+                .and_then(|m| m.modified())
+                .map(|t| now.duration_since(t).unwrap_or(Duration::from_secs(0)));
+            match age {
+                Ok(age) if age < self.retention => (),
+                Ok(_) => {
+                    log::info!("Removing old snapshot {}", entry.path().display());
+                    std::fs::remove_file(entry.path()).unwrap_or(());
+                },
+                Err(x) => log::error!("Failed to get age of {}: {}", entry.path().display(), x),
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Headless self-play: bots play N games against each other, and we report aggregate statistics.
+//
+// cargo run --release -p core --example selfplay -- --games 1000 --bots greedy,random
+
+use rand::{Rng, SeedableRng};
+
+use core::{Game, GameState, RuleSet, PlayerAction, BotKind, Bot};
+
+const USAGE: &str = "\
+Usage: selfplay [options]
+  --games N                 number of games to play (default: 100)
+  --seed S                  seed for dealing and for the bots (default: 0)
+  --players N               number of players: 2 or 4 (default: 2)
+  --bots B0,B1              bots for team 0 and team 1: random, greedy, or mcts
+                            (default: greedy,greedy)
+  --no-forced-captures
+  --no-forced-declarations
+  --no-majority-bonus
+  --xeri-figure-double
+  --xeri-score N
+  --table-size N
+  -h, --help                print this message
+";
+
+struct Config {
+    ngames: usize,
+    seed: u64,
+    nplayers: usize,
+    bots: [BotKind; 2],
+    rules: RuleSet,
+}
+
+#[derive(Default)]
+struct Stats {
+    ngames: usize,
+    wins: [usize; 2],
+    ties: usize,
+    points: [usize; 2],
+    xeres: [usize; 2],
+    ncaptures: usize,
+    nforced: usize,
+    /// games where the winner would not have won without the majority bonus
+    majority_decided: usize,
+}
+
+fn parse_bot(s: &str) -> Result<BotKind, String> {
+    match s {
+        "random" => Ok(BotKind::Random),
+        "greedy" => Ok(BotKind::Greedy),
+        "mcts" => Ok(BotKind::Mcts),
+        _ => Err(format!("Unknown bot: {}", s)),
+    }
+}
+
+fn parse_args() -> Result<Config, String> {
+    let mut cfg = Config {
+        ngames: 100,
+        seed: 0,
+        nplayers: 2,
+        bots: [BotKind::Greedy, BotKind::Greedy],
+        rules: RuleSet::default(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut val = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--games" => cfg.ngames = val()?.parse().map_err(|e| format!("{:?}", e))?,
+            "--seed" => cfg.seed = val()?.parse().map_err(|e| format!("{:?}", e))?,
+            "--players" => cfg.nplayers = val()?.parse().map_err(|e| format!("{:?}", e))?,
+            "--bots" => {
+                let v = val()?;
+                let bots: Vec<&str> = v.split(',').collect();
+                if bots.len() != 2 {
+                    return Err(format!("Invalid bots: {}", v));
+                }
+                cfg.bots = [parse_bot(bots[0])?, parse_bot(bots[1])?];
+            },
+            "--no-forced-captures" => cfg.rules.forced_captures = false,
+            "--no-forced-declarations" => cfg.rules.forced_declarations = false,
+            "--no-majority-bonus" => cfg.rules.majority_bonus = false,
+            "--xeri-figure-double" => cfg.rules.xeri_figure_double = true,
+            "--xeri-score" => cfg.rules.xeri_score = val()?.parse().map_err(|e| format!("{:?}", e))?,
+            "--table-size" => cfg.rules.table_size = val()?.parse().map_err(|e| format!("{:?}", e))?,
+            "--help" | "-h" => {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            },
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    if cfg.nplayers != 2 && cfg.nplayers != 4 {
+        return Err(format!("Invalid number of players: {}", cfg.nplayers));
+    }
+    cfg.rules.validate(cfg.nplayers)?;
+
+    Ok(cfg)
+}
+
+fn run(cfg: &Config) -> Stats {
+    let mut stats = Stats::default();
+    let mut rng = rand_pcg::Pcg64::seed_from_u64(cfg.seed);
+    // one bot per seat: team of seat i is i % 2
+    let mut bots: Vec<Box<dyn Bot + Send>> = (0..cfg.nplayers)
+        .map(|i| cfg.bots[i % 2].mk_bot(cfg.seed + i as u64))
+        .collect();
+
+    while stats.ngames < cfg.ngames {
+        // Every game starts from a fresh Game, so that we do not accumulate history. The first
+        // player of a new Game is always at position 0, so we rotate the seats instead, to
+        // alternate the team that plays first.
+        let rot = stats.ngames % cfg.nplayers;
+        let seat = |tpos: u8| (tpos as usize + rot) % cfg.nplayers;
+        let game_rng = rand_pcg::Pcg64::seed_from_u64(rng.gen());
+        let mut game = Game::new_with_rules(cfg.nplayers, cfg.rules.clone(), game_rng);
+        loop {
+            match game.state().clone() {
+                GameState::NextTurn(tpos) => {
+                    let view = game.get_player_game_view(tpos);
+                    let act = bots[seat(tpos.0)].choose_action(&view).expect("bot action");
+                    game.apply_action_mut(tpos, act).expect("bot actions are legal");
+                },
+
+                GameState::RoundDone => game.new_round(),
+
+                GameState::GameDone(scores) | GameState::MatchDone(scores, _) => {
+                    // order the sheets by bot team (the team of position 0 plays the bot of seat(0))
+                    let mut sheets: Vec<_> = scores.iter().map(|(ss, _)| ss.clone()).collect();
+                    if seat(0) % 2 == 1 {
+                        sheets.swap(0, 1);
+                    }
+                    stats.add_game(&game, &sheets);
+                    break;
+                },
+            }
+        }
+    }
+
+    stats
+}
+
+impl Stats {
+    fn add_game<R: rand::Rng + Clone>(&mut self, game: &Game<R>, sheets: &[core::ScoreSheet]) {
+        self.ngames += 1;
+        for t in 0..2 {
+            self.points[t] += sheets[t].score;
+            self.xeres[t] += sheets[t].nr_xeres;
+        }
+
+        let (s0, s1) = (sheets[0].score, sheets[1].score);
+        if s0 == s1 {
+            self.ties += 1;
+        } else {
+            let (winner, loser) = if s0 > s1 { (0, 1) } else { (1, 0) };
+            self.wins[winner] += 1;
+            if sheets[winner].score - sheets[winner].cards_score <= sheets[loser].score {
+                self.majority_decided += 1;
+            }
+        }
+
+        let record = game.history().last().expect("game record");
+        for pa in record.actions.iter() {
+            if let PlayerAction::Capture(_) = pa.action {
+                self.ncaptures += 1;
+                self.nforced += pa.forced_cards.len();
+            }
+        }
+    }
+
+    fn report(&self, cfg: &Config) {
+        let n = self.ngames as f64;
+        let pct = |x: usize| 100.0 * (x as f64) / n;
+        println!("games: {} players: {} seed: {}", self.ngames, cfg.nplayers, cfg.seed);
+        println!("rules: {:?}", cfg.rules);
+        for t in 0..2 {
+            println!(
+                "team {} ({}): wins: {:.1}% avg points: {:.2} avg xeres: {:.2}",
+                t, cfg.bots[t], pct(self.wins[t]), self.points[t] as f64 / n, self.xeres[t] as f64 / n
+            );
+        }
+        println!("ties: {:.1}%", pct(self.ties));
+        println!("xeres per game: {:.2}", (self.xeres[0] + self.xeres[1]) as f64 / n);
+        println!(
+            "forced cards per capture: {:.3}",
+            if self.ncaptures == 0 { 0.0 } else { self.nforced as f64 / self.ncaptures as f64 }
+        );
+        println!("games decided by the majority bonus: {:.1}%", pct(self.majority_decided));
+    }
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let stats = run(&cfg);
+    stats.report(&cfg);
+}
@@ -6,12 +6,13 @@
 
 use serde::{Deserialize, Serialize};
 
-use super::card::Card;
-use super::deck::Deck;
+use super::card::{Card, CardSet};
 use super::table::{Table, TableEntry, Declaration, PlayerTpos};
 use super::game::{PlayerGameView};
+use super::error::ActionError;
+use super::rules::RuleSet;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PlayerAction {
     LayDown(Card),
     Declare(DeclAction),
@@ -19,7 +20,7 @@ pub enum PlayerAction {
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PerformedAction {
     pub action: PlayerAction,
     pub player: PlayerTpos,
@@ -28,12 +29,12 @@ pub struct PerformedAction {
 }
 
 // NB: by convention, the first card is the handcard stored as a TableEntry
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct DeclAction {
     pub tentries: Vec<Vec<TableEntry>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CaptureAction {
     pub handcard: Card,
     pub tentries: Vec<Vec<TableEntry>>,
@@ -73,12 +74,12 @@ impl DeclAction {
     }
 
     /// Is the value bounded to 1-10?
-    pub fn validate_bounded(&self) -> Result<(), String> {
+    pub fn validate_bounded(&self) -> Result<(), ActionError> {
         let val = self.value();
         if val > 0 && val <= 10 {
             Ok(())
         } else {
-            Err(format!("Invalid delaration: invalid value: {}", val))
+            Err(ActionError::InvalidDeclValue { value: val })
         }
     }
 
@@ -113,46 +114,54 @@ impl DeclAction {
         }
     }
 
-    pub fn validate_decl_base(&self, hand: &Deck) -> Result<(), String> {
+    pub fn validate_decl_base(&self, hand: &CardSet) -> Result<(), ActionError> {
         if (self.tentries.len() == 0) || (self.tentries[0].len() == 0) {
-            return Err("Invalid declaration: empty".to_string());
+            return Err(ActionError::EmptyDecl);
         }
 
         if !self.tentries[0][0].is_card() {
-            return Err("First entry is not a card".to_string());
+            return Err(ActionError::DeclFirstEntryNotCard);
         }
 
         if !self.same_value() {
-            return Err("Invalid declaration: Not all groups have the same value".to_string());
+            return Err(ActionError::DeclGroupValueMismatch);
         }
 
         if self.tentries.iter().flatten().nth(1).is_none() {
-            return Err("Invalid declaration: Need more than one cards".to_string());
+            return Err(ActionError::DeclTooFewCards);
+        }
+
+        // group declarations have to be on their own (and cannot be in the first group, which
+        // contains the hand card)
+        for (i, tvec) in self.tentries.iter().enumerate() {
+            let has_group = tvec.iter().find(|te| te.is_decl() && te.ref_decl().is_group()).is_some();
+            if has_group && (i == 0 || tvec.len() > 1) {
+                return Err(ActionError::GroupDeclCombined);
+            }
         }
 
         self.validate_bounded()?;
 
         let value = self.value();
-        let value_in_hand = hand.cards
-            .iter()
-            .find(|c| **c != self.handcard() && c.rank.0 == value)
-            .is_some();
+        let mut backing = hand.with_value(value);
+        backing.remove(&self.handcard());
+        let value_in_hand = !backing.is_empty();
 
         if !value_in_hand {
-            return Err(format!("Invalid declaration: There is no value {} card in your hand", value));
+            return Err(ActionError::NoBackingCard { value: value });
         }
 
         Ok(())
     }
 
-    pub fn validate_decl(&self, _table: &Table, hand: &Deck) -> Result<(), String> {
+    pub fn validate_decl(&self, _table: &Table, hand: &CardSet) -> Result<(), ActionError> {
 
         self.validate_decl_base(hand)?;
 
         match self.get_single_decl() {
             GetSingleRes::Zero => (),
-            GetSingleRes::OnlyOne(d) if d.value() != self.value() && d.is_group() => return Err("You may not raise group declarations".to_string()),
-            GetSingleRes::MoreThanOne => return Err("You may not combine more than one declaration to form a new one".to_string()),
+            GetSingleRes::OnlyOne(d) if d.value() != self.value() && d.is_group() => return Err(ActionError::CannotRaiseGroupDecl),
+            GetSingleRes::MoreThanOne => return Err(ActionError::MultipleDecls),
             _ => (),
         }
 
@@ -160,15 +169,15 @@ impl DeclAction {
     }
 
     // - users can only add to their declaration (but not raise)
-    pub fn validate_decl_continuation(&self, decl: &Declaration, _table: &Table, hand: &Deck) -> Result<(), String> {
+    pub fn validate_decl_continuation(&self, decl: &Declaration, _table: &Table, hand: &CardSet) -> Result<(), ActionError> {
 
         self.validate_decl_base(hand)?;
 
         match self.get_single_decl() {
-            GetSingleRes::Zero => return Err("You cannot create a new declaration if you have one on the table".to_string()),
-            GetSingleRes::OnlyOne(d) if d != decl => return Err("You cannot act on a declaration other than your lastest one".to_string()),
-            GetSingleRes::OnlyOne(d) if d.value() != self.value() => return Err("You may not raise your declaration".to_string()),
-            GetSingleRes::MoreThanOne => return Err("You may not combine more than one declaration to form a new one".to_string()),
+            GetSingleRes::Zero => return Err(ActionError::NewDeclWhileOwnExists),
+            GetSingleRes::OnlyOne(d) if d != decl => return Err(ActionError::NotLatestDecl),
+            GetSingleRes::OnlyOne(d) if d.value() != self.value() => return Err(ActionError::CannotRaiseOwnDecl),
+            GetSingleRes::MoreThanOne => return Err(ActionError::MultipleDecls),
             _ => (),
         }
 
@@ -176,39 +185,39 @@ impl DeclAction {
     }
 }
 
-fn validate_laydown(card: Card, table: &Table) -> Result<(), String> {
+fn validate_laydown(card: Card, table: &Table) -> Result<(), ActionError> {
     let matching_card = table.entries
         .iter()
         .find(|te| te.value() == card.rank.0)
         .is_some();
 
         if matching_card {
-            return Err(format!("You cannot lay down a card ({}) if a card or declaration with the same value exists on the table.", card));
+            return Err(ActionError::LayDownMatchesTableValue { card: card });
         }
 
         Ok(())
 }
 
 impl CaptureAction {
-    pub fn validate_capture(&self, table: &Table) -> Result<(), String> {
+    pub fn validate_capture(&self, table: &Table) -> Result<(), ActionError> {
         if (self.tentries.len() == 0) || (self.tentries[0].len() == 0) {
-            return Err("Invalid capture: empty".to_string());
+            return Err(ActionError::EmptyCapture);
         }
 
         if !self.same_value() {
-            return Err("Invalid capture: Not all groups have the same value".to_string());
+            return Err(ActionError::CaptureGroupValueMismatch);
         }
 
         for tvec in self.tentries.iter() {
-            if tvec.iter().find(|x| x.is_decl()).is_some()  && tvec.len() < 1 {
-                return Err("Invalid capture: declarations can only be captured on their own".to_string());
+            if tvec.iter().find(|x| x.is_decl()).is_some()  && tvec.len() > 1 {
+                return Err(ActionError::DeclNotCapturedAlone);
             }
         }
 
         if self.handcard.rank.is_figure() {
             for te_vec in self.tentries.iter() {
                 if te_vec.len() > 1 {
-                    return Err("Invalid capture: Figures cannot be used to capture multiple cards".to_string());
+                    return Err(ActionError::FigureMultiCapture);
                 }
             }
 
@@ -217,14 +226,14 @@ impl CaptureAction {
             match (ncaptured, ntable) {
                 (1, 1) => (),
                 (1, 2) => (),
-                (2, 2) => return Err("invalid capture: if 2 same figures exist on the table, only one can be captured.".to_string()),
+                (2, 2) => return Err(ActionError::OnlyOneOfTwoFigures),
                 // For the two cases below, we could force the action as we do with other
                 // obligations, but this is tricky and also counter-intuitive to the player because
                 // it's a very special case, so we just invalidate the action.
-                (1, 3) => return Err("invalid capture: if 3 same figures exist on the table, all three must be captured.".to_string()),
-                (2, 3) => return Err("invalid capture: if 3 same figures exist on the table, all three must be captured.".to_string()),
+                (1, 3) => return Err(ActionError::MustCaptureAllThreeFigures),
+                (2, 3) => return Err(ActionError::MustCaptureAllThreeFigures),
                 (3, 3) => (),
-                _ => return Err("Huh?".to_string()),
+                _ => return Err(ActionError::InvalidFigureCapture),
             }
 
         }
@@ -232,6 +241,33 @@ impl CaptureAction {
         Ok(())
     }
 
+    /// Validate a capture of a player that has a declaration on the table
+    pub fn validate_capture_continuation(&self, decl: &Declaration, table: &Table, hand: &CardSet, rules: &RuleSet) -> Result<(), ActionError> {
+        self.validate_capture(table)?;
+
+        // NB: with forced captures, capturing with the value of the declaration captures it as well
+        let value = decl.value();
+        if self.value() != value || rules.forced_captures {
+            return Ok(());
+        }
+
+        let captures_decl = self.tentries
+            .iter()
+            .flatten()
+            .any(|te| match te {
+                TableEntry::Decl(d) => d == decl,
+                _ => false,
+            });
+
+        let mut backing = hand.with_value(value);
+        backing.remove(&self.handcard);
+        if !captures_decl && backing.is_empty() {
+            return Err(ActionError::LastBackingCard { value: value });
+        }
+
+        Ok(())
+    }
+
     pub fn value(&self) -> u8 {
         self.handcard.rank.0
     }
@@ -270,28 +306,111 @@ impl CaptureAction {
 
 impl PlayerAction {
 
+    /// The card that the player played from their hand
+    pub fn hand_card(&self) -> Card {
+        match self {
+            PlayerAction::LayDown(c) => c.clone(),
+            PlayerAction::Capture(ca) => ca.handcard.clone(),
+            PlayerAction::Declare(da) => da.handcard(),
+        }
+    }
+
     /// Validate action given a player's view
     ///
-    /// NB: This function does not check whether the referenced cards exist in the table. This will
-    /// happen later.
-    pub fn validate(&self, view: &PlayerGameView) -> Result<(), String> {
-        use PlayerAction::*;
+    /// If validation succeeds, applying the action to the game cannot fail.
+    pub fn validate(&self, view: &PlayerGameView) -> Result<(), ActionError> {
         if !view.is_my_turn() {
-            return Err("Not this player's turn".into());
+            return Err(ActionError::NotYourTurn);
         }
 
+        self.validate_move(view.pid, &view.table, &view.own_hand, &view.rules)
+    }
+
+    /// Validate action of player tpos with the given table and hand, assuming it is their turn
+    pub fn validate_move(&self, tpos: PlayerTpos, table: &Table, hand: &CardSet, rules: &RuleSet) -> Result<(), ActionError> {
+        self.validate_move_with(tpos, table, &table.card_set(), hand, rules)
+    }
+
+    /// Same as validate_move(), but with the table cards (Table::card_set()) computed by the
+    /// caller, so that validating many actions on the same table does not rebuild them.
+    pub(crate) fn validate_move_with(
+        &self,
+        tpos: PlayerTpos,
+        table: &Table,
+        table_cards: &CardSet,
+        hand: &CardSet,
+        rules: &RuleSet,
+    ) -> Result<(), ActionError> {
+        use PlayerAction::*;
+
         // RULE: if a user has made a declaration, they are only allowed to:
-        // - capture (their declaration or otherwise)
+        // - capture (their declaration or otherwise, as long as they keep a card to capture it)
         // - add to their declaration (but not raise)
-        let player_decl = view.table.find_decl_from(view.pid);
+        let player_decl = table.find_decl_from(tpos);
         match (player_decl, self) {
-            (None,    LayDown(c))  => validate_laydown(c.clone(), &view.table),
-            (Some(_), LayDown(c))  => Err("Cannot lay down a card when a declaration of yours exists.".to_string()),
-            (_,       Capture(ca)) => ca.validate_capture(&view.table),
-            (None,    Declare(da)) => da.validate_decl(&view.table, &view.own_hand),
-            (Some(d), Declare(da)) => da.validate_decl_continuation(&d, &view.table, &view.own_hand),
+            (None,    LayDown(c))  => validate_laydown(c.clone(), table),
+            (Some(_), LayDown(_))  => Err(ActionError::LayDownWithDecl),
+            (None,    Capture(ca)) => ca.validate_capture(table),
+            (Some(d), Capture(ca)) => ca.validate_capture_continuation(&d, table, hand, rules),
+            (None,    Declare(da)) => da.validate_decl(table, hand),
+            (Some(d), Declare(da)) => da.validate_decl_continuation(&d, table, hand),
+        }?;
+
+        // check that everything the action refers to exists
+        match self {
+            LayDown(c) => validate_hand_card(c, hand),
+            Capture(ca) => {
+                validate_hand_card(&ca.handcard, hand)?;
+                validate_table_entries(ca.tentries.iter().flatten(), table, table_cards)
+            },
+            Declare(da) => {
+                // by convention, the first entry is the hand card
+                validate_hand_card(&da.handcard(), hand)?;
+                validate_table_entries(da.tentries.iter().flatten().skip(1), table, table_cards)
+            },
+        }
+    }
+}
+
+fn validate_hand_card(card: &Card, hand: &CardSet) -> Result<(), ActionError> {
+    if hand.contains(card) {
+        Ok(())
+    } else {
+        Err(ActionError::CardNotInHand { card: card.clone() })
+    }
+}
+
+/// Check that all entries exist on the table (table_cards: Table::card_set()), and that no entry is
+/// used more than once
+fn validate_table_entries<'a, I>(entries: I, table: &Table, table_cards: &CardSet) -> Result<(), ActionError>
+where
+    I: Iterator<Item=&'a TableEntry>
+{
+    let mut seen_cards = CardSet::empty();
+    let mut seen_decls = vec![];
+    for te in entries {
+        match te {
+            TableEntry::Card(c) => {
+                if !table_cards.contains(c) {
+                    return Err(ActionError::CardNotOnTable { card: c.clone() });
+                }
+                if !seen_cards.insert(c) {
+                    return Err(ActionError::DuplicateTableEntry);
+                }
+            },
+            TableEntry::Decl(d) => {
+                if !table.entries.contains(te) {
+                    return Err(ActionError::DeclNotOnTable);
+                }
+                if seen_decls.contains(&d) {
+                    return Err(ActionError::DuplicateTableEntry);
+                }
+                seen_decls.push(d);
+            },
         }
     }
+
+    Ok(())
 }
 
 /**
@@ -309,9 +428,9 @@ pub struct DeclActionBuilder {
 
 impl DeclActionBuilder {
 
-    pub fn new(hcard: &Card, value: u8) -> Result<DeclActionBuilder, String> {
+    pub fn new(hcard: &Card, value: u8) -> Result<DeclActionBuilder, ActionError> {
         if value < 1 || value > 10 {
-            return Err(format!("Invalid value: {}", value))
+            return Err(ActionError::InvalidDeclValue { value: value })
         }
 
         let mut current = vec![];
@@ -319,7 +438,7 @@ impl DeclActionBuilder {
 
         let tentry = TableEntry::Card(hcard.clone());
         if hcard.rank.0 > value {
-            return Err(format!("Rank of hand card {} is larger than the declaration value {}", hcard.rank.0, value));
+            return Err(ActionError::HandCardExceedsDeclValue { card: hcard.clone(), value: value });
         } else if hcard.rank.0 == value {
             tentries.push(vec![tentry]);
         } else {
@@ -365,7 +484,7 @@ impl DeclActionBuilder {
         true
     }
 
-    pub fn add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), String> {
+    pub fn add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), ActionError> {
         let ret = self.do_add_table_entry(tentry);
         if ret.is_ok() {
             self.entries_set.insert(tentry.clone());
@@ -374,18 +493,18 @@ impl DeclActionBuilder {
         ret
     }
 
-    fn do_add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), String> {
+    fn do_add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), ActionError> {
 
         let current_value = self.current_value() + tentry.value();
         if current_value > self.value {
-            return Err("Cannot add entry to current declaration (it will exceed declared value)".to_string());
+            return Err(ActionError::ExceedsValue);
         }
 
         match tentry {
             TableEntry::Decl(tdecl) if self.has_decl() =>
-                Err("Cannot add more than two declarations".to_string()),
+                Err(ActionError::MultipleDecls),
             TableEntry::Decl(tdecl) if tdecl.is_group() && (tdecl.value() != self.value) =>
-                Err("Cannot add a group declaration to a raiase".to_string()),
+                Err(ActionError::CannotRaiseGroupDecl),
             _ => Ok(()),
         }?;
 
@@ -459,7 +578,7 @@ impl CaptureActionBuilder {
         self.current.iter().fold(0, |acc, x| acc + x.value())
     }
 
-    pub fn add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), String> {
+    pub fn add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), ActionError> {
         let ret = self.do_add_table_entry(tentry);
         if ret.is_ok() {
             self.entries_set.insert(tentry.clone());
@@ -472,13 +591,13 @@ impl CaptureActionBuilder {
         self.entries_set.contains(tentry)
     }
 
-    fn do_add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), String> {
+    fn do_add_table_entry(&mut self, tentry: &TableEntry) -> Result<(), ActionError> {
         let handc = &self.action.handcard;
         let val = self.action.value();
         if handc.rank.is_figure() {
             match tentry {
-                TableEntry::Card(c) if c.rank != handc.rank => Err(format!("You cannot capture {} with {}", c, handc)),
-                TableEntry::Decl(_) => Err("Cannot capture declarations with a figure".to_string()),
+                TableEntry::Card(c) if c.rank != handc.rank => Err(ActionError::FigureCaptureMismatch { card: c.clone(), handcard: handc.clone() }),
+                TableEntry::Decl(_) => Err(ActionError::FigureCapturesDecl),
                 _ => Ok(())
             }?;
         }
@@ -491,13 +610,13 @@ impl CaptureActionBuilder {
             }
 
             TableEntry::Decl(tdecl) => {
-                Err("Declarations must be picked up on their own".to_string())
+                Err(ActionError::DeclNotCapturedAlone)
             },
 
             TableEntry::Card(tdecl) => {
                 let curr_val = self.current_value() + tentry.value();
                 if curr_val > val {
-                    Err("Cannot add entry to current declaration (it will exceed declared value)".to_string())
+                    Err(ActionError::ExceedsValue)
                 } else {
                     self.current.push(tentry.clone());
                     assert_eq!(self.current_value(), curr_val);
@@ -0,0 +1,286 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Computer opponents
+//
+// Bots only get to see what a human player would (a PlayerGameView), and they pick one of the
+// legal actions (see movegen).
+
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use super::card::Card;
+use super::table::{TableEntry, PlayerTpos};
+use super::actions::PlayerAction;
+use super::game::PlayerGameView;
+use super::scoring::{card_value, NCARDS, NCARDS_SCORE};
+use super::mcts::{MctsBot, MctsConfig};
+
+pub trait Bot {
+    /// Choose an action for the player of the view. Returns None if it is not their turn.
+    fn choose_action(&mut self, view: &PlayerGameView) -> Option<PlayerAction>;
+}
+
+/// Plays a random legal action
+#[derive(Debug, Clone)]
+pub struct RandomBot<R: rand::Rng> {
+    rng: R,
+}
+
+impl<R: rand::Rng> RandomBot<R> {
+    pub fn new(rng: R) -> RandomBot<R> {
+        RandomBot { rng: rng }
+    }
+}
+
+impl<R: rand::Rng> Bot for RandomBot<R> {
+    fn choose_action(&mut self, view: &PlayerGameView) -> Option<PlayerAction> {
+        view.legal_actions().choose(&mut self.rng).cloned()
+    }
+}
+
+/// Plays the action with the best immediate expected outcome (see GreedyBot::evaluate())
+#[derive(Debug, Clone, Default)]
+pub struct GreedyBot {}
+
+impl GreedyBot {
+    pub fn new() -> GreedyBot {
+        GreedyBot {}
+    }
+
+    /// Estimate the points that an action is worth to the player.
+    ///
+    /// We consider:
+    ///  - the value of captured cards (scoring::card_value)
+    ///  - every card counts towards the majority bonus (if enabled)
+    ///  - xeres, made by us or made possible for the opponents by leaving a single entry
+    ///  - cards left on the table (laid down or declared) might be captured by the opponents
+    ///  - a declaration is worth its cards if we keep a card to capture it with
+    pub fn evaluate(view: &PlayerGameView, act: &PlayerAction) -> f32 {
+        let est = Estimate::new(view);
+        let rules = &view.rules;
+
+        let mut score = match act {
+            PlayerAction::LayDown(c) => {
+                let val = c.rank.0;
+                let mut score = -est.p_opp_has(val) * est.cards_value(std::iter::once(c));
+                if view.table.nentries() == 0 {
+                    score -= est.p_opp_has(val) * est.xeri_value(val);
+                }
+                score
+            },
+
+            PlayerAction::Capture(ca) => {
+                let val = ca.value();
+                let mut captured = ca.get_table_cards();
+                captured.push(ca.handcard.clone());
+                let used: Vec<&TableEntry> = ca.tentries.iter().flatten().collect();
+                let mut remaining = remaining_entries(view, &used);
+                if rules.forced_captures && !ca.handcard.rank.is_figure() {
+                    remaining.retain(|te| {
+                        if te.value() == val {
+                            captured.extend(entry_cards(te));
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
+
+                let mut score = est.cards_value(captured.iter());
+                if remaining.len() == 0 {
+                    score += est.xeri_value(val);
+                } else if remaining.len() == 1 {
+                    let te = remaining[0];
+                    score -= est.p_opp_has(te.value()) * (est.xeri_value(te.value()) + est.cards_value(entry_cards(te).iter()));
+                }
+                score
+            },
+
+            PlayerAction::Declare(da) => {
+                let val = da.value();
+                let mut decl_cards: Vec<Card> = da.tentries.iter().flatten().flat_map(entry_cards).collect();
+                // by convention, the first entry is the hand card
+                let used: Vec<&TableEntry> = da.tentries.iter().flatten().skip(1).collect();
+                let mut remaining = remaining_entries(view, &used);
+                if rules.forced_declarations {
+                    remaining.retain(|te| {
+                        if te.is_card() && te.value() == val {
+                            decl_cards.extend(entry_cards(te));
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
+
+                let decl_value = est.cards_value(decl_cards.iter());
+                let p_steal = est.p_opp_has(val);
+                // we capture the declaration, unless an opponent does it first
+                let mut score = (1.0 - p_steal) * decl_value - p_steal * decl_value;
+                if remaining.len() == 0 {
+                    score -= p_steal * est.xeri_value(val);
+                }
+                score
+            },
+        };
+
+        // Using the last card that backs our declaration for anything else than capturing the
+        // declaration, means that we will probably lose it.
+        if let Some(decl) = view.table.find_decl_from(view.pid) {
+            let dval = decl.value();
+            let nbacking = view.iter_hand_cards().filter(|c| c.rank.0 == dval).count();
+            let (hcard, captures_decl) = match act {
+                PlayerAction::LayDown(c) => (c.clone(), false),
+                PlayerAction::Declare(da) => (da.handcard(), false),
+                PlayerAction::Capture(ca) => {
+                    let captures_decl = ca.tentries.iter().flatten().any(|te| te.is_decl() && te.ref_decl() == decl);
+                    (ca.handcard.clone(), captures_decl)
+                },
+            };
+            if hcard.rank.0 == dval && nbacking == 1 && !captures_decl {
+                score -= est.cards_value(decl.cards.iter().flatten());
+            }
+        }
+
+        score
+    }
+}
+
+impl Bot for GreedyBot {
+    fn choose_action(&mut self, view: &PlayerGameView) -> Option<PlayerAction> {
+        let mut best: Option<(f32, PlayerAction)> = None;
+        for act in view.legal_actions() {
+            let score = GreedyBot::evaluate(view, &act);
+            match best {
+                Some((best_score, _)) if best_score >= score => (),
+                _ => best = Some((score, act)),
+            }
+        }
+
+        best.map(|(_, act)| act)
+    }
+}
+
+/// Table entries that are not part of used
+fn remaining_entries<'a>(view: &'a PlayerGameView, used: &[&TableEntry]) -> Vec<&'a TableEntry> {
+    view.iter_table_entries()
+        .filter(|te| !used.contains(te))
+        .collect()
+}
+
+fn entry_cards(te: &TableEntry) -> Vec<Card> {
+    match te {
+        TableEntry::Card(c) => vec![c.clone()],
+        TableEntry::Decl(d) => d.cards.iter().flatten().cloned().collect(),
+    }
+}
+
+/// Estimates based on the information available to a player
+struct Estimate<'a> {
+    view: &'a PlayerGameView,
+    /// points for each card because of the majority bonus
+    card_weight: f32,
+    /// number of cards the opponents hold
+    opp_ncards: usize,
+    /// number of cards we have not seen
+    unseen: usize,
+}
+
+impl<'a> Estimate<'a> {
+    fn new(view: &'a PlayerGameView) -> Estimate<'a> {
+        let card_weight = if view.rules.majority_bonus {
+            NCARDS_SCORE as f32 / (NCARDS / 2 + 1) as f32
+        } else {
+            0.0
+        };
+
+        let opp_ncards = view.player_decks_sz
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| is_opponent(view.pid, PlayerTpos(*i as u8)))
+            .map(|(_, n)| n)
+            .sum();
+
+        let seen = view.own_hand.len() + view.iter_table_entries().map(|te| entry_cards(te).len()).sum::<usize>();
+        Estimate {
+            view: view,
+            card_weight: card_weight,
+            opp_ncards: opp_ncards,
+            unseen: NCARDS - seen,
+        }
+    }
+
+    fn cards_value<'b, I: Iterator<Item=&'b Card>>(&self, cards: I) -> f32 {
+        cards.map(|c| card_value(c) as f32 + self.card_weight).sum()
+    }
+
+    fn xeri_value(&self, val: u8) -> f32 {
+        let rules = &self.view.rules;
+        let mut ret = rules.xeri_score as f32;
+        if rules.xeri_figure_double && val > 10 {
+            ret *= 2.0;
+        }
+        ret
+    }
+
+    /// Probability that an opponent has a card with the given value
+    ///
+    /// We do not know which cards have been captured, so we assume that every card we have not
+    /// seen might be in the opponents' hands.
+    fn p_opp_has(&self, val: u8) -> f32 {
+        if self.opp_ncards == 0 || self.unseen == 0 {
+            return 0.0;
+        }
+
+        let seen = self.view.iter_hand_cards().filter(|c| c.rank.0 == val).count()
+            + self.view.iter_table_entries()
+                .flat_map(entry_cards)
+                .filter(|c| c.rank.0 == val)
+                .count();
+        let unseen_val = 4usize.saturating_sub(seen);
+        let p_miss = 1.0 - (unseen_val as f32 / self.unseen as f32);
+        1.0 - p_miss.powi(self.opp_ncards as i32)
+    }
+}
+
+fn is_opponent(me: PlayerTpos, other: PlayerTpos) -> bool {
+    me.0 % 2 != other.0 % 2
+}
+
+/// Available bots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BotKind {
+    Random,
+    Greedy,
+    /// Monte Carlo tree search (see mcts), with the default budget
+    Mcts,
+}
+
+impl BotKind {
+    pub fn all() -> &'static [BotKind] {
+        &[BotKind::Random, BotKind::Greedy, BotKind::Mcts]
+    }
+
+    /// Create a bot of this kind. seed is used for bots that make random choices.
+    pub fn mk_bot(&self, seed: u64) -> Box<dyn Bot + Send> {
+        match self {
+            BotKind::Random => Box::new(RandomBot::new(StdRng::seed_from_u64(seed))),
+            BotKind::Greedy => Box::new(GreedyBot::new()),
+            BotKind::Mcts => Box::new(MctsBot::new(StdRng::seed_from_u64(seed), MctsConfig::default())),
+        }
+    }
+}
+
+impl std::fmt::Display for BotKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BotKind::Random => write!(f, "Random"),
+            BotKind::Greedy => write!(f, "Greedy"),
+            BotKind::Mcts => write!(f, "MCTS"),
+        }
+    }
+}
@@ -68,6 +68,15 @@ impl Rank {
 }
 
 impl Suit {
+    fn index(&self) -> u8 {
+        match self {
+            Self::Spade => 0,
+            Self::Club => 1,
+            Self::Heart => 2,
+            Self::Diamond => 3,
+        }
+    }
+
     pub fn is_red(&self) -> bool {
         match self {
             Self::Spade => false,
@@ -161,6 +170,207 @@ impl std::fmt::Debug for Card {
     }
 }
 
+/**
+ * CardSet
+ */
+
+/// A set of cards, stored as a 52-bit bitmap
+///
+/// Card with suit s and rank r is bit (s * 13 + r - 1), so that iterating the set yields cards
+/// ordered by suit and then by rank.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct CardSet(u64);
+
+const NRANKS: u8 = 13;
+const SUITS: [Suit; 4] = [Suit::Spade, Suit::Club, Suit::Heart, Suit::Diamond];
+
+impl Card {
+    /// Index of the card in a CardSet (0..52)
+    pub fn index(&self) -> u8 {
+        self.suit.index() * NRANKS + self.rank.0 - 1
+    }
+
+    pub fn from_index(idx: u8) -> Card {
+        assert!(idx < 4 * NRANKS, "Invalid card index");
+        Card {
+            suit: SUITS[(idx / NRANKS) as usize],
+            rank: Rank(idx % NRANKS + 1),
+        }
+    }
+}
+
+impl CardSet {
+    pub fn empty() -> CardSet {
+        CardSet(0)
+    }
+
+    pub fn full() -> CardSet {
+        CardSet((1u64 << (4 * NRANKS)) - 1)
+    }
+
+    /// All the cards of the given rank
+    pub fn rank_mask(rank: Rank) -> CardSet {
+        let r = 1u64 << (rank.0 - 1);
+        CardSet(r | r << NRANKS | r << (2 * NRANKS) | r << (3 * NRANKS))
+    }
+
+    /// All the cards with the given value (the value of a card is its rank)
+    pub fn value_mask(val: u8) -> CardSet {
+        match Rank::try_from(val) {
+            Ok(rank) => Self::rank_mask(rank),
+            Err(_) => Self::empty(),
+        }
+    }
+
+    /// All the cards of the given suit
+    pub fn suit_mask(suit: Suit) -> CardSet {
+        CardSet(((1u64 << NRANKS) - 1) << (suit.index() * NRANKS))
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(&self, card: &Card) -> bool {
+        self.0 & (1u64 << card.index()) != 0
+    }
+
+    /// Returns true if the card was not in the set
+    pub fn insert(&mut self, card: &Card) -> bool {
+        let ret = !self.contains(card);
+        self.0 |= 1u64 << card.index();
+        ret
+    }
+
+    /// Returns true if the card was in the set
+    pub fn remove(&mut self, card: &Card) -> bool {
+        let ret = self.contains(card);
+        self.0 &= !(1u64 << card.index());
+        ret
+    }
+
+    pub fn union(&self, other: CardSet) -> CardSet {
+        CardSet(self.0 | other.0)
+    }
+
+    pub fn intersection(&self, other: CardSet) -> CardSet {
+        CardSet(self.0 & other.0)
+    }
+
+    pub fn difference(&self, other: CardSet) -> CardSet {
+        CardSet(self.0 & !other.0)
+    }
+
+    pub fn is_subset(&self, other: CardSet) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    /// Cards of the set with the given value
+    pub fn with_value(&self, val: u8) -> CardSet {
+        self.intersection(Self::value_mask(val))
+    }
+
+    pub fn has_value(&self, val: u8) -> bool {
+        !self.with_value(val).is_empty()
+    }
+
+    pub fn iter(&self) -> CardSetIter {
+        CardSetIter(self.0)
+    }
+}
+
+/// Iterator over the cards of a CardSet, in index order
+#[derive(Debug, Clone)]
+pub struct CardSetIter(u64);
+
+impl Iterator for CardSetIter {
+    type Item = Card;
+
+    fn next(&mut self) -> Option<Card> {
+        if self.0 == 0 {
+            return None;
+        }
+        let idx = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Some(Card::from_index(idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.0.count_ones() as usize;
+        (n, Some(n))
+    }
+}
+
+impl IntoIterator for CardSet {
+    type Item = Card;
+    type IntoIter = CardSetIter;
+
+    fn into_iter(self) -> CardSetIter {
+        self.iter()
+    }
+}
+
+impl<'a> std::iter::FromIterator<&'a Card> for CardSet {
+    fn from_iter<I: IntoIterator<Item=&'a Card>>(iter: I) -> CardSet {
+        let mut ret = CardSet::empty();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl std::iter::FromIterator<Card> for CardSet {
+    fn from_iter<I: IntoIterator<Item=Card>>(iter: I) -> CardSet {
+        let mut ret = CardSet::empty();
+        for c in iter {
+            ret.insert(&c);
+        }
+        ret
+    }
+}
+
+impl<'a> Extend<&'a Card> for CardSet {
+    fn extend<I: IntoIterator<Item=&'a Card>>(&mut self, iter: I) {
+        for c in iter {
+            self.insert(c);
+        }
+    }
+}
+
+impl std::ops::BitOr for CardSet {
+    type Output = CardSet;
+    fn bitor(self, rhs: CardSet) -> CardSet {
+        self.union(rhs)
+    }
+}
+
+impl std::ops::BitAnd for CardSet {
+    type Output = CardSet;
+    fn bitand(self, rhs: CardSet) -> CardSet {
+        self.intersection(rhs)
+    }
+}
+
+impl std::ops::Sub for CardSet {
+    type Output = CardSet;
+    fn sub(self, rhs: CardSet) -> CardSet {
+        self.difference(rhs)
+    }
+}
+
+impl std::fmt::Debug for CardSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
 #[test]
 fn try_from_tests() {
     // rank
@@ -173,3 +383,31 @@ fn try_from_tests() {
     // card
     assert_eq!(Card::try_from(['♥','T']).unwrap(), Card{suit: Suit::Heart, rank: Rank(10)});
 }
+
+#[test]
+fn cardset_tests() {
+    let full = CardSet::full();
+    assert_eq!(full.len(), 52);
+    for (i, c) in full.iter().enumerate() {
+        assert_eq!(c.index() as usize, i);
+        assert_eq!(Card::from_index(i as u8), c);
+    }
+
+    let mut set = CardSet::empty();
+    let d5 = Card::try_from("D5").unwrap();
+    let s5 = Card::try_from("S5").unwrap();
+    assert!(set.insert(&d5));
+    assert!(!set.insert(&d5));
+    set.insert(&s5);
+    set.insert(&Card::try_from("HK").unwrap());
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.with_value(5).iter().collect::<Vec<_>>(), vec![s5.clone(), d5.clone()]);
+    assert!(!set.has_value(6));
+    assert_eq!((set & CardSet::suit_mask(Suit::Heart)).len(), 1);
+    assert_eq!((full - set).len(), 49);
+    assert!(set.is_subset(full));
+    assert_eq!(CardSet::rank_mask(Rank(13)).len(), 4);
+    assert!(CardSet::value_mask(14).is_empty());
+    assert!(set.remove(&d5));
+    assert!(!set.contains(&d5));
+}
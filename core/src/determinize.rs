@@ -0,0 +1,167 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Sampling of hidden information
+//
+// A player knows their own hand and every card that has been played in public: the cards on the
+// table, the captured piles, and the cards of the performed actions. The hands of the other
+// players and the main deck are hidden. A determinization is a complete Game, where the hidden
+// cards are dealt randomly (uniformly) from the cards that the player has not seen.
+
+use rand::seq::SliceRandom;
+
+use super::card::{Card, CardSet};
+use super::deck::Deck;
+use super::table::TableEntry;
+use super::actions::{PlayerAction, PerformedAction};
+use super::game::{Game, PlayerGameView};
+
+/// Number of attempts to find a deal where every declaration is backed (see determinize())
+const MAX_DEAL_ATTEMPTS: usize = 100;
+
+/// Cards that are known to everyone
+#[derive(Debug, Clone, Default)]
+pub struct PublicCards {
+    cards: CardSet,
+}
+
+impl PublicCards {
+    pub fn new() -> PublicCards {
+        PublicCards { cards: CardSet::empty() }
+    }
+
+    /// Public cards of a view: the table and the captured piles
+    pub fn from_view(view: &PlayerGameView) -> PublicCards {
+        let mut ret = PublicCards::new();
+        for te in view.iter_table_entries() {
+            ret.add_table_entry(te);
+        }
+        for captures in view.captures.iter() {
+            ret.cards = ret.cards | captures.cards();
+        }
+        ret
+    }
+
+    fn add_table_entry(&mut self, te: &TableEntry) {
+        match te {
+            TableEntry::Card(c) => { self.cards.insert(c); },
+            TableEntry::Decl(d) => self.cards.extend(d.cards.iter().flatten()),
+        }
+    }
+
+    /// Add the cards of a performed action (the hand card and every table card it involved)
+    pub fn add_action(&mut self, pa: &PerformedAction) {
+        match &pa.action {
+            PlayerAction::LayDown(c) => { self.cards.insert(c); },
+            PlayerAction::Capture(ca) => {
+                self.cards.insert(&ca.handcard);
+                for te in ca.tentries.iter().flatten() {
+                    self.add_table_entry(te);
+                }
+            },
+            PlayerAction::Declare(da) => {
+                for te in da.tentries.iter().flatten() {
+                    self.add_table_entry(te);
+                }
+            },
+        }
+        self.cards.extend(pa.forced_cards.iter());
+    }
+
+    pub fn add_actions<'a, I: Iterator<Item=&'a PerformedAction>>(&mut self, actions: I) {
+        for pa in actions {
+            self.add_action(pa);
+        }
+    }
+
+    pub fn contains(&self, card: &Card) -> bool {
+        self.cards.contains(card)
+    }
+
+    pub fn ncards(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn cards(&self) -> CardSet {
+        self.cards
+    }
+
+    /// Cards that the player of the view has not seen (i.e., hidden from them)
+    pub fn unseen(&self, view: &PlayerGameView) -> Vec<Card> {
+        (CardSet::full() - self.cards - view.hand_set()).iter().collect()
+    }
+}
+
+/// Sample a game consistent with the view and the public actions of the current game.
+///
+/// The hands of the other players and the main deck are drawn uniformly from the unseen cards,
+/// under the constraint that a player with a declaration on the table holds a card to capture it.
+/// If no such deal is found after a number of attempts, the backing cards are dealt first.
+///
+/// Debug games are not dealt from a full deck, so there might be more unseen cards than hidden
+/// ones. Panics if there are less (i.e., if the view and the history are inconsistent).
+pub fn determinize<R: rand::Rng + Clone>(view: &PlayerGameView, history: &[PerformedAction], rng: &mut R) -> Game<R> {
+    let mut public = PublicCards::from_view(view);
+    public.add_actions(history.iter());
+    let mut unseen = public.unseen(view);
+
+    let nplayers = view.player_decks_sz.len();
+    let nhidden = view.main_deck_sz
+        + view.player_decks_sz
+            .iter()
+            .enumerate()
+            .filter(|(p, _)| *p != view.pid.0 as usize)
+            .map(|(_, n)| n)
+            .sum::<usize>();
+    assert!(unseen.len() >= nhidden, "not enough unseen cards");
+
+    // (player, value) of the declarations that need a backing card
+    let decls: Vec<(usize, u8)> = view.iter_table_entries()
+        .filter_map(|te| match te {
+            TableEntry::Decl(d) if d.player != view.pid => Some((d.player.0 as usize, d.value())),
+            _ => None,
+        })
+        .collect();
+
+    for _ in 0..MAX_DEAL_ATTEMPTS {
+        unseen.shuffle(rng);
+        let (hands, main_deck) = deal(view, vec![Deck::empty(); nplayers], unseen.clone());
+        let backed = decls.iter().all(|(p, val)| hands[*p].cards.iter().any(|c| c.rank.0 == *val));
+        if backed {
+            return Game::from_view(view, hands, main_deck, rng.clone());
+        }
+    }
+
+    let mut hands = vec![Deck::empty(); nplayers];
+    for (p, val) in decls.iter() {
+        if hands[*p].ncards() >= view.player_decks_sz[*p] || hands[*p].cards.iter().any(|c| c.rank.0 == *val) {
+            continue;
+        }
+        if let Some(idx) = unseen.iter().position(|c| c.rank.0 == *val) {
+            hands[*p].push(unseen.swap_remove(idx));
+        }
+    }
+    let (hands, main_deck) = deal(view, hands, unseen);
+    Game::from_view(view, hands, main_deck, rng.clone())
+}
+
+/// Fill up the hands of the other players and the main deck, in that order
+fn deal(view: &PlayerGameView, mut hands: Vec<Deck>, mut cards: Vec<Card>) -> (Vec<Deck>, Deck) {
+    for (p, hand) in hands.iter_mut().enumerate() {
+        if p == view.pid.0 as usize {
+            continue;
+        }
+        while hand.ncards() < view.player_decks_sz[p] {
+            hand.push(cards.pop().expect("enough unseen cards"));
+        }
+    }
+
+    let mut main_deck = Deck::empty();
+    while main_deck.ncards() < view.main_deck_sz {
+        main_deck.push(cards.pop().expect("enough unseen cards"));
+    }
+    (hands, main_deck)
+}
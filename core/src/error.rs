@@ -4,6 +4,9 @@
 // vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
 //
 
+use serde::{Deserialize, Serialize};
+
+use super::card::Card;
 
 #[derive(Debug)]
 pub enum Error {
@@ -16,3 +19,124 @@ pub enum Error {
     /// str -> Card coversion failed (str has a different length than two)
     InvalidStringLen,
 }
+
+/// Reasons for rejecting a player action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionError {
+    /// It is not the player's turn
+    NotYourTurn,
+    /// The game is not in a state where actions can be applied (e.g., round or game is done)
+    GameNotInProgress,
+
+    /// A card cannot be laid down if a table entry has the same value
+    LayDownMatchesTableValue { card: Card },
+    /// A card cannot be laid down while the player has a declaration on the table
+    LayDownWithDecl,
+
+    /// Capture does not include any table entries
+    EmptyCapture,
+    /// Not all capture groups have the value of the hand card
+    CaptureGroupValueMismatch,
+    /// Declarations can only be captured on their own
+    DeclNotCapturedAlone,
+    /// Figures can only capture a single card per group
+    FigureMultiCapture,
+    /// Figures can only capture cards of the same rank
+    FigureCaptureMismatch { card: Card, handcard: Card },
+    /// Figures cannot capture declarations
+    FigureCapturesDecl,
+    /// If 2 same figures exist on the table, only one can be captured
+    OnlyOneOfTwoFigures,
+    /// If 3 same figures exist on the table, all three must be captured
+    MustCaptureAllThreeFigures,
+    /// Invalid figure capture
+    InvalidFigureCapture,
+    /// A player needs to keep a card to capture their declaration with
+    LastBackingCard { value: u8 },
+
+    /// Declaration does not include any entries
+    EmptyDecl,
+    /// The first entry of a declaration has to be the hand card
+    DeclFirstEntryNotCard,
+    /// Not all declaration groups have the same value
+    DeclGroupValueMismatch,
+    /// A declaration needs more than one card
+    DeclTooFewCards,
+    /// Declarations values have to be between 1 and 10
+    InvalidDeclValue { value: u8 },
+    /// The hand card has a larger rank than the declaration value
+    HandCardExceedsDeclValue { card: Card, value: u8 },
+    /// There is no hand card (other than the one played) to back the declaration
+    NoBackingCard { value: u8 },
+    /// Group declarations have to be on their own
+    GroupDeclCombined,
+    /// Group declarations cannot be raised
+    CannotRaiseGroupDecl,
+    /// More than one declarations cannot be combined
+    MultipleDecls,
+    /// A player with a declaration on the table cannot create a new one
+    NewDeclWhileOwnExists,
+    /// A player can only act on their latest declaration
+    NotLatestDecl,
+    /// A player cannot raise their own declaration
+    CannotRaiseOwnDecl,
+
+    /// Adding the entry exceeds the value of the capture or declaration
+    ExceedsValue,
+
+    /// The card is not in the player's hand
+    CardNotInHand { card: Card },
+    /// The card is not on the table
+    CardNotOnTable { card: Card },
+    /// The declaration is not on the table
+    DeclNotOnTable,
+    /// The same table entry is used more than once
+    DuplicateTableEntry,
+}
+
+impl std::fmt::Display for ActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use ActionError::*;
+        match self {
+            NotYourTurn => write!(f, "Not this player's turn"),
+            GameNotInProgress => write!(f, "Invalid state for applying action"),
+
+            LayDownMatchesTableValue { card } => write!(f, "You cannot lay down a card ({}) if a card or declaration with the same value exists on the table.", card),
+            LayDownWithDecl => write!(f, "Cannot lay down a card when a declaration of yours exists."),
+
+            EmptyCapture => write!(f, "Invalid capture: empty"),
+            CaptureGroupValueMismatch => write!(f, "Invalid capture: Not all groups have the same value"),
+            DeclNotCapturedAlone => write!(f, "Invalid capture: declarations can only be captured on their own"),
+            FigureMultiCapture => write!(f, "Invalid capture: Figures cannot be used to capture multiple cards"),
+            FigureCaptureMismatch { card, handcard } => write!(f, "You cannot capture {} with {}", card, handcard),
+            FigureCapturesDecl => write!(f, "Cannot capture declarations with a figure"),
+            OnlyOneOfTwoFigures => write!(f, "Invalid capture: if 2 same figures exist on the table, only one can be captured."),
+            MustCaptureAllThreeFigures => write!(f, "Invalid capture: if 3 same figures exist on the table, all three must be captured."),
+            InvalidFigureCapture => write!(f, "Invalid capture with a figure"),
+            LastBackingCard { value } => write!(f, "Invalid capture: You need to keep a value {} card to capture your declaration", value),
+
+            EmptyDecl => write!(f, "Invalid declaration: empty"),
+            DeclFirstEntryNotCard => write!(f, "First entry is not a card"),
+            DeclGroupValueMismatch => write!(f, "Invalid declaration: Not all groups have the same value"),
+            DeclTooFewCards => write!(f, "Invalid declaration: Need more than one cards"),
+            InvalidDeclValue { value } => write!(f, "Invalid declaration: invalid value: {}", value),
+            HandCardExceedsDeclValue { card, value } => write!(f, "Rank of hand card {} is larger than the declaration value {}", card, value),
+            NoBackingCard { value } => write!(f, "Invalid declaration: There is no value {} card in your hand", value),
+            GroupDeclCombined => write!(f, "Invalid declaration: group declarations cannot be combined with other cards"),
+            CannotRaiseGroupDecl => write!(f, "You may not raise group declarations"),
+            MultipleDecls => write!(f, "You may not combine more than one declaration to form a new one"),
+            NewDeclWhileOwnExists => write!(f, "You cannot create a new declaration if you have one on the table"),
+            NotLatestDecl => write!(f, "You cannot act on a declaration other than your lastest one"),
+            CannotRaiseOwnDecl => write!(f, "You may not raise your declaration"),
+
+            ExceedsValue => write!(f, "Cannot add entry (it will exceed the value)"),
+
+            CardNotInHand { card } => write!(f, "Card {} is not in your hand", card),
+            CardNotOnTable { card } => write!(f, "Card {} is not on the table", card),
+            DeclNotOnTable => write!(f, "Declaration is not on the table"),
+            DuplicateTableEntry => write!(f, "The same table entry cannot be used more than once"),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
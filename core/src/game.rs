@@ -9,14 +9,19 @@ use std::clone::Clone;
 use serde::{Deserialize, Serialize};
 
 use super::deck::Deck;
-use super::card::Card;
+use super::card::{Card, CardSet};
 use super::table::{Table, Declaration, PlayerTpos, TableEntry};
 use super::actions::{PlayerAction, DeclAction, CaptureAction, PerformedAction};
 use super::scoring::{Captures, ScoreSheet};
+use super::rules::{RuleSet, HAND_SIZE};
+use super::movegen;
+use super::error::ActionError;
+use super::turn_timer::{TurnTimeout, TimeoutPolicy};
 
 //  Rules:
 //  - https://www.pagat.com/fishing/diloti.html
-//  We implement a variant with forced captures and also forced declarations.
+//  By default, we implement a variant with forced captures and also forced declarations (see
+//  RuleSet for the supported variations).
 //
 // Extended rules:
 //  - https://www.pagat.com/fishing/diloti.html
@@ -25,7 +30,7 @@ use super::scoring::{Captures, ScoreSheet};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Player {
-    pub hand: Deck,
+    pub hand: CardSet,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -47,10 +52,26 @@ pub struct Team {
 pub enum GameState {
     NextTurn(PlayerTpos),
     RoundDone,
+    /// Game sheets and total scores for each team
     GameDone(Vec<(ScoreSheet, usize)>),
+    /// Same as GameDone, but the match is over. The second argument is the index of the winning
+    /// team.
+    MatchDone(Vec<(ScoreSheet, usize)>, usize),
 }
 
-#[derive(Clone, Debug)]
+/// Typical target score for a match
+pub const DEFAULT_TARGET_SCORE: usize = 61;
+
+/// Maximum number of games we keep in the history. Games without a target score never end a
+/// match, so we drop the oldest ones. A match with dropped games cannot be replayed.
+pub const MAX_HISTORY_GAMES: usize = 32;
+
+/// A game, including its RNG (used for dealing new games)
+///
+/// The full state can be (de)serialized if the RNG can, e.g., rand_pcg::Pcg64 with the serde1
+/// feature. Restoring a snapshot restores the RNG state, so the following deals are the same.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(serialize = "R: Serialize", deserialize = "R: Deserialize<'de>"))]
 pub struct Game<R: rand::Rng + Clone> {
     pub(crate) table: Table,
     pub(crate) main_deck: Deck,
@@ -64,16 +85,60 @@ pub struct Game<R: rand::Rng + Clone> {
 
     pub(crate) last_action: Option<PerformedAction>,
     initst_opt: Option<InitState>,
+    /// The games of the current match, including the current one (see GameRecord), up to
+    /// MAX_HISTORY_GAMES
+    pub(crate) history: Vec<GameRecord>,
+    /// Number of games of the current match that were dropped from the history
+    #[serde(default)]
+    pub(crate) dropped_games: usize,
+    /// Log of the previous match, if there was one
+    #[serde(default)]
+    last_match: Option<ReplayLog>,
+
+    /// Score that a team needs to reach to win the match (None: games continue forever)
+    pub(crate) target_score: Option<usize>,
+    pub(crate) rules: RuleSet,
 
     rng: R,
 }
 
-// used so we can override it for debubbing purposes
-#[derive(Clone, Debug)]
-struct InitState {
-    deck: Deck,
-    table: Table,
-    hands: Vec<Deck>
+/// Initial state of a game: the table, the hands of the first round, and the rest of the deck
+///
+/// Also used so we can override it for debubbing purposes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitState {
+    pub deck: Deck,
+    pub table: Table,
+    pub hands: Vec<Deck>
+}
+
+/// Record of a single game: how it was dealt and every action performed (in all its rounds)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub first_player: PlayerTpos,
+    pub init: InitState,
+    pub actions: Vec<PerformedAction>,
+    /// Turns where the player ran out of time (see turn_timer.rs)
+    #[serde(default)]
+    pub timeouts: Vec<TurnTimeout>,
+    /// Scores of the teams when the game started
+    #[serde(default)]
+    pub scores: Vec<usize>,
+}
+
+/// Everything needed to reproduce a game from the beginning (see Game::replay())
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayLog {
+    pub nplayers: usize,
+    pub rules: RuleSet,
+    pub target_score: Option<usize>,
+    /// All games use the same (debug) initial state
+    pub fixed_init: bool,
+    pub games: Vec<GameRecord>,
+    /// Number of games at the start of the match that are missing from games (see
+    /// MAX_HISTORY_GAMES)
+    #[serde(default)]
+    pub dropped_games: usize,
 }
 
 /// This a player's point of view of the game
@@ -81,78 +146,305 @@ struct InitState {
 pub struct PlayerGameView {
     pub pid: PlayerTpos,
     pub table: Table,
-    pub own_hand: Deck,
+    pub own_hand: CardSet,
+    pub state: GameState,
+
+    pub last_action: Option<PerformedAction>,
+    pub main_deck_sz: usize,
+    pub player_decks_sz: Vec<usize>,
+    pub rules: RuleSet,
+
+    /// Cards captured by each team in the current game (everyone has seen them being captured)
+    pub captures: Vec<Captures>,
+    /// Team that captured last (gets the cards left on the table at the end of the game)
+    pub last_team_captured: usize,
+    /// Set if the last player ran out of time
+    pub last_timeout: Option<TurnTimeout>,
+    /// When the current turn times out, in milliseconds since the UNIX epoch (set by the server,
+    /// if the game has a turn timer)
+    #[serde(default)]
+    pub turn_deadline: Option<u64>,
+}
+
+/// A spectator's point of view of the game: everything that is public, but no hands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpectatorGameView {
+    pub table: Table,
     pub state: GameState,
 
     pub last_action: Option<PerformedAction>,
     pub main_deck_sz: usize,
     pub player_decks_sz: Vec<usize>,
+    pub rules: RuleSet,
+
+    pub captures: Vec<Captures>,
+    pub last_team_captured: usize,
+    pub last_timeout: Option<TurnTimeout>,
+    #[serde(default)]
+    pub turn_deadline: Option<u64>,
+    /// Match score of each team
+    pub scores: Vec<usize>,
+
+    /// The cards that each player played in the game. Only revealed once the game is done, and if
+    /// requested (see Game::get_spectator_game_view()).
+    pub revealed_hands: Option<Vec<Deck>>,
 }
 
 impl<R: rand::Rng + Clone> Game<R> {
 
 
     pub fn new_1p_debug(rng: R, table: Table, hand: Deck) -> Game<R> {
+        Self::new_1p_debug_with_rules(rng, RuleSet::default(), table, hand)
+    }
+
+    pub fn new_1p_debug_with_rules(rng: R, rules: RuleSet, table: Table, hand: Deck) -> Game<R> {
         let initst = InitState {
             deck: Deck::empty(),
             table: table,
             hands: vec![hand],
         };
-        Self::init(1, Some(initst), rng)
+        Self::init(1, rules, Some(initst), rng)
     }
 
     pub fn new_1p(rng: R) -> Game<R> {
-        Self::init(1, None, rng)
+        Self::init(1, RuleSet::default(), None, rng)
     }
 
     pub fn new_2p(rng: R) -> Game<R> {
-        Self::init(2, None, rng)
+        Self::init(2, RuleSet::default(), None, rng)
     }
 
     pub fn new_4p(rng: R) -> Game<R> {
-        Self::init(4, None, rng)
+        Self::init(4, RuleSet::default(), None, rng)
+    }
+
+    /// New game with the given variant. Rules should be validated with RuleSet::validate().
+    pub fn new_with_rules(nplayers: usize, rules: RuleSet, rng: R) -> Game<R> {
+        Self::init(nplayers, rules, None, rng)
     }
 
-    fn init(nplayers: usize, initst_opt: Option<InitState>, mut rng: R) -> Game<R> {
+    fn init(nplayers: usize, rules: RuleSet, initst_opt: Option<InitState>, mut rng: R) -> Game<R> {
 
         assert!(nplayers == 1 || nplayers == 2 || nplayers == 4);
+        assert!(rules.validate(nplayers).is_ok());
 
-        let mut initst = initst_opt.clone().unwrap_or_else(|| InitState::default(nplayers, &mut rng));
+        let initst = initst_opt.clone().unwrap_or_else(|| InitState::default(nplayers, &rules, &mut rng));
         assert!(initst.hands.len() == nplayers);
         let nteams = if nplayers == 1 { 1 } else { 2 };
 
         let first_player = PlayerTpos(0);
+        let record = GameRecord {
+            first_player: first_player,
+            init: initst.clone(),
+            actions: vec![],
+            timeouts: vec![],
+            scores: vec![0; nteams],
+        };
         let game = Game {
             table: initst.table,
             main_deck: initst.deck,
-            players: initst.hands.drain(..).map(|hd| Player { hand: hd }).collect(),
+            players: initst.hands.iter().map(|hd| Player::new(hd)).collect(),
             first_player: first_player,
             state: GameState::NextTurn(first_player),
             teams: (0..nteams).map( |_| Team::default()).collect(),
             last_team_captured: 0,
             last_action: None,
             initst_opt: initst_opt,
+            history: vec![record],
+            dropped_games: 0,
+            last_match: None,
+            target_score: None,
+            rules: rules,
             rng: rng,
         };
 
         game
     }
 
+    /// Deal all the following games the same way as the current one (used for debugging)
+    pub fn fix_init(&mut self) {
+        let record = self.history.last().expect("current game record");
+        self.initst_opt = Some(record.init.clone());
+    }
+
+    pub fn set_target_score(&mut self, target: Option<usize>) {
+        self.target_score = target;
+    }
+
+    pub fn target_score(&self) -> Option<usize> {
+        self.target_score
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn nplayers(&self) -> usize {
+        self.players.len()
+    }
+
     pub fn next_game(&mut self) {
-        let nplayers = self.players.len();
         assert!(self.state.is_game_done());
-        let mut initst = self.initst_opt.clone().unwrap_or_else(|| InitState::default(nplayers, &mut self.rng));
+        self.start_game();
+    }
+
+    /// Start a new match: team scores are reset
+    pub fn next_match(&mut self) {
+        assert!(self.state.is_match_done());
+        self.last_match = Some(self.replay_log());
+        self.history.clear();
+        self.dropped_games = 0;
+        self.reset_scores();
+        self.start_game();
+    }
+
+    fn reset_scores(&mut self) {
+        for team in self.teams.iter_mut() {
+            team.score = 0;
+        }
+    }
+
+    fn start_game(&mut self) {
+        let nplayers = self.players.len();
+        let rules = &self.rules;
+        let rng = &mut self.rng;
+        let initst = self.initst_opt.clone().unwrap_or_else(|| InitState::default(nplayers, rules, rng));
+        self.start_game_from(initst);
+    }
+
+    fn start_game_from(&mut self, initst: InitState) {
+        let nplayers = self.players.len();
         assert!(initst.hands.len() == nplayers);
+        self.first_player = PlayerTpos((self.first_player.0 + 1) % (nplayers as u8) );
+        if self.history.len() >= MAX_HISTORY_GAMES {
+            let ndrop = self.history.len() + 1 - MAX_HISTORY_GAMES;
+            self.history.drain(..ndrop);
+            self.dropped_games += ndrop;
+        }
+        self.history.push(GameRecord {
+            first_player: self.first_player,
+            init: initst.clone(),
+            actions: vec![],
+            timeouts: vec![],
+            scores: self.teams.iter().map(|t| t.score).collect(),
+        });
+
         self.table = initst.table;
         self.main_deck = initst.deck;
-        self.players = initst.hands.drain(..).map(|hd| Player { hand: hd }).collect();
-        self.first_player = PlayerTpos((self.first_player.0 + 1) % (nplayers as u8) );
+        self.players = initst.hands.iter().map(|hd| Player::new(hd)).collect();
         self.last_action = None;
 
         self.state = GameState::NextTurn(self.first_player);
     }
 
-    fn team_idx(&mut self, tpos: PlayerTpos) -> usize {
+    /// The games of the current match, including the current one. Only the last
+    /// MAX_HISTORY_GAMES are kept (see games_played()).
+    pub fn history(&self) -> &[GameRecord] {
+        &self.history
+    }
+
+    /// Number of games of the current match, including the current one and the games that were
+    /// dropped from the history
+    pub fn games_played(&self) -> usize {
+        self.dropped_games + self.history.len()
+    }
+
+    /// Build a log that can be used to reproduce the current match with Game::replay()
+    pub fn replay_log(&self) -> ReplayLog {
+        ReplayLog {
+            nplayers: self.players.len(),
+            rules: self.rules.clone(),
+            target_score: self.target_score,
+            fixed_init: self.initst_opt.is_some(),
+            games: self.history.clone(),
+            dropped_games: self.dropped_games,
+        }
+    }
+
+    /// Log of the previous match (None if this is the first one)
+    pub fn last_match_log(&self) -> Option<&ReplayLog> {
+        self.last_match.as_ref()
+    }
+
+    /// Reproduce a game by re-executing all the actions of a replay log.
+    ///
+    /// New rounds are dealt, and new games (or matches) are started as needed. If the last
+    /// action finished a round, the returned game is left in the RoundDone state. rng is only used
+    /// for games that start after the replay. Logs of matches that dropped games from their
+    /// history (see MAX_HISTORY_GAMES) are incomplete, and cannot be replayed.
+    pub fn replay(log: &ReplayLog, rng: R) -> Result<Game<R>, String> {
+        if log.dropped_games > 0 {
+            return Err(format!("Incomplete replay log: {} games were dropped", log.dropped_games));
+        }
+        let first = log.games.first().ok_or_else(|| "Empty replay log".to_string())?;
+        log.rules.validate(log.nplayers)?;
+        if first.init.hands.len() != log.nplayers {
+            return Err(format!("Invalid replay log: expecting {} hands", log.nplayers));
+        }
+
+        let mut game = Self::init(log.nplayers, log.rules.clone(), Some(first.init.clone()), rng);
+        game.set_target_score(log.target_score);
+        // NB: the first game is restored as recorded (first player and scores)
+        if first.first_player.0 as usize >= log.nplayers || first.scores.len() > game.teams.len() {
+            return Err("Invalid replay log: invalid first game".to_string());
+        }
+        game.first_player = first.first_player;
+        game.state = GameState::NextTurn(first.first_player);
+        for (team, score) in game.teams.iter_mut().zip(first.scores.iter()) {
+            team.score = *score;
+        }
+        game.history[0] = first.clone();
+        game.history[0].actions.clear();
+        game.history[0].timeouts.clear();
+        if !log.fixed_init {
+            game.initst_opt = None;
+        }
+
+        for (gi, record) in log.games.iter().enumerate() {
+            if gi > 0 {
+                match game.state {
+                    GameState::GameDone(_) => (),
+                    GameState::MatchDone(_, _) => game.reset_scores(),
+                    _ => return Err(format!("game {}: previous game is not done ({:?})", gi, game.state)),
+                }
+                if record.init.hands.len() != log.nplayers {
+                    return Err(format!("game {}: expecting {} hands", gi, log.nplayers));
+                }
+                game.start_game_from(record.init.clone());
+            }
+
+            if record.first_player != game.first_player {
+                return Err(format!("game {}: unexpected first player {}", gi, record.first_player));
+            }
+
+            // NB: timeouts come before the action that was played for the player
+            for ai in 0..=record.actions.len() {
+                if game.state.is_round_done() {
+                    game.new_round();
+                }
+
+                for t in record.timeouts.iter().filter(|t| t.nactions == ai) {
+                    game.replay_timeout(t).map_err(|err| format!("game {} timeout {}: {}", gi, ai, err))?;
+                }
+
+                let pa = match record.actions.get(ai) {
+                    Some(x) => x,
+                    None => break,
+                };
+
+                game.apply_action_mut(pa.player, pa.action.clone())
+                    .map_err(|err| format!("game {} action {}: {}", gi, ai, err))?;
+
+                if game.last_action.as_ref() != Some(pa) {
+                    return Err(format!("game {} action {}: result differs from the log ({:?})", gi, ai, game.last_action));
+                }
+            }
+        }
+
+        Ok(game)
+    }
+
+    pub(crate) fn team_idx(&self, tpos: PlayerTpos) -> usize {
         let idx = (tpos.0 % 2) as usize;
         assert!(idx < self.teams.len());
         idx
@@ -181,11 +473,14 @@ impl<R: rand::Rng + Clone> Game<R> {
     pub fn deal_hands(&mut self) {
         assert!(self.all_players_done());
         assert!(self.main_deck.ncards() > 0);
-        let hand_size = 6;
+        // NB: depending on the table size, the last round might have fewer cards
+        let nplayers = self.players.len();
+        let hand_size = std::cmp::min(HAND_SIZE, self.main_deck.ncards() / nplayers);
+        assert!(hand_size > 0);
         for _ in 0..hand_size {
-            for p in 0..self.players.len() {
-                let card = self.main_deck.pop().expect("deal a handsize of 6");
-                self.players[p].hand.push(card)
+            for p in 0..nplayers {
+                let card = self.main_deck.pop().expect("deal a hand");
+                self.players[p].hand.insert(&card);
             }
         }
     }
@@ -200,8 +495,10 @@ impl<R: rand::Rng + Clone> Game<R> {
 
     pub fn remove_player_card(&mut self, tpos:  PlayerTpos, c: &Card) -> Option<Card> {
         let player : &mut Player = self.get_player_mut(tpos)?;
-        let pos = player.iter_hand_cards().position(|x| c == x )?;
-        Some(player.hand.cards.remove(pos))
+        match player.hand.remove(c) {
+            true => Some(c.clone()),
+            false => None,
+        }
     }
 
     pub fn remove_table_card(&mut self, c: &Card) -> Option<Card> {
@@ -224,24 +521,142 @@ impl<R: rand::Rng + Clone> Game<R> {
         PlayerGameView {
             pid: pid,
             table: self.table.clone(),
-            own_hand: self.players[pid.0 as usize].hand.clone(),
+            own_hand: self.players[pid.0 as usize].hand,
             state: self.state.clone(),
             last_action: self.last_action.clone(),
 
             main_deck_sz: self.main_deck.ncards(),
-            player_decks_sz: self.players.iter().map(|p| p.hand.ncards()).collect(),
+            player_decks_sz: self.players.iter().map(|p| p.hand.len()).collect(),
+            rules: self.rules.clone(),
+            captures: self.teams.iter().map(|t| t.captures.clone()).collect(),
+            last_team_captured: self.last_team_captured,
+            last_timeout: self.last_timeout(),
+            turn_deadline: None,
+        }
+    }
+
+    /// Get the view of a spectator. If reveal is set, the hands are revealed when the game is
+    /// done, since they no longer matter for the players.
+    pub fn get_spectator_game_view(&self, reveal: bool) -> SpectatorGameView {
+        let done = self.state.is_game_done() || self.state.is_match_done();
+        let revealed_hands = match self.history.last() {
+            Some(record) if reveal && done => {
+                let mut hands = vec![Deck::empty(); self.players.len()];
+                for pa in record.actions.iter() {
+                    hands[pa.player.0 as usize].push(pa.action.hand_card());
+                }
+                Some(hands)
+            },
+            _ => None,
+        };
+
+        SpectatorGameView {
+            table: self.table.clone(),
+            state: self.state.clone(),
+            last_action: self.last_action.clone(),
+            main_deck_sz: self.main_deck.ncards(),
+            player_decks_sz: self.players.iter().map(|p| p.hand.len()).collect(),
+            rules: self.rules.clone(),
+            captures: self.teams.iter().map(|t| t.captures.clone()).collect(),
+            last_team_captured: self.last_team_captured,
+            scores: self.teams.iter().map(|t| t.score).collect(),
+            revealed_hands: revealed_hands,
+            last_timeout: self.last_timeout(),
+            turn_deadline: None,
+        }
+    }
+
+    /// Build a game from a player's view, where the hidden information (the hands of the other
+    /// players and the main deck) is given by the caller. hands[view.pid] is ignored.
+    ///
+    /// The resulting game starts from the view's state (see Game::from_parts()).
+    pub(crate) fn from_view(view: &PlayerGameView, mut hands: Vec<Deck>, main_deck: Deck, rng: R) -> Game<R> {
+        assert_eq!(hands.len(), view.player_decks_sz.len());
+        hands[view.pid.0 as usize] = Deck { cards: view.own_hand.iter().collect() };
+        let first_player = view.round_first_player().unwrap_or(view.pid);
+        let teams = view.captures.iter().map(|c| Team { captures: c.clone(), score: 0 }).collect();
+        let mut game = Self::from_parts(
+            view.rules.clone(),
+            hands,
+            view.table.clone(),
+            main_deck,
+            view.state.clone(),
+            first_player,
+            teams,
+            view.last_team_captured,
+            rng,
+        );
+        game.last_action = view.last_action.clone();
+        game
+    }
+
+    /// Build a game that is in the middle of being played.
+    ///
+    /// There is no match (target score) and the first record of its history is the given state,
+    /// as if the game was dealt this way.
+    pub(crate) fn from_parts(
+        rules: RuleSet,
+        hands: Vec<Deck>,
+        table: Table,
+        main_deck: Deck,
+        state: GameState,
+        first_player: PlayerTpos,
+        teams: Vec<Team>,
+        last_team_captured: usize,
+        rng: R,
+    ) -> Game<R> {
+        let record = GameRecord {
+            first_player: first_player,
+            init: InitState {
+                deck: main_deck.clone(),
+                table: table.clone(),
+                hands: hands.clone(),
+            },
+            actions: vec![],
+            timeouts: vec![],
+            scores: teams.iter().map(|t| t.score).collect(),
+        };
+
+        Game {
+            table: table,
+            main_deck: main_deck,
+            players: hands.iter().map(|hd| Player::new(hd)).collect(),
+            teams: teams,
+            last_team_captured: last_team_captured,
+            state: state,
+            first_player: first_player,
+            last_action: None,
+            initst_opt: None,
+            history: vec![record],
+            dropped_games: 0,
+            last_match: None,
+            target_score: None,
+            rules: rules,
+            rng: rng,
         }
     }
 
+    fn replay_timeout(&mut self, t: &TurnTimeout) -> Result<(), String> {
+        match self.state {
+            GameState::NextTurn(tpos) if tpos == t.player => (),
+            _ => return Err(format!("unexpected timeout of {} ({:?})", t.player, self.state)),
+        }
+        if t.policy == TimeoutPolicy::Forfeit && self.teams.len() < 2 {
+            return Err("forfeit with a single team".to_string());
+        }
+        self.do_turn_timeout(t.clone());
+        Ok(())
+    }
+
     pub fn all_players_done(&self) -> bool {
-        self.players.iter().all(|p| p.hand.ncards() == 0)
+        self.players.iter().all(|p| p.hand.is_empty())
     }
 
     fn next_turn(&mut self) {
         if let GameState::NextTurn(curr_tpos) = self.state {
             let nplayers = self.players.len() as u8;
             let next_tpos = PlayerTpos((curr_tpos.0 + 1) % nplayers);
-            if self.get_player(next_tpos).unwrap().hand.ncards() > 0 {
+            if !self.get_player(next_tpos).unwrap().hand.is_empty() {
                 self.state = GameState::NextTurn(next_tpos);
             } else if self.main_deck.ncards() > 0 {
                 assert!(self.all_players_done());
@@ -251,55 +666,83 @@ impl<R: rand::Rng + Clone> Game<R> {
                 self.finalize_captures();
                 let mut scores = vec![];
                 for team in self.teams.iter_mut() {
-                    scores.push(team.update_score())
+                    scores.push(team.update_score(&self.rules))
                 }
-                self.state = GameState::GameDone(scores);
+                self.state = match self.match_winner(&scores) {
+                    Some(winner) => GameState::MatchDone(scores, winner),
+                    None => GameState::GameDone(scores),
+                };
             }
         } else {
             panic!("Invalid call of next_turn()")
         }
     }
 
-    pub fn apply_action(&self, tpos: PlayerTpos, action: PlayerAction) -> Result<Self, String> {
+    /// Check whether the match is over given the scores of a game that just finished.
+    ///
+    /// If more than one teams reach the target score in the same game, the one with the highest
+    /// score wins. If the highest score is shared, nobody wins and another game is played.
+    pub(crate) fn match_winner(&self, scores: &Vec<(ScoreSheet, usize)>) -> Option<usize> {
+        let target = self.target_score?;
+        let max = scores.iter().map(|(_, total)| *total).max()?;
+        if max < target {
+            return None;
+        }
 
-        match self.state {
-            GameState::NextTurn(_) => (),
-            _ => return Err(format!("Invalid state for applying action ({:?})", self.state))
-        };
+        let mut leaders = scores.iter().enumerate().filter(|(_, (_, total))| *total == max);
+        match (leaders.next(), leaders.next()) {
+            (Some((idx, _)), None) => Some(idx),
+            _ => None,
+        }
+    }
 
-        // poor man's transaction: we copy everything, try to apply the action, and then either
-        // return an error or the new state. This is because any errors during action application
-        // could leave the game in an incosistent state. It should be possible to ensure
-        // that there are no errors by doing a perfect validation, but we do not do this
-        // currently.
+    pub fn apply_action(&self, tpos: PlayerTpos, action: PlayerAction) -> Result<Self, ActionError> {
         let mut new = self.clone();
-        let performed_act = new.do_apply_action(tpos, action)?;
-        new.last_action = Some(performed_act);
-        new.next_turn();
+        new.apply_action_mut(tpos, action)?;
         Ok(new)
     }
 
+    /// Apply an action in place. If the action is invalid, the game is left unchanged.
+    pub fn apply_action_mut(&mut self, tpos: PlayerTpos, action: PlayerAction) -> Result<(), ActionError> {
+        match self.state {
+            GameState::NextTurn(curr_tpos) if curr_tpos == tpos => (),
+            GameState::NextTurn(_) => return Err(ActionError::NotYourTurn),
+            _ => return Err(ActionError::GameNotInProgress),
+        };
+
+        // NB: validation checks everything the action refers to, so applying it cannot fail
+        action.validate_move(tpos, &self.table, &self.players[tpos.0 as usize].hand, &self.rules)?;
+        let performed_act = self.do_apply_action(tpos, action);
+        self.history.last_mut().expect("current game record").actions.push(performed_act.clone());
+        self.last_action = Some(performed_act);
+        self.next_turn();
+        Ok(())
+    }
+
     pub fn state(&self) -> &GameState {
         return &self.state
     }
 
-    // NB: In case of an error, state might be incosistent.
-    fn do_apply_action(&mut self, tpos: PlayerTpos, action: PlayerAction) -> Result<PerformedAction, String> {
-        {
-            let pview = self.get_player_game_view(tpos);
-            action.validate(&pview)?
+    /// Legal actions for the player whose turn it is (empty if it is nobody's turn)
+    pub fn legal_actions(&self) -> Vec<PlayerAction> {
+        match self.state {
+            GameState::NextTurn(tpos) => self.get_player_game_view(tpos).legal_actions(),
+            _ => vec![],
         }
+    }
 
+    // NB: action is assumed to be validated
+    fn do_apply_action(&mut self, tpos: PlayerTpos, action: PlayerAction) -> PerformedAction {
         match action {
             PlayerAction::LayDown(c) => {
-                let card = self.remove_player_card(tpos, &c).ok_or_else(|| "Card does not exist")?;
+                let card = self.remove_player_card(tpos, &c).expect("validated hand card");
                 self.add_table_card(card);
-                Ok(PerformedAction {
+                PerformedAction {
                     action: PlayerAction::LayDown(c),
                     player: tpos,
                     forced_cards: vec![],
                     xeri: false,
-                })
+                }
             },
             PlayerAction::Declare(da) => self.do_apply_decl_action(tpos, da),
             PlayerAction::Capture(ca) => self.do_apply_capture_action(tpos, ca),
@@ -309,6 +752,9 @@ impl<R: rand::Rng + Clone> Game<R> {
     /// enforce obligations on a declaration
     fn decl_enforce_obligations(&mut self, da: &DeclAction, decl_cards: &mut Vec<Vec<Card>>) -> Vec<Card> {
         let mut ret: Vec<Card> = vec![];
+        if !self.rules.forced_declarations {
+            return ret;
+        }
 
         // NB: I don't think there are any cases where the declaration is valid and we shouldn't do
         // this.
@@ -322,20 +768,20 @@ impl<R: rand::Rng + Clone> Game<R> {
 
     }
 
-    fn do_apply_capture_action(&mut self, tpos: PlayerTpos, ca: CaptureAction) -> Result<PerformedAction, String> {
+    fn do_apply_capture_action(&mut self, tpos: PlayerTpos, ca: CaptureAction) -> PerformedAction {
         let mut captured_cards : Vec<Card> = vec![];
 
-        let hand_card = self.remove_player_card(tpos, &ca.handcard).ok_or_else(|| "Hand card does not exist")?;
+        let hand_card = self.remove_player_card(tpos, &ca.handcard).expect("validated hand card");
         captured_cards.push(hand_card);
 
         for te in ca.tentries.iter().flatten() {
             match te {
                 TableEntry::Card(c) => {
-                    let table_card =  self.remove_table_card(&c).ok_or_else(|| "Table card does not exist")?;
+                    let table_card =  self.remove_table_card(&c).expect("validated table card");
                     captured_cards.push(table_card);
                 },
                 TableEntry::Decl(d) => {
-                    let mut table_decl = self.remove_table_decl(&d).ok_or_else(|| "Table declaration does not exist")?;
+                    let mut table_decl = self.remove_table_decl(&d).expect("validated table declaration");
                     for c in table_decl.cards.drain(..).flatten() {
                         captured_cards.push(c);
                     }
@@ -348,7 +794,7 @@ impl<R: rand::Rng + Clone> Game<R> {
         let mut forced_cards = vec![];
 
         // only force captures if the handcard is not a figure. Check validation for more details
-        if !ca.handcard.rank.is_figure() {
+        if self.rules.forced_captures && !ca.handcard.rank.is_figure() {
             while let Some(te) = self.table.remove_entry_with_value(val) {
                 match te {
                     TableEntry::Card(c) => {
@@ -371,15 +817,15 @@ impl<R: rand::Rng + Clone> Game<R> {
         self.update_captures(tpos, captured_cards);
         let xeri = self.table.nentries() == 0;
 
-        Ok(PerformedAction {
+        PerformedAction {
             action: PlayerAction::Capture(ca),
             player: tpos,
             forced_cards: forced_cards,
             xeri: xeri,
-        })
+        }
     }
 
-    fn do_apply_decl_action(&mut self, tpos: PlayerTpos, da: DeclAction) -> Result<PerformedAction, String> {
+    fn do_apply_decl_action(&mut self, tpos: PlayerTpos, da: DeclAction) -> PerformedAction {
 
 
         let mut decl_cards : Vec<Vec<Card>> = vec![];
@@ -390,19 +836,19 @@ impl<R: rand::Rng + Clone> Game<R> {
                 match ((i,j), te) {
                     // Hand card (by convention it's the first entry)
                     ((0,0), TableEntry::Card(c)) => {
-                        let hand_card = self.remove_player_card(tpos, c).ok_or_else(|| "Hand card does not exist")?;
+                        let hand_card = self.remove_player_card(tpos, c).expect("validated hand card");
                         cards_v.push(hand_card);
                     },
 
                     // Any card (by convention it's the first entry)
                     (_, TableEntry::Card(c)) => {
-                        let table_card =  self.remove_table_card(c).ok_or_else(|| "Table card does not exist")?;
+                        let table_card =  self.remove_table_card(c).expect("validated table card");
                         cards_v.push(table_card);
                     },
 
                     // Plain declarations can be combined with other cards
                     (_, TableEntry::Decl(d)) if d.is_plain() => {
-                        let decl = self.remove_table_decl(d).ok_or_else(|| "Table declaration does not exist")?;
+                        let decl = self.remove_table_decl(d).expect("validated table declaration");
                         let (decl_cards, _) = decl.into_inner();
                         assert!(decl_cards.len() == 1); // declaration is plain
                         cards_v.extend_from_slice(&decl_cards[0]);
@@ -411,18 +857,15 @@ impl<R: rand::Rng + Clone> Game<R> {
                     // Group declarations cannot be combined with other cards, and have to
                     // be on their own
                     ((_,0), TableEntry::Decl(d)) if d.is_group() => {
-                        if entries_v_len != 1 {
-                            return Err("Invalid declaration: group declaration cannot be combined with other cards".to_string());
-                        }
+                        assert!(entries_v_len == 1); // validated
                         assert!(cards_v.len() == 0); // should be true since j is 0
-                        let decl = self.remove_table_decl(d).ok_or_else(|| "Table declaration does not exist")?;
+                        let decl = self.remove_table_decl(d).expect("validated table declaration");
                         let (cvv, _) = decl.into_inner();
                         decl_cards.extend_from_slice(&cvv);
                         break;
                     },
 
-                    // TODO: if this ever hits, add more info
-                    _ => return Err("Invalid declaration".to_string()),
+                    _ => panic!("Invalid declaration (should have been caught by validation): {:?}", da),
                 }
             }
 
@@ -440,34 +883,39 @@ impl<R: rand::Rng + Clone> Game<R> {
         };
         self.add_table_decl(decl);
 
-        Ok(PerformedAction {
+        PerformedAction {
             action: PlayerAction::Declare(da),
             player: tpos,
             forced_cards: forced_cards,
             xeri: false,
-        })
+        }
     }
 
 }
 
 impl Player {
-    pub fn iter_hand_cards(&self) -> impl Iterator<Item=&Card> {
-        self.hand.cards.iter()
+    fn new(hand: &Deck) -> Player {
+        Player { hand: hand.cards.iter().collect() }
+    }
+
+    pub fn iter_hand_cards(&self) -> impl Iterator<Item=Card> {
+        self.hand.iter()
     }
 }
 
 impl PlayerGameView {
 
-    pub fn iter_hand_cards(&self) -> impl Iterator<Item=&Card> {
-        self.own_hand.cards.iter()
+    /// Hand cards, sorted by suit and rank (see CardSet)
+    pub fn iter_hand_cards(&self) -> impl Iterator<Item=Card> {
+        self.own_hand.iter()
     }
 
-    pub fn enum_hand_cards(&self) -> impl Iterator<Item=(usize, &Card)> {
-        self.own_hand.cards.iter().enumerate()
+    pub fn enum_hand_cards(&self) -> impl Iterator<Item=(usize, Card)> {
+        self.own_hand.iter().enumerate()
     }
 
-    pub fn get_hand_card(&self, idx: usize) -> &Card {
-        &self.own_hand.cards[idx]
+    pub fn get_hand_card(&self, idx: usize) -> Card {
+        self.own_hand.iter().nth(idx).expect("valid hand index")
     }
 
     pub fn iter_table_entries(&self) -> impl Iterator<Item=&TableEntry> {
@@ -483,8 +931,13 @@ impl PlayerGameView {
     }
 
 
+    /// The player's hand as a CardSet
+    pub fn hand_set(&self) -> CardSet {
+        self.own_hand
+    }
+
     pub fn card_in_hand(&self, c: &Card) -> bool {
-        self.iter_hand_cards().find(|hc| c == *hc).is_some()
+        self.own_hand.contains(c)
     }
 
     pub fn is_my_turn(&self) -> bool {
@@ -500,6 +953,30 @@ impl PlayerGameView {
             _ => None,
         }
     }
+
+    /// All legal actions for this player (see movegen)
+    pub fn legal_actions(&self) -> Vec<PlayerAction> {
+        movegen::legal_actions(self)
+    }
+
+    /// First player of the current round.
+    ///
+    /// Players play in order, so the ones that have already played in this round hold one card
+    /// less than the active player.
+    pub fn round_first_player(&self) -> Option<PlayerTpos> {
+        let active = self.active_tpos()?;
+        let n = self.player_decks_sz.len();
+        let active_sz = self.player_decks_sz[active.0 as usize];
+        let mut first = active.0 as usize;
+        for _ in 1..n {
+            let prev = (first + n - 1) % n;
+            if self.player_decks_sz[prev] + 1 != active_sz {
+                break;
+            }
+            first = prev;
+        }
+        Some(PlayerTpos(first as u8))
+    }
 }
 
 
@@ -526,11 +1003,18 @@ impl GameState {
             _ => false,
         }
     }
+
+    pub fn is_match_done(&self) -> bool {
+        match self {
+            GameState::MatchDone(_, _) => true,
+            _ => false,
+        }
+    }
 }
 
 impl Team {
-    pub fn update_score(&mut self) -> (ScoreSheet, usize) {
-        let sheet = self.captures.score();
+    pub fn update_score(&mut self, rules: &RuleSet) -> (ScoreSheet, usize) {
+        let sheet = self.captures.score(rules);
         self.score += sheet.score;
         self.captures = Captures::new();
         (sheet, self.score)
@@ -538,23 +1022,21 @@ impl Team {
 }
 
 impl InitState {
-    fn default<R: rand::Rng>(nplayers: usize, rng: &mut R) -> Self {
+    fn default<R: rand::Rng>(nplayers: usize, rules: &RuleSet, rng: &mut R) -> Self {
         let mut deck  = Deck::full_52();
         let mut table = Table { entries: vec![] };
         let mut hands : Vec<Deck> = (0..nplayers).map(|_| Deck::empty()).collect();
 
         deck.shuffle(rng);
 
-        let hand_size = 6;
         for p in 0..nplayers {
-            for _ in 0..hand_size {
+            for _ in 0..HAND_SIZE {
                 let card = deck.pop().unwrap();
                 hands[p].cards.push(card)
             }
         }
 
-        let table_size = 4;
-        for _ in 0..table_size {
+        for _ in 0..rules.table_size {
             let card = deck.pop().unwrap();
             table.entries.push(TableEntry::Card(card));
         }
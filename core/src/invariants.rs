@@ -0,0 +1,217 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Consistency checks for the game state
+//
+// A game that is only modified via apply_action() and friends should never violate these. They
+// are meant for testing and debugging, so they favor being thorough over being fast.
+
+use super::card::{Card, CardSet};
+use super::table::{TableEntry, PlayerTpos};
+use super::actions::PlayerAction;
+use super::game::{Game, GameState, InitState};
+
+/// Add cards to a set, failing if any of them is already there
+fn add_cards<I: Iterator<Item=Card>>(set: &mut CardSet, cards: I, place: &str) -> Result<(), String> {
+    for c in cards {
+        if !set.insert(&c) {
+            return Err(format!("card {} appears more than once ({})", c, place));
+        }
+    }
+    Ok(())
+}
+
+fn table_entry_cards(te: &TableEntry) -> Vec<Card> {
+    match te {
+        TableEntry::Card(c) => vec![c.clone()],
+        TableEntry::Decl(d) => d.cards.iter().flatten().cloned().collect(),
+    }
+}
+
+fn init_cards(init: &InitState) -> CardSet {
+    let mut ret: CardSet = init.deck.cards.iter().collect();
+    ret.extend(init.hands.iter().flat_map(|hd| hd.cards.iter()));
+    for te in init.table.entries.iter() {
+        ret.extend(table_entry_cards(te).iter());
+    }
+    ret
+}
+
+impl<R: rand::Rng + Clone> Game<R> {
+
+    /// Check that the game state is consistent. Returns a description of the first violation.
+    ///
+    /// - Every card is in exactly one place: the main deck, a hand, the table, or the captures of
+    ///   a team. While a game is in progress, every card it was dealt with is still there (i.e.,
+    ///   all 52 cards, unless the game was set up for debugging). Once it is done, there are none.
+    /// - Declarations have at least two cards, a value between 1 and 10 that all their groups
+    ///   share, and belong to a player that has no other declaration and holds a card to capture
+    ///   it with.
+    /// - Players take turns in order starting from the first player, so the hands of the players
+    ///   that already played in the current turn around the table have one card less.
+    /// - There is a team for each team_idx(), and the last capture went to the capturing team.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        self.check_teams()?;
+        self.check_cards()?;
+        self.check_decls()?;
+        self.check_turn()
+    }
+
+    fn check_teams(&self) -> Result<(), String> {
+        let nplayers = self.players.len();
+        let nteams = if nplayers == 1 { 1 } else { 2 };
+        if self.teams.len() != nteams {
+            return Err(format!("{} teams for {} players", self.teams.len(), nplayers));
+        }
+
+        if self.last_team_captured >= nteams {
+            return Err(format!("invalid last team that captured: {}", self.last_team_captured));
+        }
+
+        if let Some(pa) = &self.last_action {
+            if pa.player.0 as usize >= nplayers {
+                return Err(format!("last action by invalid player {}", pa.player));
+            }
+
+            if let PlayerAction::Capture(_) = pa.action {
+                let idx = self.team_idx(pa.player);
+                if idx != self.last_team_captured {
+                    return Err(format!("{} captured last, but the last team that captured is {}", pa.player, self.last_team_captured));
+                }
+            }
+
+            if pa.xeri && !self.table.entries.is_empty() {
+                return Err("last action was a xeri, but the table is not empty".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_cards(&self) -> Result<(), String> {
+        let mut found = CardSet::empty();
+        add_cards(&mut found, self.main_deck.cards.iter().cloned(), "main deck")?;
+        for (p, player) in self.players.iter().enumerate() {
+            add_cards(&mut found, player.iter_hand_cards(), &format!("hand of {}", PlayerTpos(p as u8)))?;
+        }
+        for te in self.table.entries.iter() {
+            add_cards(&mut found, table_entry_cards(te).into_iter(), "table")?;
+        }
+        for (t, team) in self.teams.iter().enumerate() {
+            add_cards(&mut found, team.captures.iter_cards(), &format!("captures of team {}", t))?;
+            if let Some(c) = team.captures.xeres().iter().find(|c| !team.captures.cards().contains(c)) {
+                return Err(format!("xeri card {} of team {} is not captured", c, t));
+            }
+        }
+
+        match self.state {
+            GameState::NextTurn(_) | GameState::RoundDone => {
+                let record = self.history.last().ok_or_else(|| "no game record".to_string())?;
+                let missing = init_cards(&record.init) - found;
+                if !missing.is_empty() {
+                    return Err(format!("cards missing from the game: {}", missing));
+                }
+            },
+            GameState::GameDone(_) | GameState::MatchDone(_, _) => {
+                if !found.is_empty() {
+                    return Err(format!("cards left after the game is done: {}", found));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn check_decls(&self) -> Result<(), String> {
+        let nplayers = self.players.len();
+        let mut owners: Vec<PlayerTpos> = vec![];
+        for te in self.table.entries.iter() {
+            let decl = match te {
+                TableEntry::Decl(d) => d,
+                TableEntry::Card(_) => continue,
+            };
+
+            if decl.player.0 as usize >= nplayers {
+                return Err(format!("declaration of invalid player {}", decl.player));
+            }
+            if owners.contains(&decl.player) {
+                return Err(format!("{} has more than one declarations", decl.player));
+            }
+            owners.push(decl.player);
+
+            if decl.cards.iter().any(|g| g.is_empty()) {
+                return Err(format!("declaration of {} has an empty group", decl.player));
+            }
+            if decl.cards.iter().map(|g| g.len()).sum::<usize>() < 2 {
+                return Err(format!("declaration of {} has less than two cards", decl.player));
+            }
+
+            let value = decl.value();
+            if value < 1 || value > 10 {
+                return Err(format!("declaration of {} has invalid value {}", decl.player, value));
+            }
+            for group in decl.cards.iter() {
+                let group_value = group.iter().fold(0, |acc, c| acc + c.rank.0);
+                if group_value != value {
+                    return Err(format!("declaration of {} has a group of value {} (expecting {})", decl.player, group_value, value));
+                }
+            }
+
+            if !self.players[decl.player.0 as usize].hand.has_value(value) {
+                return Err(format!("{} has no card to capture their declaration", decl.player));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_turn(&self) -> Result<(), String> {
+        let nplayers = self.players.len();
+        if self.first_player.0 as usize >= nplayers {
+            return Err(format!("invalid first player {}", self.first_player));
+        }
+
+        let hand_sizes: Vec<usize> = self.players.iter().map(|p| p.hand.len()).collect();
+        match self.state {
+            GameState::NextTurn(tpos) => {
+                if tpos.0 as usize >= nplayers {
+                    return Err(format!("turn of invalid player {}", tpos));
+                }
+                let size = hand_sizes[tpos.0 as usize];
+                if size == 0 {
+                    return Err(format!("turn of {}, who has no cards", tpos));
+                }
+
+                let first = self.first_player.0 as usize;
+                let nplayed = (tpos.0 as usize + nplayers - first) % nplayers;
+                for i in 0..nplayers {
+                    let p = (first + i) % nplayers;
+                    let expected = if i < nplayed { size - 1 } else { size };
+                    if hand_sizes[p] != expected {
+                        return Err(format!("turn of {}, but {} has {} cards (expecting {})", tpos, PlayerTpos(p as u8), hand_sizes[p], expected));
+                    }
+                }
+            },
+
+            GameState::RoundDone => {
+                if !self.all_players_done() {
+                    return Err("round is done, but players have cards".to_string());
+                }
+                if self.main_deck.ncards() == 0 {
+                    return Err("round is done, but there are no cards to deal".to_string());
+                }
+            },
+
+            GameState::GameDone(_) | GameState::MatchDone(_, _) => {
+                if !self.all_players_done() {
+                    return Err("game is done, but players have cards".to_string());
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
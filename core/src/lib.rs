@@ -19,6 +19,13 @@ pub mod srvcli;
 pub mod repr;
 pub mod actions;
 pub mod scoring;
+pub mod movegen;
+pub mod rules;
+pub mod bot;
+pub mod determinize;
+pub mod mcts;
+pub mod invariants;
+pub mod turn_timer;
 
 #[cfg(test)]
 pub mod tests;
@@ -26,6 +33,12 @@ pub mod tests;
 pub use card::{Card, Rank, Suit};
 pub use deck::Deck;
 pub use table::{Table, TableEntry, Declaration};
-pub use game::{Game, GameState, PlayerGameView};
+pub use game::{Game, GameState, PlayerGameView, SpectatorGameView, ReplayLog, GameRecord};
 pub use actions::{PlayerAction, DeclAction, DeclActionBuilder, CaptureAction, CaptureActionBuilder};
 pub use scoring::{ScoreSheet};
+pub use rules::RuleSet;
+pub use error::ActionError;
+pub use bot::{Bot, BotKind, RandomBot, GreedyBot};
+pub use determinize::{determinize, PublicCards};
+pub use mcts::{MctsBot, MctsConfig};
+pub use turn_timer::{TurnTimer, TimeoutPolicy, TurnTimeout};
@@ -0,0 +1,230 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Information-set Monte Carlo tree search bot
+//
+// We implement single-observer ISMCTS (Cowling, Powley, Whitehouse, 2012). Each iteration samples
+// a determinization: a complete game consistent with what the bot's player has seen, where the
+// hands of the other players and the main deck are random. We then walk down a single tree (shared
+// by all determinizations) using the actions that are legal in the sampled game, expand a node,
+// play random actions until the end of the game, and update the nodes on the path with the result.
+//
+// Nodes are selected with UCB, where the number of times a node was available for selection is
+// used instead of the number of visits of its parent (actions are not always legal in every
+// determinization).
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+
+use super::table::PlayerTpos;
+use super::actions::PlayerAction;
+use super::game::{Game, GameState, PlayerGameView};
+use super::bot::Bot;
+use super::determinize::determinize;
+
+/// Search budget. The search stops when either limit is reached.
+#[derive(Debug, Clone)]
+pub struct MctsConfig {
+    /// Maximum number of iterations
+    pub iterations: usize,
+    /// Maximum time to search
+    pub time_limit: Option<Duration>,
+    /// UCB exploration constant
+    pub exploration: f64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            iterations: 5000,
+            time_limit: Some(Duration::from_secs(1)),
+            exploration: 0.7,
+        }
+    }
+}
+
+/// Point difference that maps to the maximum (and minimum) reward
+const REWARD_POINTS_SCALE: f64 = 30.0;
+
+struct Node {
+    /// action that leads to this node (None for the root)
+    action: Option<PlayerAction>,
+    /// team of the player that performed the action
+    team: usize,
+    visits: u32,
+    avail: u32,
+    reward: f64,
+    children: Vec<usize>,
+}
+
+impl Node {
+    fn new(action: Option<PlayerAction>, team: usize) -> Node {
+        Node {
+            action: action,
+            team: team,
+            visits: 0,
+            avail: 0,
+            reward: 0.0,
+            children: vec![],
+        }
+    }
+
+    fn ucb(&self, exploration: f64) -> f64 {
+        let visits = self.visits as f64;
+        self.reward / visits + exploration * ((self.avail as f64).ln() / visits).sqrt()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MctsBot<R: rand::Rng + Clone> {
+    rng: R,
+    config: MctsConfig,
+}
+
+impl<R: rand::Rng + Clone> MctsBot<R> {
+    pub fn new(rng: R, config: MctsConfig) -> MctsBot<R> {
+        MctsBot {
+            rng: rng,
+            config: config,
+        }
+    }
+
+    fn search(&mut self, view: &PlayerGameView) -> Option<PlayerAction> {
+        let legal = view.legal_actions();
+        match legal.len() {
+            0 => return None,
+            1 => return legal.into_iter().next(),
+            _ => (),
+        }
+
+        let start = Instant::now();
+        let mut tree = vec![Node::new(None, team(view.pid))];
+        for _ in 0..self.config.iterations {
+            if let Some(limit) = self.config.time_limit {
+                if start.elapsed() >= limit {
+                    break;
+                }
+            }
+            self.iteration(view, &mut tree);
+        }
+
+        tree[0].children
+            .iter()
+            .max_by_key(|c| tree[**c].visits)
+            .and_then(|c| tree[*c].action.clone())
+    }
+
+    fn iteration(&mut self, view: &PlayerGameView, tree: &mut Vec<Node>) {
+        // the view includes the captured cards, so there is no need for the action history
+        let mut game = determinize(view, &[], &mut self.rng);
+        let mut path = vec![0];
+        let mut node = 0;
+
+        // selection and expansion
+        loop {
+            let tpos = match game.state() {
+                GameState::NextTurn(tpos) => *tpos,
+                GameState::RoundDone => {
+                    game.new_round();
+                    continue;
+                },
+                GameState::GameDone(_) | GameState::MatchDone(_, _) => break,
+            };
+
+            let legal = game.legal_actions();
+            let legal_set: HashSet<&PlayerAction> = legal.iter().collect();
+            let mut tried = HashSet::new();
+            for i in 0..tree[node].children.len() {
+                let child_idx = tree[node].children[i];
+                let child = &mut tree[child_idx];
+                let act = child.action.as_ref().expect("child action");
+                tried.insert(act.clone());
+                if legal_set.contains(act) {
+                    child.avail += 1;
+                }
+            }
+
+            let untried: Vec<&PlayerAction> = legal.iter().filter(|a| !tried.contains(*a)).collect();
+            if let Some(act) = untried.choose(&mut self.rng) {
+                let act = (*act).clone();
+                let mut child = Node::new(Some(act.clone()), team(tpos));
+                child.avail = 1;
+                tree.push(child);
+                let child_idx = tree.len() - 1;
+                tree[node].children.push(child_idx);
+                game.apply_action_mut(tpos, act).expect("legal action");
+                path.push(child_idx);
+                break;
+            }
+
+            let exploration = self.config.exploration;
+            let next = tree[node].children
+                .iter()
+                .filter(|c| legal_set.contains(tree[**c].action.as_ref().unwrap()))
+                .max_by(|c1, c2| {
+                    let u1 = tree[**c1].ucb(exploration);
+                    let u2 = tree[**c2].ucb(exploration);
+                    u1.partial_cmp(&u2).unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .cloned()
+                .expect("legal child");
+            let act = tree[next].action.clone().unwrap();
+            game.apply_action_mut(tpos, act).expect("legal action");
+            node = next;
+            path.push(node);
+        }
+
+        // simulation
+        let rewards = rollout(&mut game, &mut self.rng);
+
+        // backpropagation
+        for idx in path.iter().skip(1) {
+            let node = &mut tree[*idx];
+            node.visits += 1;
+            node.reward += rewards[node.team];
+        }
+        tree[0].visits += 1;
+    }
+}
+
+impl<R: rand::Rng + Clone> Bot for MctsBot<R> {
+    fn choose_action(&mut self, view: &PlayerGameView) -> Option<PlayerAction> {
+        if !view.is_my_turn() {
+            return None;
+        }
+        self.search(view)
+    }
+}
+
+fn team(tpos: PlayerTpos) -> usize {
+    (tpos.0 % 2) as usize
+}
+
+/// Play random actions until the end of the game, and return the reward (0..1) of each team
+fn rollout<R: rand::Rng + Clone, Rg: rand::Rng>(game: &mut Game<R>, rng: &mut Rg) -> Vec<f64> {
+    loop {
+        match game.state().clone() {
+            GameState::NextTurn(tpos) => {
+                let act = game.legal_actions().choose(rng).expect("legal action").clone();
+                game.apply_action_mut(tpos, act).expect("legal action");
+            },
+            GameState::RoundDone => game.new_round(),
+            GameState::GameDone(scores) | GameState::MatchDone(scores, _) => {
+                let points: Vec<f64> = scores.iter().map(|(ss, _)| ss.score as f64).collect();
+                return match points.len() {
+                    1 => vec![(points[0] / REWARD_POINTS_SCALE).min(1.0)],
+                    2 => {
+                        let r0 = (0.5 + (points[0] - points[1]) / (2.0 * REWARD_POINTS_SCALE)).max(0.0).min(1.0);
+                        vec![r0, 1.0 - r0]
+                    },
+                    _ => panic!("Unexpected number of teams"),
+                };
+            },
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+// Legal move generation
+//
+// We enumerate candidate actions structurally (every lay down, every way to partition table
+// entries into groups of the right value for captures and declarations), and then keep only the
+// ones that PlayerAction::validate() accepts. This way, the rules live in a single place
+// (actions.rs) and the generator only has to be exhaustive.
+//
+// Candidates are generated in a canonical form so that there are no duplicates:
+//  - table entries within a group are in table order
+//  - groups are ordered by their first table entry
+//  - for declarations, the first group starts with the hand card (see DeclAction)
+//
+// Sets of table entries are bitmasks of their indices (the table has less than 64 entries), so
+// that the enumeration does not allocate. Table entries are only cloned for the actions we
+// return, and the hand and table card sets are computed once for all candidates.
+
+use super::card::{Card, CardSet};
+use super::table::TableEntry;
+use super::actions::{PlayerAction, DeclAction, CaptureAction};
+use super::game::PlayerGameView;
+
+/// Maximum value of a declaration
+const MAX_DECL_VALUE: u8 = 10;
+
+/// A set of table entries: bit i is the i-th entry
+type Mask = u64;
+
+/// Return all the legal actions for the player of the view.
+///
+/// If it is not the player's turn, the result is empty.
+pub fn legal_actions(view: &PlayerGameView) -> Vec<PlayerAction> {
+    let mut ret = vec![];
+    if !view.is_my_turn() {
+        return ret;
+    }
+
+    let vals: Vec<u8> = view.iter_table_entries().map(|te| te.value()).collect();
+    assert!(vals.len() < 64, "too many table entries");
+    let all: Mask = (1 << vals.len()) - 1;
+
+    let gen = Gen {
+        view: view,
+        vals: &vals,
+        hand: view.hand_set(),
+        table_cards: view.table.card_set(),
+    };
+    for hcard in gen.hand.iter() {
+        gen.push(PlayerAction::LayDown(hcard.clone()), &mut ret);
+        gen.captures(&hcard, all, &mut ret);
+        gen.decls(&hcard, all, &mut ret);
+    }
+
+    ret
+}
+
+/// State shared by the generation of all candidates
+struct Gen<'a> {
+    view: &'a PlayerGameView,
+    vals: &'a [u8],
+    hand: CardSet,
+    table_cards: CardSet,
+}
+
+impl<'a> Gen<'a> {
+    /// Add the candidate to out, if it is legal
+    fn push(&self, act: PlayerAction, out: &mut Vec<PlayerAction>) {
+        let view = self.view;
+        if act.validate_move_with(view.pid, &view.table, &self.table_cards, &self.hand, &view.rules).is_ok() {
+            out.push(act);
+        }
+    }
+
+    fn tentries(&self, mask: Mask) -> Vec<TableEntry> {
+        iter_mask(mask).map(|i| self.view.get_table_entry(i).clone()).collect()
+    }
+
+    fn captures(&self, hcard: &Card, all: Mask, out: &mut Vec<PlayerAction>) {
+        partitions(self.vals, all, 0, hcard.rank.0, &mut vec![], &mut |groups| {
+            let act = PlayerAction::Capture(CaptureAction {
+                handcard: hcard.clone(),
+                tentries: groups.iter().map(|g| self.tentries(*g)).collect(),
+            });
+            self.push(act, out);
+        });
+    }
+
+    fn decls(&self, hcard: &Card, all: Mask, out: &mut Vec<PlayerAction>) {
+        let hval = hcard.rank.0;
+        let mut others = self.hand;
+        others.remove(hcard);
+        for val in hval..=MAX_DECL_VALUE {
+            // there has to be a different hand card to back the declaration
+            if !others.has_value(val) {
+                continue;
+            }
+
+            // first group: the hand card plus (possibly no) table entries
+            subsets_with_sum(self.vals, all, val - hval, &mut |first| {
+                let mut emit = |groups: &[Mask]| {
+                    let mut g0 = vec![TableEntry::Card(hcard.clone())];
+                    g0.extend(self.tentries(first));
+                    let mut tentries = vec![g0];
+                    tentries.extend(groups.iter().map(|g| self.tentries(*g)));
+                    self.push(PlayerAction::Declare(DeclAction { tentries: tentries }), out);
+                };
+                emit(&[]); // no additional groups
+                partitions(self.vals, all & !first, 0, val, &mut vec![], &mut emit);
+            });
+        }
+    }
+}
+
+/// Indices of the entries in mask, in increasing order
+fn iter_mask(mut mask: Mask) -> impl Iterator<Item=usize> {
+    std::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let i = mask.trailing_zeros() as usize;
+        mask &= mask - 1;
+        Some(i)
+    })
+}
+
+/// Call f for all subsets of cands (entries of vals) whose values sum to target.
+fn subsets_with_sum(vals: &[u8], cands: Mask, target: u8, f: &mut dyn FnMut(Mask)) {
+    fn rec(vals: &[u8], cands: Mask, target: u8, curr: Mask, f: &mut dyn FnMut(Mask)) {
+        if target == 0 {
+            f(curr);
+            return;
+        }
+
+        for idx in iter_mask(cands) {
+            let v = vals[idx];
+            if v > target {
+                continue;
+            }
+            // only consider the candidates after idx, so that we see every subset once
+            let rest = cands & !((2 << idx) - 1);
+            rec(vals, rest, target - v, curr | (1 << idx), f);
+        }
+    }
+
+    rec(vals, cands, target, 0, f)
+}
+
+/// Call f for all non-empty sets of disjoint groups of avail (entries of vals) where each group
+/// sums to target. Groups are ordered by their first element, which is also their smallest one.
+fn partitions(
+    vals: &[u8],
+    avail: Mask,
+    min_first: usize,
+    target: u8,
+    curr: &mut Vec<Mask>,
+    f: &mut dyn FnMut(&[Mask]),
+) {
+    for first in iter_mask(avail) {
+        if first < min_first || vals[first] > target {
+            continue;
+        }
+
+        let rest = avail & !((2 << first) - 1);
+        subsets_with_sum(vals, rest, target - vals[first], &mut |tail| {
+            let group = tail | (1 << first);
+            curr.push(group);
+            f(curr);
+            partitions(vals, avail & !group, first + 1, target, curr, f);
+            curr.pop();
+        });
+    }
+}
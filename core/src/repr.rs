@@ -13,11 +13,39 @@
 //                  |
 //                  ------------- player id
 
+// string representation for actions
+//   - lay down:  L SR
+//   - capture:   C SR ( E E ) ( E )
+//                  |  ------------- groups of table entries (cards or declarations)
+//                  ---------------- hand card
+//   - declare:   D ( SR E E ) ( E )
+//                    --------------- the first group starts with the hand card
+// Declarations, raises, and additions to a declaration are all written the same way.
+
+// string representation for a game position (similar to FEN for chess). Fields are separated by
+// " / ":
+//   HANDS / TABLE / DECK / STATE / CAPTURES / SCORES
+//   - HANDS: the hand of each player (in table position order), separated by " | "
+//   - TABLE: the table entries, as above
+//   - DECK: the main deck. Cards are dealt from the end.
+//   - STATE: whose turn it is, the first player of the game, and the team that captured last,
+//     e.g., "P1 P0 T0". The turn is "R" if the round is done, and "G" if the game is done.
+//   - CAPTURES: the cards captured by each team, separated by " | ". Xeres are marked with "*".
+//   - SCORES: the total score of each team, from previous games of the match
+// "-" is used for empty hands, tables, decks, and captures. For example, a 2-player game:
+//   D5 S4 | H2 CK / HT 1:[ D3 H2 ]: / - / P0 P1 T1 / SK* HK | - / 0 10
+// The last fields can be omitted when parsing. The defaults are: "P0 P0 T0" for the state,
+// nothing captured, and zero scores. For example: "D5 S4 | H2 CK / HT S5 / HA H3".
+
 use std::convert::TryFrom;
 
 use super::deck::Deck;
-use super::card::Card;
+use super::card::{Card, CardSet};
 use super::table::{Table, TableEntry, PlayerTpos, Declaration};
+use super::actions::{PlayerAction, DeclAction, CaptureAction};
+use super::game::{Game, GameState, Team};
+use super::scoring::Captures;
+use super::rules::RuleSet;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DeckRepr(pub String);
@@ -31,6 +59,12 @@ pub struct TableEntryRepr(pub String);
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TableRepr(pub String);
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionRepr(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameRepr(pub String);
+
 
 
 /**
@@ -48,8 +82,17 @@ impl DeckRepr {
     }
 
     pub fn fmt_deck(deck: &Deck, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Self::fmt_cards(deck.cards.iter(), f)
+    }
+
+    pub fn fmt_cards<C, I>(cards: I, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    where
+        C: std::borrow::Borrow<Card>,
+        I: Iterator<Item=C>,
+    {
         let mut sep = "";
-        for card in deck.cards.iter() {
+        for card in cards {
+            let card = card.borrow();
             f.write_str(sep)?;
             sep = " ";
             write!(f, "{}", card)?;
@@ -246,6 +289,328 @@ fn parse_table<'a, I>(iter: I) -> Option<Table> where
     })
 }
 
+/**
+ * Actions
+ */
+
+impl ActionRepr {
+    pub fn new<T: Into<String>>(s: T) -> Self {
+        Self(s.into())
+    }
+
+    pub fn parse(&self) -> Option<PlayerAction> {
+        let iter = self.0.split_whitespace();
+        parse_action(iter)
+    }
+
+    pub fn fmt_action(action: &PlayerAction, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match action {
+            PlayerAction::LayDown(c) => write!(f, "L {}", c),
+            PlayerAction::Capture(ca) => {
+                write!(f, "C {}", ca.handcard)?;
+                fmt_groups(&ca.tentries, f)
+            },
+            PlayerAction::Declare(da) => {
+                write!(f, "D")?;
+                fmt_groups(&da.tentries, f)
+            },
+        }
+    }
+}
+
+fn fmt_groups(groups: &Vec<Vec<TableEntry>>, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for group in groups.iter() {
+        write!(f, " (")?;
+        for entry in group.iter() {
+            write!(f, " ")?;
+            TableEntryRepr::fmt_table_entry(entry, f)?;
+        }
+        write!(f, " )")?;
+    }
+    Ok(())
+}
+
+fn parse_groups<'a, I>(iter: I) -> Option<Vec<Vec<TableEntry>>> where
+    I: Iterator<Item=&'a str>,
+{
+    let mut groups = vec![];
+    let mut iter_peek = iter.peekable();
+
+    while let Some(tok) = iter_peek.next() {
+        if tok != "(" {
+            return None;
+        }
+
+        let mut group = vec![];
+        loop {
+            match iter_peek.peek() {
+                None => return None,
+                Some(&")") => {
+                    iter_peek.next();
+                    break;
+                },
+                Some(_) => {
+                    let ret = parse_table_entry(iter_peek);
+                    iter_peek = ret.0;
+                    group.push(ret.1?);
+                }
+            }
+        }
+
+        if group.len() == 0 {
+            return None;
+        }
+        groups.push(group);
+    }
+
+    if groups.len() == 0 {
+        return None;
+    }
+    Some(groups)
+}
+
+fn parse_action<'a, I>(mut iter: I) -> Option<PlayerAction> where
+    I: Iterator<Item=&'a str>,
+{
+    match iter.next()? {
+        "L" | "l" => {
+            let card = Card::try_from(iter.next()?).ok()?;
+            match iter.next() {
+                None => Some(PlayerAction::LayDown(card)),
+                Some(_) => None,
+            }
+        },
+        "C" | "c" => {
+            let card = Card::try_from(iter.next()?).ok()?;
+            Some(PlayerAction::Capture(CaptureAction {
+                handcard: card,
+                tentries: parse_groups(iter)?,
+            }))
+        },
+        "D" | "d" => {
+            let tentries = parse_groups(iter)?;
+            if !tentries[0][0].is_card() {
+                return None;
+            }
+            Some(PlayerAction::Declare(DeclAction { tentries: tentries }))
+        },
+        _ => None,
+    }
+}
+
+/**
+ * Game positions
+ */
+
+impl GameRepr {
+    pub fn new<T: Into<String>>(s: T) -> Self {
+        Self(s.into())
+    }
+
+    pub fn from_game<R: rand::Rng + Clone>(game: &Game<R>) -> Self {
+        Self(game.to_string())
+    }
+
+    /// Build a game from the position. The game has no target score (see Game::from_parts()).
+    pub fn parse<R: rand::Rng + Clone>(&self, rules: RuleSet, rng: R) -> Result<Game<R>, String> {
+        let mut fields = split_tokens(self.0.split_whitespace(), "/");
+        if fields.len() < 3 || fields.len() > 6 {
+            return Err(format!("Expecting 3 to 6 fields, but found {}", fields.len()));
+        }
+
+        let hands = split_tokens(fields[0].iter().cloned(), "|")
+            .iter()
+            .map(|toks| parse_deck(empty_or(toks)))
+            .collect::<Option<Vec<Deck>>>()
+            .ok_or_else(|| "Invalid hands".to_string())?;
+        let nplayers = hands.len();
+        if nplayers != 1 && nplayers != 2 && nplayers != 4 {
+            return Err(format!("Invalid number of players: {}", nplayers));
+        }
+        rules.validate(nplayers)?;
+        let nteams = if nplayers == 1 { 1 } else { 2 };
+
+        // defaults for omitted fields
+        if fields.len() < 4 {
+            fields.push(vec!["P0", "P0", "T0"]);
+        }
+        if fields.len() < 5 {
+            fields.push(if nteams == 1 { vec!["-"] } else { vec!["-", "|", "-"] });
+        }
+        if fields.len() < 6 {
+            fields.push(vec!["0"; nteams]);
+        }
+
+        let table = parse_table(empty_or(&fields[1])).ok_or_else(|| "Invalid table".to_string())?;
+        let deck = parse_deck(empty_or(&fields[2])).ok_or_else(|| "Invalid deck".to_string())?;
+
+        let (state, first_player, last_team) = match fields[3].as_slice() {
+            [turn, first, last] => {
+                let state = match *turn {
+                    "R" => GameState::RoundDone,
+                    "G" => return Err("Finished games are not supported".to_string()),
+                    x => GameState::NextTurn(parse_tpos(x, nplayers).ok_or_else(|| format!("Invalid turn: {}", x))?),
+                };
+                let first = parse_tpos(first, nplayers).ok_or_else(|| format!("Invalid first player: {}", first))?;
+                let last = last.strip_prefix("T")
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .filter(|x| *x < nteams)
+                    .ok_or_else(|| format!("Invalid team: {}", last))?;
+                (state, first, last)
+            },
+            _ => return Err("Invalid state".to_string()),
+        };
+
+        let mut captures = split_tokens(fields[4].iter().cloned(), "|")
+            .iter()
+            .map(|toks| parse_captures(empty_or(toks)))
+            .collect::<Option<Vec<Captures>>>()
+            .ok_or_else(|| "Invalid captures".to_string())?;
+        let scores = fields[5]
+            .iter()
+            .map(|x| x.parse::<usize>().ok())
+            .collect::<Option<Vec<usize>>>()
+            .ok_or_else(|| "Invalid scores".to_string())?;
+        if captures.len() != nteams || scores.len() != nteams {
+            return Err(format!("Expecting captures and scores for {} teams", nteams));
+        }
+
+        // every card can only appear once
+        let mut all = CardSet::empty();
+        let table_cards = table.entries.iter().flat_map(|te| match te {
+            TableEntry::Card(c) => vec![c.clone()],
+            TableEntry::Decl(d) => d.cards.iter().flatten().cloned().collect(),
+        });
+        let cards = hands.iter().flat_map(|h| h.cards.iter().cloned())
+            .chain(deck.cards.iter().cloned())
+            .chain(table_cards)
+            .chain(captures.iter().flat_map(|c| c.iter_cards()));
+        for c in cards {
+            if !all.insert(&c) {
+                return Err(format!("Card {} appears more than once", c));
+            }
+        }
+
+        match state {
+            GameState::NextTurn(tpos) if hands[tpos.0 as usize].ncards() == 0 => {
+                return Err(format!("Player {} has no cards to play", tpos));
+            },
+            GameState::RoundDone if hands.iter().any(|h| h.ncards() > 0) || deck.ncards() == 0 => {
+                return Err("Round is done, but there are cards in the hands or no cards in the deck".to_string());
+            },
+            _ => (),
+        }
+
+        let teams = captures
+            .drain(..)
+            .zip(scores.iter())
+            .map(|(c, s)| Team { captures: c, score: *s })
+            .collect();
+        Ok(Game::from_parts(rules, hands, table, deck, state, first_player, teams, last_team, rng))
+    }
+
+    pub fn fmt_game<R: rand::Rng + Clone>(game: &Game<R>, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut sep = "";
+        for player in game.players.iter() {
+            f.write_str(sep)?;
+            sep = " | ";
+            fmt_or_empty(player.hand.len(), f, |f| DeckRepr::fmt_cards(player.hand.iter(), f))?;
+        }
+
+        write!(f, " / ")?;
+        fmt_or_empty(game.table.nentries(), f, |f| TableRepr::fmt_table(&game.table, f))?;
+        write!(f, " / ")?;
+        fmt_or_empty(game.main_deck.ncards(), f, |f| DeckRepr::fmt_deck(&game.main_deck, f))?;
+
+        write!(f, " / ")?;
+        match game.state {
+            GameState::NextTurn(tpos) => write!(f, "{}", tpos)?,
+            GameState::RoundDone => write!(f, "R")?,
+            GameState::GameDone(_) | GameState::MatchDone(_, _) => write!(f, "G")?,
+        }
+        write!(f, " {} T{}", game.first_player, game.last_team_captured)?;
+
+        write!(f, " /")?;
+        let mut sep = " ";
+        for team in game.teams.iter() {
+            f.write_str(sep)?;
+            sep = " | ";
+            let captures = &team.captures;
+            fmt_or_empty(captures.ncards(), f, |f| {
+                let mut sep = "";
+                for c in captures.iter_cards() {
+                    let mark = if captures.xeres().contains(&c) { "*" } else { "" };
+                    write!(f, "{}{}{}", sep, c, mark)?;
+                    sep = " ";
+                }
+                Ok(())
+            })?;
+        }
+
+        write!(f, " /")?;
+        for team in game.teams.iter() {
+            write!(f, " {}", team.score)?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_or_empty<F>(n: usize, f: &mut std::fmt::Formatter, fmt: F) -> std::fmt::Result
+where
+    F: FnOnce(&mut std::fmt::Formatter) -> std::fmt::Result,
+{
+    if n == 0 {
+        write!(f, "-")
+    } else {
+        fmt(f)
+    }
+}
+
+/// Split tokens into groups, using sep as the separator
+fn split_tokens<'a, I>(iter: I, sep: &str) -> Vec<Vec<&'a str>> where
+    I: Iterator<Item=&'a str>,
+{
+    let mut ret = vec![vec![]];
+    for tok in iter {
+        if tok == sep {
+            ret.push(vec![]);
+        } else {
+            ret.last_mut().unwrap().push(tok);
+        }
+    }
+    ret
+}
+
+/// "-" stands for an empty list of tokens
+fn empty_or<'a, 'b>(toks: &'b [&'a str]) -> std::iter::Cloned<std::slice::Iter<'b, &'a str>> {
+    match toks {
+        ["-"] => [].iter().cloned(),
+        _ => toks.iter().cloned(),
+    }
+}
+
+fn parse_tpos(s: &str, nplayers: usize) -> Option<PlayerTpos> {
+    s.strip_prefix("P")
+        .and_then(|x| x.parse::<u8>().ok())
+        .filter(|x| (*x as usize) < nplayers)
+        .map(PlayerTpos)
+}
+
+fn parse_captures<'a, I>(iter: I) -> Option<Captures> where
+    I: Iterator<Item=&'a str>,
+{
+    let mut captures = Captures::new();
+    let mut cards = vec![];
+    for tok in iter {
+        match tok.strip_suffix("*") {
+            Some(x) => captures.add_cards_(vec![Card::try_from(x).ok()?], true),
+            None => cards.push(Card::try_from(tok).ok()?),
+        }
+    }
+    captures.add_cards_(cards, false);
+    Some(captures)
+}
+
 /**
  * Implementation of std::fmt::Display traits
  */
@@ -256,6 +621,12 @@ impl std::fmt::Display for Deck {
     }
 }
 
+impl std::fmt::Display for CardSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        DeckRepr::fmt_cards(self.iter(), f)
+    }
+}
+
 impl std::fmt::Debug for TableEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         TableEntryRepr::fmt_table_entry(self, f)
@@ -267,3 +638,15 @@ impl std::fmt::Display for Table {
         TableRepr::fmt_table(self, f)
     }
 }
+
+impl<R: rand::Rng + Clone> std::fmt::Display for Game<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        GameRepr::fmt_game(self, f)
+    }
+}
+
+impl std::fmt::Display for PlayerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        ActionRepr::fmt_action(self, f)
+    }
+}
@@ -0,0 +1,75 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use serde::{Deserialize, Serialize};
+
+use super::scoring::{NCARDS, XERI_SCORE};
+
+/// Number of cards dealt to each player on every round
+pub const HAND_SIZE: usize = 6;
+/// Number of cards initially placed on the table in the classic variant
+pub const TABLE_SIZE: usize = 4;
+
+/// Rule variant of a game
+///
+/// The default is the classic variant: forced captures and forced declarations, 10 points for
+/// every xeri, and 4 points for the team with the most cards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSet {
+    /// When capturing with a non-figure, every other table entry with the same value is captured
+    /// as well
+    pub forced_captures: bool,
+    /// When declaring, every table card with the declaration's value is added to it
+    pub forced_declarations: bool,
+    /// Points for each xeri (0 disables xeri scoring)
+    pub xeri_score: usize,
+    /// A xeri made with a figure (J, Q, K) counts double
+    pub xeri_figure_double: bool,
+    /// Extra points for the team that captured the majority of the cards
+    pub majority_bonus: bool,
+    /// Number of cards initially placed on the table
+    pub table_size: usize,
+}
+
+impl RuleSet {
+    pub fn classic() -> RuleSet {
+        RuleSet {
+            forced_captures: true,
+            forced_declarations: true,
+            xeri_score: XERI_SCORE,
+            xeri_figure_double: false,
+            majority_bonus: true,
+            table_size: TABLE_SIZE,
+        }
+    }
+
+    /// Check that the rules can be used for a game with the given number of players.
+    ///
+    /// Games have 1, 2, or 4 players. After placing the table cards, the rest of the deck has to
+    /// be dealt evenly to the players. The last round might have fewer than HAND_SIZE cards per
+    /// player.
+    pub fn validate(&self, nplayers: usize) -> Result<(), String> {
+        if nplayers != 1 && nplayers != 2 && nplayers != 4 {
+            return Err(format!("Invalid number of players: {}", nplayers));
+        }
+
+        if self.table_size + nplayers * HAND_SIZE > NCARDS {
+            return Err(format!("Invalid table size: {}", self.table_size));
+        }
+
+        if (NCARDS - self.table_size) % nplayers != 0 {
+            return Err(format!("Table size {} does not allow dealing the deck evenly to {} players", self.table_size, nplayers));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::classic()
+    }
+}
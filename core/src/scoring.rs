@@ -5,10 +5,17 @@
 
 use serde::{Deserialize, Serialize};
 
-use super::card::{Card, Rank, Suit};
+use super::card::{Card, CardSet, Rank, Suit};
+use super::rules::RuleSet;
 
+/// Cards captured by a team
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Captures(Vec<Capture>);
+pub struct Captures {
+    /// all captured cards (including xeres)
+    cards: CardSet,
+    /// cards that made a xeri
+    xeres: Vec<Card>,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Capture {
@@ -34,6 +41,8 @@ pub struct ScoreSheet {
     pub nr_cards: usize,           // 4 points for the team with the more cards
     pub nr_xeres: usize,           // 10 points for each xeri
     pub score_cards: Vec<Card>,    // scoring cards
+    pub cards_score: usize,        // points for having the more cards
+    pub xeres_score: usize,        // points for xeres
     pub score: usize,              // total score
 }
 
@@ -43,6 +52,8 @@ impl ScoreSheet {
             nr_cards: 0,
             nr_xeres: 0,
             score_cards: vec![],
+            cards_score: 0,
+            xeres_score: 0,
             score: 0,
         }
     }
@@ -51,18 +62,24 @@ impl ScoreSheet {
         self.nr_cards > (NCARDS / 2)
     }
 
-    pub fn add_capture(mut self, capture: &Capture) -> Self {
+    pub fn add_capture(mut self, capture: &Capture, rules: &RuleSet) -> Self {
         let card = match capture {
             Capture::Card(c) => c,
             Capture::Xeri(c) => {
+                let mut xeri_score = rules.xeri_score;
+                if rules.xeri_figure_double && c.rank.is_figure() {
+                    xeri_score *= 2;
+                }
                 self.nr_xeres += 1;
-                self.score += XERI_SCORE;
+                self.xeres_score += xeri_score;
+                self.score += xeri_score;
                 c
             },
         };
 
         self.nr_cards += 1;
-        if self.nr_cards == (NCARDS / 2) + 1 {
+        if self.nr_cards == (NCARDS / 2) + 1 && rules.majority_bonus {
+            self.cards_score += NCARDS_SCORE;
             self.score  += NCARDS_SCORE;
         }
 
@@ -90,18 +107,17 @@ impl Capture {
 
 impl Captures {
     pub fn new() -> Captures {
-        Captures(vec![])
+        Captures {
+            cards: CardSet::empty(),
+            xeres: vec![],
+        }
     }
 
-    pub fn add_cards_(&mut self, mut cards: Vec<Card>, is_xeri: bool) {
-        let mut iter = cards.drain(..);
+    pub fn add_cards_(&mut self, cards: Vec<Card>, is_xeri: bool) {
         if is_xeri {
-            let xeri_card = iter.next().unwrap();
-            let xeri = Capture::Xeri(xeri_card);
-            self.0.push(xeri);
+            self.xeres.push(cards[0].clone());
         }
-
-        self.0.extend(iter.map(|x| Capture::Card(x)));
+        self.cards.extend(cards.iter());
     }
 
     pub fn add_final_cards(&mut self, cards: Vec<Card>, is_xeri: bool) {
@@ -113,7 +129,26 @@ impl Captures {
         self.add_cards_(cards, is_xeri);
     }
 
-    pub fn score(&self) -> ScoreSheet {
-        self.0.iter().fold(ScoreSheet::new(), |ss, c| ss.add_capture(c))
+    pub fn iter_cards(&self) -> impl Iterator<Item=Card> {
+        self.cards.iter()
+    }
+
+    pub fn cards(&self) -> CardSet {
+        self.cards
+    }
+
+    /// Cards that made a xeri, in the order they were captured
+    pub fn xeres(&self) -> &[Card] {
+        &self.xeres
+    }
+
+    pub fn ncards(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn score(&self, rules: &RuleSet) -> ScoreSheet {
+        let xeres: CardSet = self.xeres.iter().collect();
+        let ss = self.xeres.iter().fold(ScoreSheet::new(), |ss, c| ss.add_capture(&Capture::Xeri(c.clone()), rules));
+        (self.cards - xeres).iter().fold(ss, |ss, c| ss.add_capture(&Capture::Card(c), rules))
     }
 }
@@ -5,22 +5,36 @@
 //
 
 use serde::{Deserialize, Serialize};
-use crate::{game, deck, table, actions, repr};
+use crate::{game, table, actions, repr, rules, bot, turn_timer};
+use crate::error::ActionError;
 
-pub use game::PlayerGameView;
+pub use game::{PlayerGameView, SpectatorGameView};
 pub use table::PlayerTpos;
 
 /// Server <-> client interaction
 
+/// Debug (scenario) game: every hand, the table, and the deck are fixed
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreateReqDebug {
-    pub hand_s: String,
-    pub table_s: String,
+    /// Initial position (see repr::GameRepr). The starting player can be set in the (optional)
+    /// state field.
+    pub game_s: String,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreateReq {
     pub nplayers: u8,
+    /// Score for winning the match (None: play games forever)
+    pub target_score: Option<usize>,
+    /// Game variant
+    #[serde(default)]
+    pub rules: rules::RuleSet,
+    /// Time limit for each turn (None: players can take as long as they want)
+    #[serde(default)]
+    pub turn_timer: Option<turn_timer::TurnTimer>,
+    /// List the game in the public games directory (GET /games) while it waits for players
+    #[serde(default)]
+    pub public: bool,
     pub debug: Option<CreateReqDebug>,
 }
 
@@ -32,6 +46,20 @@ pub struct CreateRep {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JoinReq { }
 
+/// A public game that waits for players (GET /games)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameListing {
+    pub game_id: String,
+    /// name of the player that created the game
+    pub admin: String,
+    pub nplayers: u8,
+    /// number of seats that are not taken
+    pub free_seats: u8,
+    pub target_score: Option<usize>,
+    pub rules: rules::RuleSet,
+    pub turn_timer: Option<turn_timer::TurnTimer>,
+}
+
 /**
  * Lobby state
  */
@@ -45,8 +73,10 @@ pub struct PlayerInfo {
     pub tpos: PlayerTpos,
     /// player name
     pub name: String,
-    /// connected: is player connected to the server?
+    /// connected: is player connected to the server? (bots are always connected)
     pub connected: bool,
+    /// is player a bot?
+    pub bot: bool,
 }
 
 /// PlayerId identifies a player in the lobby.
@@ -66,6 +96,17 @@ pub struct LobbyInfo {
     pub players: Vec<PlayerInfo>,
     pub self_id: PlayerId, // self id in the vector (so that the player knows who they are)
     pub nplayers: u8,   // total number of players for the game
+    /// names of the spectators watching the game
+    pub spectators: Vec<String>,
+}
+
+/// What a spectator sees: the players and, once it has started, the game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpectatorInfo {
+    pub players: Vec<PlayerInfo>,
+    pub nplayers: u8,
+    pub spectators: Vec<String>,
+    pub game: Option<SpectatorGameView>,
 }
 
 /**
@@ -77,11 +118,27 @@ pub struct LobbyInfo {
  * Message types
  */
 
+/// Maximum length of a chat message (in characters). Longer messages are truncated.
+pub const CHAT_MAX_LEN: usize = 256;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerMsg {
+    /// Secret token for the player's seat, sent once after registration. Presenting it when
+    /// connecting again (/ws/<game_id>/<name>?token=<token>) reattaches the player to their seat.
+    SessionToken(String),
     LobbyUpdate(LobbyInfo),
     GameUpdate(PlayerGameView),
-    InvalidAction(String),
+    InvalidAction(ActionError),
+    /// Update for spectators (/ws/<game_id>/watch/<name>), who get no other messages
+    SpectatorUpdate(SpectatorInfo),
+    /// Chat message from a player (ts: seconds since the UNIX epoch). Recent messages are
+    /// replayed to players when they (re)connect, after the LobbyUpdate.
+    Chat { from: PlayerId, text: String, ts: u64 },
+    /// Sent to quick-match players (/ws/quickmatch/<nplayers>/<name>) when they join the queue.
+    /// If there is no game for them within timeout_secs, the server closes the connection.
+    QuickMatchWaiting { timeout_secs: u64 },
+    /// Id of the game that a quick-match player was placed in. Sent before the SessionToken.
+    QuickMatch(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,34 +146,43 @@ pub enum ClientMsg {
     StartGame,
     SwapTpos(PlayerTpos, PlayerTpos),
     PlayerAction(actions::PlayerAction),
+    /// Add a bot to a free position in the lobby (admin only)
+    AddBot(bot::BotKind),
+    /// Remove the bot at the given position from the lobby (admin only)
+    RemoveBot(PlayerTpos),
+    /// Send a chat message to all players
+    Chat(String),
 }
 
 impl CreateReq {
     pub fn verify_debug(&mut self) {
-        let valid = self.get_debug_hand().is_some() && self.get_debug_table().is_some();
-        if !valid {
+        if self.check_debug_game().is_err() {
             self.debug = None
         }
     }
 
-    pub fn get_debug_hand(&self) -> Option<deck::Deck> {
-        if let Some(debug) = &self.debug {
-            if debug.hand_s.len() == 0 {
-                None
-            } else {
-                repr::DeckRepr::new(&debug.hand_s).parse()
-            }
-        } else { None }
+    /// Check the debug position for this request. Returns an error message if it is invalid.
+    pub fn check_debug_game(&self) -> Result<(), String> {
+        let debug = match &self.debug {
+            Some(x) if x.game_s.len() > 0 => x,
+            _ => return Ok(()),
+        };
+
+        // the rng is not used for the position itself
+        let rng = rand::rngs::mock::StepRng::new(0, 1);
+        let game = repr::GameRepr::new(&debug.game_s).parse(self.rules.clone(), rng)?;
+        if game.players.len() != self.nplayers as usize {
+            return Err(format!("Position is for {} players", game.players.len()));
+        }
+        Ok(())
     }
 
-    pub fn get_debug_table(&self) -> Option<table::Table> {
-        if let Some(debug) = &self.debug {
-            if debug.table_s.len() == 0 {
-                None
-            } else {
-                repr::TableRepr::new(&debug.table_s).parse()
-            }
-        } else { None }
+    /// Debug position for this request (see check_debug_game())
+    pub fn get_debug_game(&self) -> Option<repr::GameRepr> {
+        match &self.debug {
+            Some(debug) if debug.game_s.len() > 0 => Some(repr::GameRepr::new(&debug.game_s)),
+            _ => None,
+        }
     }
 }
 
@@ -198,6 +264,10 @@ impl LobbyInfo {
         }
     }
 
+    pub fn has_free_slots(&self) -> bool {
+        self.players.len() < self.nplayers as usize
+    }
+
     pub fn disconnected_players(&self) -> Vec<PlayerId> {
         self.players
             .iter()
@@ -5,7 +5,7 @@
 //
 
 use serde::{Deserialize, Serialize};
-use super::card::Card;
+use super::card::{Card, CardSet};
 
 
 /// Player identifier based on their position on the table
@@ -213,6 +213,17 @@ impl Table {
         Some(self.entries.remove(pos))
     }
 
+    /// Cards on the table that are not part of a declaration
+    pub fn card_set(&self) -> CardSet {
+        self.entries
+            .iter()
+            .filter_map(|e| match e {
+                TableEntry::Card(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn iter_cards_with_val(&self, val: u8) -> impl Iterator<Item=&TableEntry> {
         self.entries.iter().filter(move |x| x.is_card() && x.value() == val)
     }
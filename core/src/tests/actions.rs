@@ -5,15 +5,18 @@
 //
 
 use std::convert::TryFrom;
+use rand::{SeedableRng, seq::SliceRandom};
 
 use crate::{
-    repr::{TableRepr, TableEntryRepr, DeckRepr},
+    repr::{TableRepr, TableEntryRepr, DeckRepr, ActionRepr},
     actions::{DeclAction, PlayerAction, CaptureAction},
     table::{Table, TableEntry, PlayerTpos},
     game::Game,
     card::Card,
+    error::ActionError,
 };
 
+use super::{TestRng, Until, mk_game, play};
 
 pub fn mk_te(s: &str) -> TableEntry {
     TableEntryRepr::new(s).parse().unwrap()
@@ -23,6 +26,10 @@ pub fn mk_table(s: &str) -> Table {
     TableRepr::new(s).parse().unwrap()
 }
 
+pub fn mk_act(s: &str) -> PlayerAction {
+    ActionRepr::new(s).parse().unwrap()
+}
+
 #[test]
 fn act_t0() {
     let rng = rand::thread_rng(); // not going to be used
@@ -41,13 +48,11 @@ fn act_t0() {
             tentries: vec![vec![mk_te("C9")]],
         };
         println!("action: {:?} is be invalid (no cards from table)", decl_act);
-        assert!(decl_act.validate_decl(&table, &hand).is_err());
+        assert_eq!(decl_act.validate_decl(&table, &hand), Err(ActionError::DeclTooFewCards));
     }
 
     {
-        let decl_act = PlayerAction::Declare(DeclAction {
-            tentries: vec![vec![mk_te("♦5"), mk_te("♠4")]],
-        });
+        let decl_act = mk_act("D ( ♦5 ♠4 )");
         let game = game.apply_action(tpos, decl_act).unwrap();
         println!("{}", mk_table("♥T 0:[ ♦5 ♠4 ][ H9 ]:"));
         // NB: equality is based on order, so we might just need to rearrange the result
@@ -66,26 +71,16 @@ fn no_two_decls() {
     let hand = &game.players[0].hand;
     let table = &game.table;
 
-    let act1 = PlayerAction::Declare(DeclAction {
-        tentries: vec![
-            vec![mk_te("D2")],
-            vec![mk_te("S2")],
-        ],
-    });
+    let act1 = mk_act("D ( D2 ) ( S2 )");
     println!("hand: {}", hand);
     println!("table: {}", table);
     println!("act1: {:?}", act1);
     let game = game.apply_action(tpos, act1).unwrap();
 
-    let act2 = PlayerAction::Declare(DeclAction {
-        tentries: vec![
-            vec![mk_te("D5")],
-            vec![mk_te("S5")],
-        ],
-    });
+    let act2 = mk_act("D ( D5 ) ( S5 )");
     println!("act2: {:?}", act2);
     let res = game.apply_action(tpos, act2);
-    assert!(res.is_err());
+    assert_eq!(res.as_ref().unwrap_err(), &ActionError::NewDeclWhileOwnExists);
     println!("table: {:?}", res);
 }
 
@@ -101,24 +96,13 @@ fn two_figs_on_table() {
     let hand = &game.players[0].hand;
     let table = &game.table;
     {
-        let act1 = PlayerAction::Capture(CaptureAction {
-            handcard: Card::try_from("DK").unwrap(),
-            tentries: vec![
-                vec![mk_te("SK")],
-                vec![mk_te("HK")],
-            ],
-        });
+        let act1 = mk_act("C DK ( SK ) ( HK )");
         let res1 = game.apply_action(tpos, act1);
         println!("res: {:?}", res1);
-        assert!(res1.is_err());
+        assert_eq!(res1.unwrap_err(), ActionError::OnlyOneOfTwoFigures);
     }
     {
-        let act2 = PlayerAction::Capture(CaptureAction {
-            handcard: Card::try_from("DK").unwrap(),
-            tentries: vec![
-                vec![mk_te("HK")],
-            ],
-        });
+        let act2 = mk_act("C DK ( HK )");
         println!("table before action: {:?}", game.table.entries);
         let res2 = game.apply_action(tpos, act2);
         println!("res2: {:?}", res2);
@@ -142,39 +126,21 @@ fn three_figs_on_table() {
     let table = &game.table;
 
     {
-        let act = PlayerAction::Capture(CaptureAction {
-            handcard: Card::try_from("DK").unwrap(),
-            tentries: vec![
-                vec![mk_te("SK")],
-            ],
-        });
+        let act = mk_act("C DK ( SK )");
         let res = game.apply_action(tpos, act);
         println!("res: {:?}", res);
-        assert!(res.is_err());
+        assert_eq!(res.unwrap_err(), ActionError::MustCaptureAllThreeFigures);
     }
 
     {
-        let act = PlayerAction::Capture(CaptureAction {
-            handcard: Card::try_from("DK").unwrap(),
-            tentries: vec![
-                vec![mk_te("SK")],
-                vec![mk_te("HK")],
-            ],
-        });
+        let act = mk_act("C DK ( SK ) ( HK )");
         let res = game.apply_action(tpos, act);
         println!("res: {:?}", res);
-        assert!(res.is_err());
+        assert_eq!(res.unwrap_err(), ActionError::MustCaptureAllThreeFigures);
     }
 
     {
-        let act = PlayerAction::Capture(CaptureAction {
-            handcard: Card::try_from("DK").unwrap(),
-            tentries: vec![
-                vec![mk_te("SK")],
-                vec![mk_te("HK")],
-                vec![mk_te("CK")],
-            ],
-        });
+        let act = mk_act("C DK ( SK ) ( HK ) ( CK )");
         println!("table before action: {:?}", game.table.entries);
         let res = game.apply_action(tpos, act);
         println!("res2: {:?}", res);
@@ -184,3 +150,113 @@ fn three_figs_on_table() {
         assert_eq!(game.table.entries, vec![]);
     }
 }
+
+#[test]
+fn nonexistent_entries() {
+    let tpos = PlayerTpos(0);
+    let game = mk_game("S2 S5 1:[ D3 H2 ]:", "D7 H5 C5");
+
+    let act = mk_act("L D9");
+    let res = game.apply_action(tpos, act);
+    assert_eq!(res.unwrap_err(), ActionError::CardNotInHand { card: Card::try_from("D9").unwrap() });
+
+    let act = mk_act("C D7 ( S5 C2 )");
+    let res = game.apply_action(tpos, act);
+    assert_eq!(res.unwrap_err(), ActionError::CardNotOnTable { card: Card::try_from("C2").unwrap() });
+
+    let act = mk_act("C H5 ( S5 ) ( S5 )");
+    let res = game.apply_action(tpos, act);
+    assert_eq!(res.unwrap_err(), ActionError::DuplicateTableEntry);
+
+    let act = mk_act("C H5 ( 1:[ D3 S2 ]: )");
+    let res = game.apply_action(tpos, act);
+    assert_eq!(res.unwrap_err(), ActionError::DeclNotOnTable);
+
+    let act = mk_act("C H5 ( S5 ) ( 1:[ D3 H2 ]: )");
+    assert!(game.apply_action(tpos, act).is_ok());
+}
+
+#[test]
+fn apply_action_mut() {
+    let tpos = PlayerTpos(0);
+    let mut game = mk_game("S2 S5", "D7 H5 C5");
+
+    // an invalid action leaves the game unchanged
+    let act = mk_act("C D7 ( S5 S2 ) ( S2 S5 )");
+    assert_eq!(game.apply_action_mut(tpos, act), Err(ActionError::DuplicateTableEntry));
+    assert_eq!(game.table.entries, mk_table("S2 S5").entries);
+    assert_eq!(game.players[0].hand.len(), 3);
+
+    let act = mk_act("C D7 ( S5 S2 )");
+    let game_copy = game.apply_action(tpos, act.clone()).unwrap();
+    assert!(game.apply_action_mut(tpos, act).is_ok());
+    assert_eq!(game.table.entries, game_copy.table.entries);
+    assert_eq!(game.players[0].hand, game_copy.players[0].hand);
+    assert_eq!(game.table.entries, vec![]);
+}
+
+#[test]
+fn action_repr() {
+    let act = mk_act("C D7 ( S5 S2 ) ( 1:[ D3 H4 ]: )");
+    let expected = PlayerAction::Capture(CaptureAction {
+        handcard: Card::try_from("D7").unwrap(),
+        tentries: vec![vec![mk_te("S5"), mk_te("S2")], vec![mk_te("1:[ D3 H4 ]:")]],
+    });
+    assert_eq!(act, expected);
+    assert_eq!(format!("{}", act), "C ♦7 ( ♠5 ♠2 ) ( 1:[ ♦3 ♥4 ]: )");
+
+    let act = mk_act("D ( D5 S4 ) ( H9 )");
+    let expected = PlayerAction::Declare(DeclAction {
+        tentries: vec![vec![mk_te("D5"), mk_te("S4")], vec![mk_te("H9")]],
+    });
+    assert_eq!(act, expected);
+    assert_eq!(mk_act("L HT"), PlayerAction::LayDown(Card::try_from("HT").unwrap()));
+
+    for s in ["", "X D5", "L", "L D5 D6", "C D5", "C D5 ( )", "C D5 ( S5", "C D5 S5", "D ( 0:[ D3 H2 ]: S5 )"].iter() {
+        assert_eq!(ActionRepr::new(*s).parse(), None, "{:?}", s);
+    }
+
+    // every legal action round-trips
+    let mut game = Game::new_2p(TestRng::seed_from_u64(0));
+    let mut rng = TestRng::seed_from_u64(0);
+    play(&mut game, Until::Steps(100), |game, _| {
+        let legal = game.legal_actions();
+        for act in legal.iter() {
+            assert_eq!(&mk_act(&act.to_string()), act);
+        }
+        legal.choose(&mut rng).unwrap().clone()
+    });
+}
+
+#[test]
+fn decl_captured_alone() {
+    let tpos = PlayerTpos(0);
+    let game = mk_game("S5 1:[ D3 H2 ]:", "HT H5 C9");
+
+    // a declaration cannot be part of a larger group
+    let act = mk_act("C HT ( S5 1:[ D3 H2 ]: )");
+    assert_eq!(game.apply_action(tpos, act).unwrap_err(), ActionError::DeclNotCapturedAlone);
+
+    let act = mk_act("C H5 ( S5 ) ( 1:[ D3 H2 ]: )");
+    let game = game.apply_action(tpos, act).unwrap();
+    assert_eq!(game.table.entries, vec![]);
+}
+
+#[test]
+fn group_decl_combined() {
+    let tpos = PlayerTpos(0);
+    let game = mk_game("S5 S3 1:[ D3 H2 ][ C5 ]:", "DT HT D2 H5");
+
+    // a group declaration cannot be combined with the hand card
+    let act = mk_act("D ( DT 1:[ D3 H2 ][ C5 ]: )");
+    assert_eq!(game.apply_action(tpos, act).unwrap_err(), ActionError::GroupDeclCombined);
+
+    // or with other table entries
+    let act = mk_act("D ( DT ) ( S5 1:[ D3 H2 ][ C5 ]: )");
+    assert_eq!(game.apply_action(tpos, act).unwrap_err(), ActionError::GroupDeclCombined);
+
+    // but it can be a group on its own
+    let act = mk_act("D ( D2 S3 ) ( 1:[ D3 H2 ][ C5 ]: ) ( S5 )");
+    let game = game.apply_action(tpos, act).unwrap();
+    assert_eq!(game.table.entries, mk_table("0:[ D2 S3 ][ D3 H2 ][ C5 ][ S5 ]:").entries);
+}
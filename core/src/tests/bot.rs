@@ -0,0 +1,71 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use std::convert::TryFrom;
+use rand::SeedableRng;
+
+use crate::{
+    actions::{PlayerAction, CaptureAction},
+    table::PlayerTpos,
+    game::{Game, GameState},
+    bot::{Bot, RandomBot, GreedyBot},
+    card::Card,
+};
+
+use super::actions::mk_te;
+use super::{TestRng, Until, mk_game, play};
+
+/// Play a game where team 0 uses bot0 and team 1 uses bot1. Returns the total scores.
+fn play_game(seed: u64, bot0: &mut dyn Bot, bot1: &mut dyn Bot) -> Vec<usize> {
+    let mut game = Game::new_2p(TestRng::seed_from_u64(seed));
+    play(&mut game, Until::GameDone, |game, tpos| {
+        let view = game.get_player_game_view(tpos);
+        let bot: &mut dyn Bot = if tpos.0 % 2 == 0 { bot0 } else { bot1 };
+        bot.choose_action(&view).expect("bot action")
+    });
+    match game.state() {
+        GameState::GameDone(scores) | GameState::MatchDone(scores, _) => {
+            scores.iter().map(|(_, total)| *total).collect()
+        },
+        x => panic!("unexpected state: {:?}", x),
+    }
+}
+
+#[test]
+fn bot_not_my_turn() {
+    let game = Game::new_2p(TestRng::seed_from_u64(0));
+    let view = game.get_player_game_view(PlayerTpos(1));
+    assert_eq!(GreedyBot::new().choose_action(&view), None);
+    assert_eq!(RandomBot::new(TestRng::seed_from_u64(0)).choose_action(&view), None);
+}
+
+#[test]
+fn greedy_xeri() {
+    let game = mk_game("S5 H3", "D8 C3 H9");
+    let view = game.get_player_game_view(PlayerTpos(0));
+    let expected = PlayerAction::Capture(CaptureAction {
+        handcard: Card::try_from("D8").unwrap(),
+        tentries: vec![vec![mk_te("S5"), mk_te("H3")]],
+    });
+    assert_eq!(GreedyBot::new().choose_action(&view), Some(expected));
+}
+
+#[test]
+fn greedy_vs_random() {
+    let mut greedy = GreedyBot::new();
+    let mut random = RandomBot::new(TestRng::seed_from_u64(0));
+    let (mut greedy_total, mut random_total) = (0, 0);
+    for seed in 0..20 {
+        // alternate sides, so that first player advantage does not matter
+        let scores = play_game(seed, &mut greedy, &mut random);
+        greedy_total += scores[0];
+        random_total += scores[1];
+        let scores = play_game(seed, &mut random, &mut greedy);
+        greedy_total += scores[1];
+        random_total += scores[0];
+    }
+    assert!(greedy_total > random_total, "greedy: {} random: {}", greedy_total, random_total);
+}
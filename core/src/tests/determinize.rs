@@ -0,0 +1,114 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use std::collections::HashSet;
+use rand::SeedableRng;
+
+use crate::{
+    table::{TableEntry, PlayerTpos},
+    game::{Game, GameState},
+    scoring::Captures,
+    determinize::{determinize, PublicCards},
+};
+
+use super::mcts::play_random;
+use super::TestRng;
+
+#[test]
+fn determinize_consistent() {
+    let mut rng = TestRng::seed_from_u64(0);
+    for seed in 0..20 {
+        let mut game = Game::new_2p(TestRng::seed_from_u64(seed));
+        play_random(&mut game, seed, seed as usize);
+        let tpos = match game.state() {
+            GameState::NextTurn(tpos) => *tpos,
+            _ => continue,
+        };
+        let view = game.get_player_game_view(tpos);
+        let sample = determinize(&view, &[], &mut rng);
+        assert_eq!(format!("{:?}", sample.state), format!("{:?}", game.state));
+        assert_eq!(sample.first_player, game.first_player);
+        assert_eq!(sample.table.entries, game.table.entries);
+        assert_eq!(sample.main_deck.ncards(), game.main_deck.ncards());
+
+        let mut cards = HashSet::new();
+        for p in 0..2 {
+            let pview = sample.get_player_game_view(PlayerTpos(p));
+            assert_eq!(pview.player_decks_sz, view.player_decks_sz);
+            assert_eq!(pview.main_deck_sz, view.main_deck_sz);
+            for c in pview.iter_hand_cards() {
+                assert!(cards.insert(c.clone()), "duplicate card: {}", c);
+            }
+
+            // every declaration is backed
+            for te in pview.iter_table_entries() {
+                if let TableEntry::Decl(d) = te {
+                    if d.player == pview.pid {
+                        assert!(pview.iter_hand_cards().any(|c| c.rank.0 == d.value()));
+                    }
+                }
+            }
+        }
+        assert_eq!(sample.players[tpos.0 as usize].hand, game.players[tpos.0 as usize].hand);
+    }
+}
+
+#[test]
+fn determinize_history() {
+    let mut rng = TestRng::seed_from_u64(0);
+    let mut game = Game::new_2p(TestRng::seed_from_u64(1));
+    play_random(&mut game, 1, 20);
+    let tpos = match game.state() {
+        GameState::NextTurn(tpos) => *tpos,
+        x => panic!("unexpected state: {:?}", x),
+    };
+
+    // a view without the captured piles: the history tells us which cards were captured
+    let mut view = game.get_player_game_view(tpos);
+    view.captures = vec![Captures::new(), Captures::new()];
+    let history = &game.history().last().unwrap().actions;
+    let public = PublicCards::from_view(&view);
+    let mut public_hist = public.clone();
+    public_hist.add_actions(history.iter());
+    assert!(public_hist.ncards() > public.ncards());
+    for c in game.teams.iter().flat_map(|t| t.captures.iter_cards()) {
+        assert!(public_hist.contains(&c));
+    }
+
+    let sample = determinize(&view, history, &mut rng);
+    assert_eq!(sample.main_deck.ncards(), game.main_deck.ncards());
+    for c in sample.players.iter().flat_map(|p| p.hand.iter()) {
+        assert!(!public_hist.contains(&c));
+    }
+}
+
+#[test]
+fn determinize_uniform() {
+    let mut rng = TestRng::seed_from_u64(0);
+    let game = Game::new_2p(TestRng::seed_from_u64(0));
+    let tpos = match game.state() {
+        GameState::NextTurn(tpos) => *tpos,
+        x => panic!("unexpected state: {:?}", x),
+    };
+    let view = game.get_player_game_view(tpos);
+    let opp = ((tpos.0 + 1) % 2) as usize;
+    let unseen = PublicCards::from_view(&view).unseen(&view);
+
+    let nsamples = 4000;
+    let mut counts = vec![0usize; unseen.len()];
+    for _ in 0..nsamples {
+        let sample = determinize(&view, &[], &mut rng);
+        for c in sample.players[opp].hand.iter() {
+            counts[unseen.iter().position(|u| *u == c).unwrap()] += 1;
+        }
+    }
+
+    let expected = view.player_decks_sz[opp] as f64 / unseen.len() as f64;
+    for (c, n) in unseen.iter().zip(counts.iter()) {
+        let p = *n as f64 / nsamples as f64;
+        assert!((p - expected).abs() < 0.04, "{}: {} (expected: {})", c, p, expected);
+    }
+}
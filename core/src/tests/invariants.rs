@@ -0,0 +1,100 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use rand::SeedableRng;
+
+use crate::{
+    game::{Game, GameState},
+    table::{TableEntry, PlayerTpos},
+    rules::RuleSet,
+};
+
+use super::actions::{mk_act, mk_table};
+use super::{TestRng, Until, mk_position, step, random_actions};
+
+fn check(game: &Game<TestRng>, what: &str) {
+    if let Err(err) = game.check_invariants() {
+        panic!("invariant violated after {}: {}\n{:?}", what, err, game);
+    }
+}
+
+/// Play a match with random legal actions, checking the invariants after every step
+fn play_checked_match(nplayers: usize, rules: RuleSet, seed: u64) {
+    let mut rng = TestRng::seed_from_u64(seed);
+    let mut game = Game::new_with_rules(nplayers, rules, TestRng::seed_from_u64(seed + 1));
+    game.set_target_score(Some(31));
+    check(&game, "new game");
+
+    // avoid looping forever if the match is never decided (ties)
+    let mut choose = random_actions(&mut rng);
+    for i in 0..10_000 {
+        if !step(&mut game, Until::MatchDone, &mut choose) {
+            return;
+        }
+        check(&game, &format!("step {}", i));
+    }
+}
+
+#[test]
+fn invariants_random_matches() {
+    let mut variant = RuleSet::classic();
+    variant.forced_captures = false;
+    variant.forced_declarations = false;
+    variant.table_size = 0;
+
+    for seed in 0..8 {
+        for nplayers in [1usize, 2, 4].iter() {
+            play_checked_match(*nplayers, RuleSet::classic(), seed);
+            play_checked_match(*nplayers, variant.clone(), seed);
+        }
+    }
+}
+
+#[test]
+fn invariants_violations() {
+    let game = Game::new_2p(TestRng::seed_from_u64(0));
+    assert!(game.check_invariants().is_ok());
+
+    // a card shows up twice
+    let mut g = game.clone();
+    let card = g.main_deck.cards[0].clone();
+    g.table.entries.push(TableEntry::Card(card));
+    assert!(g.check_invariants().is_err());
+
+    // a card goes missing
+    let mut g = game.clone();
+    g.main_deck.pop();
+    assert!(g.check_invariants().is_err());
+
+    // out of turn (nobody has played yet)
+    let mut g = game.clone();
+    g.state = GameState::NextTurn(PlayerTpos(1));
+    assert!(g.check_invariants().is_err());
+
+    // invalid team
+    let mut g = game.clone();
+    g.last_team_captured = 2;
+    assert!(g.check_invariants().is_err());
+
+    let game = mk_position("D5 S4 | H2 C8 / S5 HT / HA H3");
+    assert!(game.check_invariants().is_ok());
+    let with_table = |table_s: &str| {
+        let mut g = game.clone();
+        g.table = mk_table(table_s);
+        g.check_invariants()
+    };
+    assert!(with_table("S5 HT 1:[ S6 C2 ]:").is_ok());
+    assert!(with_table("S5 HT 1:[ S6 C2 ][ C9 ]:").unwrap_err().contains("group of value 9"));
+    assert!(with_table("S5 HT 1:[ SA CA ]: 1:[ S6 C2 ]:").unwrap_err().contains("more than one"));
+    assert!(with_table("S5 HT 1:[ S6 C3 ]:").unwrap_err().contains("no card to capture"));
+    assert!(with_table("S5 HT 1:[ S6 ]:").unwrap_err().contains("less than two cards"));
+
+    // the capture goes to the wrong team
+    let mut g = game.apply_action(PlayerTpos(0), mk_act("C D5 ( S5 )")).unwrap();
+    assert!(g.check_invariants().is_ok());
+    g.last_team_captured = 1;
+    assert!(g.check_invariants().is_err());
+}
@@ -0,0 +1,58 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use std::convert::TryFrom;
+
+use crate::{
+    actions::{PlayerAction, CaptureAction},
+    table::PlayerTpos,
+    game::{Game, GameState},
+    scoring::ScoreSheet,
+    card::Card,
+};
+
+use super::actions::mk_te;
+use super::mk_game;
+
+fn mk_scores(totals: &[usize]) -> Vec<(ScoreSheet, usize)> {
+    totals.iter().map(|t| (ScoreSheet::new(), *t)).collect()
+}
+
+#[test]
+fn match_winner() {
+    let mut game = Game::new_2p(rand::thread_rng());
+    assert_eq!(game.match_winner(&mk_scores(&[70, 10])), None);
+
+    game.set_target_score(Some(61));
+    assert_eq!(game.match_winner(&mk_scores(&[60, 10])), None);
+    assert_eq!(game.match_winner(&mk_scores(&[61, 10])), Some(0));
+    assert_eq!(game.match_winner(&mk_scores(&[10, 65])), Some(1));
+    // both teams cross the target: highest score wins
+    assert_eq!(game.match_winner(&mk_scores(&[62, 65])), Some(1));
+    // both teams cross the target with the same score: keep playing
+    assert_eq!(game.match_winner(&mk_scores(&[65, 65])), None);
+}
+
+#[test]
+fn match_done() {
+    let mut game = mk_game("S5", "D5");
+    game.set_target_score(Some(10));
+
+    // xeri
+    let act = PlayerAction::Capture(CaptureAction {
+        handcard: Card::try_from("D5").unwrap(),
+        tentries: vec![vec![mk_te("S5")]],
+    });
+    let mut game = game.apply_action(PlayerTpos(0), act).unwrap();
+    match game.state() {
+        GameState::MatchDone(scores, 0) => assert_eq!(scores[0].1, 10),
+        x => panic!("unexpected state: {:?}", x),
+    }
+
+    game.next_match();
+    assert!(game.teams.iter().all(|t| t.score == 0));
+    assert!(game.legal_actions().len() > 0);
+}
@@ -0,0 +1,77 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use std::convert::TryFrom;
+use rand::SeedableRng;
+
+use crate::{
+    actions::{PlayerAction, CaptureAction},
+    table::PlayerTpos,
+    game::{Game, GameState},
+    bot::{Bot, RandomBot},
+    mcts::{MctsBot, MctsConfig},
+    card::Card,
+};
+
+use super::actions::mk_te;
+use super::{TestRng, Until, mk_game, play, bot_actions};
+
+fn test_config() -> MctsConfig {
+    MctsConfig {
+        iterations: 200,
+        time_limit: None,
+        ..MctsConfig::default()
+    }
+}
+
+/// Play random actions for nactions turns
+pub fn play_random(game: &mut Game<TestRng>, seed: u64, nactions: usize) {
+    let mut bot = RandomBot::new(TestRng::seed_from_u64(seed));
+    play(game, Until::Steps(nactions), bot_actions(&mut bot));
+}
+
+#[test]
+fn mcts_xeri() {
+    let game = mk_game("S5 H3", "D8 C3 H9");
+    let view = game.get_player_game_view(PlayerTpos(0));
+    let expected = PlayerAction::Capture(CaptureAction {
+        handcard: Card::try_from("D8").unwrap(),
+        tentries: vec![vec![mk_te("S5"), mk_te("H3")]],
+    });
+    let mut bot = MctsBot::new(TestRng::seed_from_u64(0), test_config());
+    assert_eq!(bot.choose_action(&view), Some(expected));
+}
+
+#[test]
+fn mcts_legal_actions() {
+    for seed in 0..5 {
+        let mut game = Game::new_2p(TestRng::seed_from_u64(seed));
+        play_random(&mut game, seed, 3 + seed as usize);
+        let tpos = match game.state() {
+            GameState::NextTurn(tpos) => *tpos,
+            x => panic!("unexpected state: {:?}", x),
+        };
+        let view = game.get_player_game_view(tpos);
+        let mut bot = MctsBot::new(TestRng::seed_from_u64(seed), test_config());
+        let act = bot.choose_action(&view).expect("mcts action");
+        assert!(view.legal_actions().contains(&act));
+        assert_eq!(bot.choose_action(&game.get_player_game_view(PlayerTpos((tpos.0 + 1) % 2))), None);
+    }
+}
+
+#[test]
+fn round_first_player() {
+    let mut game = Game::new_2p(TestRng::seed_from_u64(0));
+    let first = match game.state() {
+        GameState::NextTurn(tpos) => *tpos,
+        x => panic!("unexpected state: {:?}", x),
+    };
+    for i in 0..4 {
+        let view = game.get_player_game_view(first);
+        assert_eq!(view.round_first_player(), Some(first), "action {}", i);
+        play_random(&mut game, 0, 1);
+    }
+}
@@ -5,3 +5,102 @@
 //
 
 pub mod actions;
+pub mod movegen;
+pub mod matches;
+pub mod rules;
+pub mod replay;
+pub mod snapshot;
+pub mod bot;
+pub mod mcts;
+pub mod determinize;
+pub mod scenarios;
+pub mod invariants;
+pub mod turn_timer;
+
+use rand::{SeedableRng, seq::SliceRandom};
+
+use crate::{
+    repr::{TableRepr, DeckRepr, GameRepr},
+    actions::PlayerAction,
+    table::PlayerTpos,
+    game::{Game, GameState},
+    rules::RuleSet,
+    bot::Bot,
+};
+
+pub type TestRng = rand::rngs::StdRng;
+
+/// Single-player game with the given table and hand
+pub fn mk_game(table_s: &str, hand_s: &str) -> Game<TestRng> {
+    mk_game_with_rules(RuleSet::default(), table_s, hand_s)
+}
+
+pub fn mk_game_with_rules(rules: RuleSet, table_s: &str, hand_s: &str) -> Game<TestRng> {
+    let table = TableRepr::new(table_s).parse().unwrap();
+    let hand = DeckRepr::new(hand_s).parse().unwrap();
+    Game::new_1p_debug_with_rules(TestRng::seed_from_u64(0), rules, table, hand)
+}
+
+/// Game in the given position (see GameRepr)
+pub fn mk_position(s: &str) -> Game<TestRng> {
+    GameRepr::new(s).parse(RuleSet::default(), TestRng::seed_from_u64(0)).unwrap()
+}
+
+/// How far to play (see play())
+#[derive(Debug, Clone, Copy)]
+pub enum Until {
+    /// the given number of steps, or until the game is done
+    Steps(usize),
+    GameDone,
+    /// start new games until the match is done
+    MatchDone,
+}
+
+/// Play one step: an action chosen by choose() for the player whose turn it is, a new round, or
+/// (for Until::MatchDone) a new game. Returns false if there is nothing left to play.
+pub fn step<R, F>(game: &mut Game<R>, until: Until, choose: &mut F) -> bool
+where
+    R: rand::Rng + Clone,
+    F: FnMut(&Game<R>, PlayerTpos) -> PlayerAction,
+{
+    match game.state().clone() {
+        GameState::NextTurn(tpos) => {
+            let act = choose(game, tpos);
+            *game = game.apply_action(tpos, act).unwrap();
+        },
+        GameState::RoundDone => game.new_round(),
+        GameState::GameDone(_) => match until {
+            Until::MatchDone => game.next_game(),
+            _ => return false,
+        },
+        GameState::MatchDone(_, _) => return false,
+    }
+    true
+}
+
+/// Play steps until the given point. Returns the number of steps played.
+pub fn play<R, F>(game: &mut Game<R>, until: Until, mut choose: F) -> usize
+where
+    R: rand::Rng + Clone,
+    F: FnMut(&Game<R>, PlayerTpos) -> PlayerAction,
+{
+    let max = match until {
+        Until::Steps(n) => n,
+        _ => usize::MAX,
+    };
+    let mut nsteps = 0;
+    while nsteps < max && step(game, until, &mut choose) {
+        nsteps += 1;
+    }
+    nsteps
+}
+
+/// Choose random legal actions
+pub fn random_actions<'a, R: rand::Rng + Clone>(rng: &'a mut TestRng) -> impl FnMut(&Game<R>, PlayerTpos) -> PlayerAction + 'a {
+    move |game: &Game<R>, _| game.legal_actions().choose(rng).unwrap().clone()
+}
+
+/// Choose the actions of the given bot
+pub fn bot_actions<'a, R: rand::Rng + Clone>(bot: &'a mut dyn Bot) -> impl FnMut(&Game<R>, PlayerTpos) -> PlayerAction + 'a {
+    move |game: &Game<R>, tpos| bot.choose_action(&game.get_player_game_view(tpos)).expect("bot action")
+}
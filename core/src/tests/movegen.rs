@@ -0,0 +1,111 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use std::convert::TryFrom;
+use std::collections::HashSet;
+
+use crate::{
+    actions::{DeclAction, PlayerAction, CaptureAction},
+    table::PlayerTpos,
+    game::Game,
+    card::Card,
+};
+
+use super::actions::mk_te;
+use super::{TestRng, mk_game};
+
+// all generated actions are unique and can be applied
+fn check_actions(game: &Game<TestRng>, actions: &Vec<PlayerAction>) {
+    let set: HashSet<&PlayerAction> = actions.iter().collect();
+    assert_eq!(set.len(), actions.len());
+    for act in actions.iter() {
+        let res = game.apply_action(PlayerTpos(0), act.clone());
+        assert!(res.is_ok(), "action: {:?} res: {:?}", act, res);
+    }
+}
+
+#[test]
+fn movegen_t0() {
+    let game = mk_game("S4 HT H9", "D5 D9 C3 C9");
+    let actions = game.legal_actions();
+    check_actions(&game, &actions);
+
+    // D9 and C9 cannot be laid down because of H9
+    assert!(actions.contains(&PlayerAction::LayDown(Card::try_from("D5").unwrap())));
+    assert!(!actions.contains(&PlayerAction::LayDown(Card::try_from("D9").unwrap())));
+
+    let decl = PlayerAction::Declare(DeclAction {
+        tentries: vec![vec![mk_te("D5"), mk_te("S4")]],
+    });
+    assert!(actions.contains(&decl));
+
+    let capture = PlayerAction::Capture(CaptureAction {
+        handcard: Card::try_from("D9").unwrap(),
+        tentries: vec![vec![mk_te("H9")]],
+    });
+    assert!(actions.contains(&capture));
+
+    // The table has no group that sums to 5
+    let bad_capture = PlayerAction::Capture(CaptureAction {
+        handcard: Card::try_from("D5").unwrap(),
+        tentries: vec![vec![mk_te("S4")]],
+    });
+    assert!(!actions.contains(&bad_capture));
+}
+
+#[test]
+fn movegen_figures() {
+    let game = mk_game("SK HK", "DK D2");
+    let actions = game.legal_actions();
+    check_actions(&game, &actions);
+    // lay down D2, capture SK, capture HK
+    assert_eq!(actions.len(), 3);
+
+    let game = mk_game("SK HK CK", "DK D2");
+    let actions = game.legal_actions();
+    check_actions(&game, &actions);
+    // lay down D2, capture all three kings
+    assert_eq!(actions.len(), 2);
+}
+
+#[test]
+fn movegen_decl_continuation() {
+    let game = mk_game("S2 S5 H3", "D2 H2 C2 D5 H5 C8");
+    let act = PlayerAction::Declare(DeclAction {
+        tentries: vec![vec![mk_te("D2")], vec![mk_te("S2")]],
+    });
+    let game = game.apply_action(PlayerTpos(0), act).unwrap();
+
+    let actions = game.legal_actions();
+    check_actions(&game, &actions);
+    for act in actions.iter() {
+        match act {
+            PlayerAction::LayDown(_) => panic!("lay down is not allowed with a declaration on the table"),
+            PlayerAction::Declare(da) => assert!(da.has_decl()),
+            PlayerAction::Capture(_) => (),
+        }
+    }
+
+    // add H2 to the declaration of 2s (C2 backs it)
+    let decl = game.table.find_decl_from(PlayerTpos(0)).unwrap().clone();
+    let add = PlayerAction::Declare(DeclAction {
+        tentries: vec![vec![mk_te("H2")], vec![crate::TableEntry::Decl(decl)]],
+    });
+    assert!(actions.contains(&add));
+}
+
+#[test]
+fn movegen_not_my_turn() {
+    let game = mk_game("S4 HT H9", "D5 D9 C3 C9");
+    let view = game.get_player_game_view(PlayerTpos(0));
+    assert!(view.legal_actions().len() > 0);
+
+    let mut game2 = Game::new_2p(rand::thread_rng());
+    let view = game2.get_player_game_view(PlayerTpos(1));
+    assert_eq!(view.legal_actions().len(), 0);
+    game2 = game2.apply_action(PlayerTpos(0), game2.legal_actions()[0].clone()).unwrap();
+    assert!(game2.legal_actions().len() > 0);
+}
@@ -0,0 +1,88 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use rand::SeedableRng;
+
+use crate::{
+    game::{Game, ReplayLog, MAX_HISTORY_GAMES},
+    table::PlayerTpos,
+};
+
+use super::{TestRng, Until, play, random_actions};
+
+pub fn assert_same_game<R: rand::Rng + Clone>(g1: &Game<R>, g2: &Game<R>) {
+    assert_eq!(g1.table.entries, g2.table.entries);
+    assert_eq!(g1.main_deck.cards, g2.main_deck.cards);
+    for (p1, p2) in g1.players.iter().zip(g2.players.iter()) {
+        assert_eq!(p1.hand, p2.hand);
+    }
+    assert_eq!(format!("{:?}", g1.teams), format!("{:?}", g2.teams));
+    assert_eq!(format!("{:?}", g1.state), format!("{:?}", g2.state));
+    assert_eq!(g1.first_player, g2.first_player);
+    assert_eq!(g1.last_action, g2.last_action);
+    assert_eq!(g1.history().len(), g2.history().len());
+}
+
+#[test]
+fn replay_match() {
+    let mut rng = TestRng::seed_from_u64(0);
+    for nplayers in [2usize, 4].iter() {
+        let mut game = Game::new_with_rules(*nplayers, Default::default(), TestRng::seed_from_u64(1));
+        game.set_target_score(Some(61));
+        play(&mut game, Until::MatchDone, random_actions(&mut rng));
+        let finished = game.clone();
+        assert!(finished.history().len() > 1);
+
+        // start a second match, and stop in the middle of it
+        game.next_match();
+        play(&mut game, Until::Steps(10), random_actions(&mut rng));
+        // the history only has the current match
+        assert_eq!(game.history().len(), 1);
+
+        // the log survives a serialization round-trip
+        let log_s = serde_json::to_string(&game.replay_log()).unwrap();
+        let log: ReplayLog = serde_json::from_str(&log_s).unwrap();
+        let replayed = Game::replay(&log, TestRng::seed_from_u64(2)).unwrap();
+        assert_same_game(&game, &replayed);
+
+        // the previous match can be replayed separately
+        let last_match = Game::replay(game.last_match_log().unwrap(), TestRng::seed_from_u64(2)).unwrap();
+        assert_same_game(&finished, &last_match);
+    }
+}
+
+#[test]
+fn replay_capped_history() {
+    // without a target score, the match never ends: only the last games are kept
+    let mut rng = TestRng::seed_from_u64(0);
+    let mut game = Game::new_2p(TestRng::seed_from_u64(1));
+    for _ in 0..(MAX_HISTORY_GAMES + 3) {
+        play(&mut game, Until::GameDone, random_actions(&mut rng));
+        game.next_game();
+    }
+    assert_eq!(game.history().len(), MAX_HISTORY_GAMES);
+    assert_eq!(game.games_played(), MAX_HISTORY_GAMES + 4);
+
+    // the log is missing the first games of the match
+    let log = game.replay_log();
+    assert_eq!(log.dropped_games, 4);
+    assert!(Game::replay(&log, TestRng::seed_from_u64(2)).is_err());
+}
+
+#[test]
+fn replay_diverging_log() {
+    let mut rng = TestRng::seed_from_u64(0);
+    let mut game = Game::new_2p(TestRng::seed_from_u64(1));
+    play(&mut game, Until::Steps(4), random_actions(&mut rng));
+
+    let mut log = game.replay_log();
+    assert!(Game::replay(&log, TestRng::seed_from_u64(2)).is_ok());
+
+    // action played by the wrong player
+    let pa = &mut log.games[0].actions[1];
+    pa.player = PlayerTpos(pa.player.0 ^ 1);
+    assert!(Game::replay(&log, TestRng::seed_from_u64(2)).is_err());
+}
@@ -0,0 +1,151 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use std::convert::TryFrom;
+use rand::SeedableRng;
+
+use crate::{
+    actions::{PlayerAction, CaptureAction, DeclAction},
+    table::PlayerTpos,
+    game::{Game, GameState},
+    scoring::{Captures, NCARDS},
+    rules::RuleSet,
+    card::Card,
+    error::ActionError,
+};
+
+use super::actions::{mk_te, mk_table, mk_act};
+use super::{TestRng, Until, mk_game_with_rules, play, random_actions};
+
+#[test]
+fn rules_validate() {
+    let classic = RuleSet::classic();
+    assert!(classic.validate(1).is_ok());
+    assert!(classic.validate(2).is_ok());
+    assert!(classic.validate(4).is_ok());
+    // the deck can be dealt evenly to 3 players, but there are no 3-player games
+    assert!(classic.validate(0).is_err());
+    assert!(classic.validate(3).is_err());
+
+    let mut rules = RuleSet::classic();
+    rules.table_size = 0;
+    assert!(rules.validate(4).is_ok());
+    rules.table_size = 2;
+    assert!(rules.validate(2).is_ok());
+    assert!(rules.validate(4).is_err());
+    rules.table_size = 42;
+    assert!(rules.validate(2).is_err());
+}
+
+#[test]
+fn rules_forced_captures() {
+    let capture = PlayerAction::Capture(CaptureAction {
+        handcard: Card::try_from("D5").unwrap(),
+        tentries: vec![vec![mk_te("S5")]],
+    });
+
+    let game = mk_game_with_rules(RuleSet::classic(), "S5 H5 C2", "D5 D2");
+    let game = game.apply_action(PlayerTpos(0), capture.clone()).unwrap();
+    assert_eq!(game.table.entries, mk_table("C2").entries);
+
+    let mut rules = RuleSet::classic();
+    rules.forced_captures = false;
+    let game = mk_game_with_rules(rules, "S5 H5 C2", "D5 D2");
+    let game = game.apply_action(PlayerTpos(0), capture.clone()).unwrap();
+    assert_eq!(game.table.entries, mk_table("H5 C2").entries);
+}
+
+/// A player with a declaration has to keep a card to capture it
+#[test]
+fn rules_keep_backing_card() {
+    let capture = mk_act("C H4 ( S4 )");
+
+    // with forced captures, the declaration is captured as well
+    let game = mk_game_with_rules(RuleSet::classic(), "S4 0:[ C2 D2 ]:", "H4 D7");
+    let game = game.apply_action(PlayerTpos(0), capture.clone()).unwrap();
+    assert_eq!(game.table.entries, vec![]);
+
+    let mut rules = RuleSet::classic();
+    rules.forced_captures = false;
+    let game = mk_game_with_rules(rules.clone(), "S4 0:[ C2 D2 ]:", "H4 D7");
+    let res = game.apply_action(PlayerTpos(0), capture.clone());
+    assert_eq!(res.unwrap_err(), ActionError::LastBackingCard { value: 4 });
+    assert!(game.apply_action(PlayerTpos(0), mk_act("C H4 ( 0:[ C2 D2 ]: )")).is_ok());
+    assert!(game.apply_action(PlayerTpos(0), mk_act("C H4 ( S4 ) ( 0:[ C2 D2 ]: )")).is_ok());
+    // the capture is not a legal action either
+    assert!(!game.legal_actions().contains(&capture));
+
+    let game = mk_game_with_rules(rules, "S4 0:[ C2 D2 ]:", "H4 C4 D7");
+    let game = game.apply_action(PlayerTpos(0), capture).unwrap();
+    assert_eq!(game.table.entries, mk_table("0:[ C2 D2 ]:").entries);
+}
+
+#[test]
+fn rules_forced_declarations() {
+    let decl = PlayerAction::Declare(DeclAction {
+        tentries: vec![vec![mk_te("D5"), mk_te("S4")]],
+    });
+
+    let game = mk_game_with_rules(RuleSet::classic(), "S4 H9", "D5 D9");
+    let game = game.apply_action(PlayerTpos(0), decl.clone()).unwrap();
+    assert_eq!(game.table.entries, mk_table("0:[ D5 S4 ][ H9 ]:").entries);
+
+    let mut rules = RuleSet::classic();
+    rules.forced_declarations = false;
+    let game = mk_game_with_rules(rules, "S4 H9", "D5 D9");
+    let game = game.apply_action(PlayerTpos(0), decl.clone()).unwrap();
+    assert_eq!(game.table.entries, mk_table("H9 0:[ D5 S4 ]:").entries);
+}
+
+#[test]
+fn rules_scoring() {
+    let mut captures = Captures::new();
+    captures.add_cards(vec![Card::try_from("DK").unwrap(), Card::try_from("SK").unwrap()], true);
+    let all = crate::deck::Deck::full_52();
+    let ncards = NCARDS / 2 + 1;
+    let rest: Vec<Card> = all.cards.iter()
+        .filter(|c| c.rank.0 > 2 && c.rank.0 < 10)
+        .take(ncards - 2)
+        .cloned()
+        .collect();
+    captures.add_cards(rest, false);
+
+    let classic = captures.score(&RuleSet::classic());
+    assert_eq!(classic.nr_cards, ncards);
+    assert_eq!(classic.score, 10 + 4);
+
+    let mut rules = RuleSet::classic();
+    rules.xeri_figure_double = true;
+    rules.majority_bonus = false;
+    let sheet = captures.score(&rules);
+    assert_eq!(sheet.xeres_score, 20);
+    assert_eq!(sheet.cards_score, 0);
+    assert_eq!(sheet.score, 20);
+
+    rules.xeri_score = 0;
+    assert_eq!(captures.score(&rules).score, 0);
+}
+
+// play random games with an empty initial table (the last round has fewer cards)
+#[test]
+fn rules_table_size() {
+    let mut rng = TestRng::seed_from_u64(0);
+    let mut rules = RuleSet::classic();
+    rules.table_size = 0;
+    for nplayers in [2usize, 4].iter() {
+        let game_rng = TestRng::seed_from_u64(*nplayers as u64);
+        let mut game = Game::new_with_rules(*nplayers, rules.clone(), game_rng);
+        assert_eq!(game.table.nentries(), 0);
+        play(&mut game, Until::GameDone, random_actions(&mut rng));
+        match game.state() {
+            GameState::GameDone(scores) | GameState::MatchDone(scores, _) => {
+                let ncards: usize = scores.iter().map(|(ss, _)| ss.nr_cards).sum();
+                assert_eq!(ncards, NCARDS);
+            },
+            x => panic!("unexpected state: {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use rand::SeedableRng;
+
+use crate::{
+    repr::GameRepr,
+    table::PlayerTpos,
+    game::{Game, GameState},
+    rules::RuleSet,
+    bot::RandomBot,
+};
+
+use super::actions::{mk_act, mk_table, mk_te};
+use super::{TestRng, Until, mk_position, step, bot_actions};
+
+fn assert_same_position(g1: &Game<TestRng>, g2: &Game<TestRng>) {
+    assert_eq!(g1.table.entries, g2.table.entries);
+    assert_eq!(g1.main_deck.cards, g2.main_deck.cards);
+    for (p1, p2) in g1.players.iter().zip(g2.players.iter()) {
+        assert_eq!(p1.hand, p2.hand);
+    }
+    for (t1, t2) in g1.teams.iter().zip(g2.teams.iter()) {
+        assert_eq!(t1.captures.cards(), t2.captures.cards());
+        assert_eq!(t1.captures.xeres().len(), t2.captures.xeres().len());
+        assert_eq!(t1.score, t2.score);
+    }
+    assert_eq!(format!("{:?}", g1.state), format!("{:?}", g2.state));
+    assert_eq!(g1.first_player, g2.first_player);
+    assert_eq!(g1.last_team_captured, g2.last_team_captured);
+}
+
+#[test]
+fn game_repr_roundtrip() {
+    for nplayers in [2usize, 4].iter() {
+        let mut game = Game::new_with_rules(*nplayers, RuleSet::default(), TestRng::seed_from_u64(0));
+        let mut bot = RandomBot::new(TestRng::seed_from_u64(0));
+        let mut choose = bot_actions(&mut bot);
+        loop {
+            // finished games cannot be represented
+            if let GameState::GameDone(_) = game.state() {
+                break;
+            }
+
+            let repr = GameRepr::from_game(&game);
+            let parsed = repr.parse(RuleSet::default(), TestRng::seed_from_u64(0)).unwrap();
+            assert_same_position(&game, &parsed);
+            assert_eq!(GameRepr::from_game(&parsed), repr);
+
+            assert!(step(&mut game, Until::GameDone, &mut choose));
+        }
+    }
+}
+
+#[test]
+fn game_repr_errors() {
+    let rules = RuleSet::default();
+    let parse = |s: &str| GameRepr::new(s).parse(rules.clone(), TestRng::seed_from_u64(0));
+
+    assert!(parse("D5 | S7 / S5 H2 / - / P0 P0 T1 / - | - / 0 0").is_ok());
+    // wrong number of fields
+    assert!(parse("D5 | S7 / S5 H2 / P0 P0 T1 / - | - / 0 0").is_err());
+    assert!(parse("D5 | S7 / S5 H2").is_err());
+    assert!(parse("D5 | S7 / S5 H2 / - / P0 P0 T1 / - | - / 0 0 / 0").is_err());
+    // three players
+    assert!(parse("D5 | S7 | H7 / S5 H2 / - / P0 P0 T1 / - | - / 0 0").is_err());
+    // duplicate card
+    assert!(parse("D5 | S7 / S5 D5 / - / P0 P0 T1 / - | - / 0 0").is_err());
+    assert!(parse("D5 | S7 / S5 H2 / - / P0 P0 T1 / S7* | - / 0 0").is_err());
+    // invalid turn, first player, and team
+    assert!(parse("D5 | S7 / S5 H2 / - / P2 P0 T1 / - | - / 0 0").is_err());
+    assert!(parse("D5 | - / S5 H2 / - / P1 P0 T1 / - | - / 0 0").is_err());
+    assert!(parse("D5 | S7 / S5 H2 / - / P0 X0 T1 / - | - / 0 0").is_err());
+    assert!(parse("D5 | S7 / S5 H2 / - / P0 P0 T2 / - | - / 0 0").is_err());
+    // round cannot be done while players have cards
+    assert!(parse("D5 | S7 / S5 H2 / HA / R P0 T1 / - | - / 0 0").is_err());
+    // missing scores
+    assert!(parse("D5 | S7 / S5 H2 / - / P0 P0 T1 / - | - / 0").is_err());
+}
+
+#[test]
+fn game_repr_defaults() {
+    let game = mk_position("D5 S4 | H2 CK / HT S5 / HA H3");
+    assert_eq!(GameRepr::from_game(&game).0, "♠4 ♦5 | ♣K ♥2 / ♥T ♠5 / ♥A ♥3 / P0 P0 T0 / - | - / 0 0");
+    let game = mk_position("D5 S4 | H2 CK / HT S5 / HA H3 / P1 P1 T0");
+    assert_eq!(GameRepr::from_game(&game).0, "♠4 ♦5 | ♣K ♥2 / ♥T ♠5 / ♥A ♥3 / P1 P1 T0 / - | - / 0 0");
+}
+
+/// The last team that captured gets the cards left on the table at the end of the game
+#[test]
+fn scenario_last_capture() {
+    let mut game = mk_position("D5 | S7 / S5 H2 / - / P0 P0 T1 / HK | SK* / 0 10");
+    game.apply_action_mut(PlayerTpos(0), mk_act("C D5 ( S5 )")).unwrap();
+    assert_eq!(game.last_team_captured, 0);
+    game.apply_action_mut(PlayerTpos(1), mk_act("L S7")).unwrap();
+    match game.state() {
+        GameState::GameDone(scores) => {
+            // HK D5 S5 H2 S7
+            assert_eq!(scores[0].0.nr_cards, 5);
+            assert_eq!(scores[0].0.nr_xeres, 0);
+            assert_eq!(scores[1].0.nr_cards, 1);
+            assert_eq!(scores[1].0.nr_xeres, 1);
+            assert_eq!(scores[1].1, 20);
+        },
+        x => panic!("unexpected state: {:?}", x),
+    }
+}
+
+/// Clearing the table is a xeri, and the round ends when every player has played their cards
+#[test]
+fn scenario_end_of_round() {
+    let mut game = mk_position("D5 | S7 / S5 / HA H2 / P0 P1 T1 / - | - / 0 0");
+    game.apply_action_mut(PlayerTpos(0), mk_act("C D5 ( S5 )")).unwrap();
+    assert!(game.last_action.as_ref().unwrap().xeri);
+    game.apply_action_mut(PlayerTpos(1), mk_act("L S7")).unwrap();
+    assert!(game.state().is_round_done());
+    assert_eq!(GameRepr::from_game(&game).0, "- | - / ♠7 / ♥A ♥2 / R P1 T0 / ♠5 ♦5* | - / 0 0");
+
+    // the first player of the game starts every round
+    game.new_round();
+    match game.state() {
+        GameState::NextTurn(tpos) => assert_eq!(*tpos, PlayerTpos(1)),
+        x => panic!("unexpected state: {:?}", x),
+    }
+    assert_eq!(game.players[0].hand.len(), 1);
+    assert_eq!(game.table.entries, mk_table("S7").entries);
+}
+
+/// Spectators see no hands, unless they asked for them and the game is done
+#[test]
+fn scenario_spectator_view() {
+    let mut game = mk_position("D5 | S7 / S5 H2 / - / P0 P0 T1 / HK | SK* / 0 10");
+    let sview = game.get_spectator_game_view(true);
+    assert!(sview.revealed_hands.is_none());
+    assert_eq!(sview.player_decks_sz, vec![1, 1]);
+    assert_eq!(sview.scores, vec![0, 10]);
+
+    game.apply_action_mut(PlayerTpos(0), mk_act("C D5 ( S5 )")).unwrap();
+    game.apply_action_mut(PlayerTpos(1), mk_act("L S7")).unwrap();
+    assert!(game.get_spectator_game_view(false).revealed_hands.is_none());
+    let hands = game.get_spectator_game_view(true).revealed_hands.unwrap();
+    assert_eq!(hands[0].cards, vec![mk_te("D5").unwrap_card()]);
+    assert_eq!(hands[1].cards, vec![mk_te("S7").unwrap_card()]);
+}
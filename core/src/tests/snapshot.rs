@@ -0,0 +1,37 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use rand::SeedableRng;
+
+use crate::game::Game;
+
+use super::replay::assert_same_game;
+use super::{TestRng, Until, play, random_actions};
+
+type Rng = rand_pcg::Pcg64;
+
+#[test]
+fn snapshot_roundtrip() {
+    let mut rng = TestRng::seed_from_u64(0);
+    let mut game = Game::new_4p(Rng::seed_from_u64(1));
+    game.set_target_score(Some(200));
+    play(&mut game, Until::Steps(30), random_actions(&mut rng));
+
+    let snapshot = serde_json::to_string(&game).unwrap();
+    let mut restored: Game<Rng> = serde_json::from_str(&snapshot).unwrap();
+    assert_same_game(&game, &restored);
+    assert_eq!(restored.target_score(), game.target_score());
+    assert_eq!(restored.rules(), game.rules());
+
+    // RNG state is restored as well: both games should deal the same next game
+    let mut rng1 = TestRng::seed_from_u64(2);
+    let mut rng2 = rng1.clone();
+    play(&mut game, Until::GameDone, random_actions(&mut rng1));
+    play(&mut restored, Until::GameDone, random_actions(&mut rng2));
+    game.next_game();
+    restored.next_game();
+    assert_same_game(&game, &restored);
+}
@@ -0,0 +1,105 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use rand::SeedableRng;
+
+use crate::{
+    table::PlayerTpos,
+    game::{Game, GameState},
+    error::ActionError,
+    turn_timer::{TimeoutPolicy, TurnTimer},
+};
+
+use super::actions::mk_act;
+use super::replay::assert_same_game;
+use super::{TestRng, Until, mk_position, step, random_actions};
+
+#[test]
+fn turn_timer_validate() {
+    let timer = |secs, policy| TurnTimer { secs: secs, policy: policy };
+    assert!(timer(30, TimeoutPolicy::AutoPlay).validate(1).is_ok());
+    assert!(timer(0, TimeoutPolicy::AutoPlay).validate(2).is_err());
+    assert!(timer(4, TimeoutPolicy::AutoPlay).validate(2).is_err());
+    assert!(timer(3600, TimeoutPolicy::AutoPlay).validate(2).is_ok());
+    assert!(timer(u64::MAX, TimeoutPolicy::AutoPlay).validate(2).is_err());
+    assert!(timer(30, TimeoutPolicy::Forfeit).validate(2).is_ok());
+    assert!(timer(30, TimeoutPolicy::Forfeit).validate(1).is_err());
+}
+
+#[test]
+fn turn_timer_auto_action() {
+    // lowest card
+    let game = mk_position("D9 H3 | S7 C8 / S5 H2 / - / P0 P0 T1 / - | - / 0 0");
+    assert_eq!(game.auto_action(), Some(mk_act("L H3")));
+
+    // D5 cannot be laid down: capture
+    let game = mk_position("D5 | S7 / S5 H2 / - / P0 P0 T1 / - | - / 0 0");
+    assert_eq!(game.auto_action(), Some(mk_act("C D5 ( S5 )")));
+}
+
+#[test]
+fn turn_timer_policies() {
+    let mut game = mk_position("D9 H3 | S7 C8 / S5 H2 / - / P0 P0 T1 / - | - / 8 0");
+    assert_eq!(game.turn_timeout(PlayerTpos(1), TimeoutPolicy::AutoPlay), Err(ActionError::NotYourTurn));
+
+    game.turn_timeout(PlayerTpos(0), TimeoutPolicy::Skip { penalty: 5 }).unwrap();
+    assert_eq!(game.teams[0].score, 3);
+    assert_eq!(game.last_action.as_ref().unwrap().action, mk_act("L H3"));
+    assert_eq!(game.get_player_game_view(PlayerTpos(1)).last_timeout.unwrap().player, PlayerTpos(0));
+    game.check_invariants().unwrap();
+
+    // the penalty cannot make the score negative
+    game.turn_timeout(PlayerTpos(1), TimeoutPolicy::Skip { penalty: 5 }).unwrap();
+    assert_eq!(game.teams[1].score, 0);
+
+    game.apply_action_mut(PlayerTpos(0), mk_act("L D9")).unwrap();
+    assert!(game.get_player_game_view(PlayerTpos(1)).last_timeout.is_none());
+
+    game.turn_timeout(PlayerTpos(1), TimeoutPolicy::Forfeit).unwrap();
+    match game.state() {
+        GameState::MatchDone(scores, 0) => assert_eq!(scores.iter().map(|(_, s)| *s).collect::<Vec<_>>(), vec![3, 0]),
+        x => panic!("unexpected state: {:?}", x),
+    }
+    game.check_invariants().unwrap();
+    assert!(game.get_spectator_game_view(false).last_timeout.is_some());
+}
+
+#[test]
+fn turn_timer_replay() {
+    let mut rng = TestRng::seed_from_u64(0);
+    for nplayers in [2usize, 4].iter() {
+        let mut game = Game::new_with_rules(*nplayers, Default::default(), TestRng::seed_from_u64(1));
+        game.set_target_score(Some(61));
+        let mut choose = random_actions(&mut rng);
+        let mut turns = 0;
+        loop {
+            let timeout = match game.state().clone() {
+                // forfeit the match that is in progress after 300 turns
+                GameState::NextTurn(tpos) if turns >= 300 => Some((tpos, TimeoutPolicy::Forfeit)),
+                GameState::NextTurn(tpos) if turns % 7 == 3 => Some((tpos, TimeoutPolicy::AutoPlay)),
+                GameState::NextTurn(tpos) if turns % 7 == 5 => Some((tpos, TimeoutPolicy::Skip { penalty: 2 })),
+                _ => None,
+            };
+            if let Some((tpos, policy)) = timeout {
+                game.turn_timeout(tpos, policy).unwrap();
+            } else if !step(&mut game, Until::MatchDone, &mut choose) {
+                if turns >= 300 {
+                    break;
+                }
+                game.next_match();
+            }
+            game.check_invariants().unwrap();
+            turns += 1;
+        }
+
+        let last = game.history().last().unwrap();
+        assert_eq!(last.timeouts.last().unwrap().policy, TimeoutPolicy::Forfeit);
+
+        let replayed = Game::replay(&game.replay_log(), TestRng::seed_from_u64(2)).unwrap();
+        assert_same_game(&game, &replayed);
+        assert_eq!(last.timeouts, replayed.history().last().unwrap().timeouts);
+    }
+}
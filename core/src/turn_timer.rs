@@ -0,0 +1,143 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+// Turn timer: what happens when a player does not play in time
+//
+// The clock itself is kept by the server. When it expires, the server calls Game::turn_timeout()
+// with the policy of the game. Timeouts are recorded in the GameRecord, so that replays apply the
+// same penalties.
+
+use serde::{Deserialize, Serialize};
+
+use super::card::CardSet;
+use super::table::PlayerTpos;
+use super::actions::PlayerAction;
+use super::game::{Game, GameState};
+use super::scoring::{Captures, ScoreSheet};
+use super::error::ActionError;
+use super::deck::Deck;
+
+/// Shortest allowed time per turn (seconds)
+pub const MIN_TURN_SECS: u64 = 5;
+/// Longest allowed time per turn (seconds)
+pub const MAX_TURN_SECS: u64 = 3600;
+
+/// What happens when a player runs out of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeoutPolicy {
+    /// Play for the player (see Game::auto_action())
+    AutoPlay,
+    /// The player loses their turn, and their team loses the given points from its match score.
+    /// Since every card has to be played, the turn is played as with AutoPlay.
+    Skip { penalty: usize },
+    /// The team of the player forfeits the match (only for games with two teams)
+    Forfeit,
+}
+
+/// Turn timer of a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnTimer {
+    /// Time each player has to play
+    pub secs: u64,
+    pub policy: TimeoutPolicy,
+}
+
+/// A turn that timed out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnTimeout {
+    pub player: PlayerTpos,
+    /// Number of actions in the game before the timeout
+    pub nactions: usize,
+    pub policy: TimeoutPolicy,
+}
+
+impl TurnTimer {
+    /// Check that the timer can be used for a game with the given number of players
+    pub fn validate(&self, nplayers: usize) -> Result<(), String> {
+        if self.secs < MIN_TURN_SECS || self.secs > MAX_TURN_SECS {
+            return Err(format!("Invalid turn timer: {} seconds (allowed: {}-{})", self.secs, MIN_TURN_SECS, MAX_TURN_SECS));
+        }
+
+        if self.policy == TimeoutPolicy::Forfeit && nplayers < 2 {
+            return Err("Forfeit policy requires at least two players".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: rand::Rng + Clone> Game<R> {
+    /// Action for a player that ran out of time: lay down the lowest card, or, if that is not
+    /// possible (e.g., a capture is forced), capture with the lowest card.
+    pub fn auto_action(&self) -> Option<PlayerAction> {
+        let actions = self.legal_actions();
+        let laydown = actions.iter()
+            .filter(|a| matches!(a, PlayerAction::LayDown(_)))
+            .min_by_key(|a| a.hand_card().rank.0);
+        let capture = actions.iter()
+            .filter(|a| matches!(a, PlayerAction::Capture(_)))
+            .min_by_key(|a| a.hand_card().rank.0);
+        laydown.or(capture).or(actions.first()).cloned()
+    }
+
+    /// The player whose turn it is ran out of time: apply the policy
+    pub fn turn_timeout(&mut self, tpos: PlayerTpos, policy: TimeoutPolicy) -> Result<(), ActionError> {
+        match self.state {
+            GameState::NextTurn(curr_tpos) if curr_tpos == tpos => (),
+            GameState::NextTurn(_) => return Err(ActionError::NotYourTurn),
+            _ => return Err(ActionError::GameNotInProgress),
+        };
+
+        let nactions = self.history.last().expect("current game record").actions.len();
+        self.do_turn_timeout(TurnTimeout { player: tpos, nactions: nactions, policy: policy });
+        if policy != TimeoutPolicy::Forfeit {
+            let action = self.auto_action().expect("a legal action");
+            self.apply_action_mut(tpos, action)?;
+        }
+        Ok(())
+    }
+
+    /// Record a timeout and apply its penalty. The action (if any) is applied by the caller.
+    pub(crate) fn do_turn_timeout(&mut self, timeout: TurnTimeout) {
+        let idx = self.team_idx(timeout.player);
+        match timeout.policy {
+            TimeoutPolicy::AutoPlay => (),
+            TimeoutPolicy::Skip { penalty } => {
+                let team = &mut self.teams[idx];
+                team.score = team.score.saturating_sub(penalty);
+            },
+            TimeoutPolicy::Forfeit => self.forfeit(idx),
+        }
+        self.history.last_mut().expect("current game record").timeouts.push(timeout);
+    }
+
+    /// A team forfeits: the cards of the game are discarded, and the other team wins the match
+    fn forfeit(&mut self, team_idx: usize) {
+        assert_eq!(self.teams.len(), 2);
+        self.main_deck = Deck::empty();
+        self.table.entries.clear();
+        for p in self.players.iter_mut() {
+            p.hand = CardSet::empty();
+        }
+        let mut scores = vec![];
+        for team in self.teams.iter_mut() {
+            team.captures = Captures::new();
+            scores.push((ScoreSheet::new(), team.score));
+        }
+        self.state = GameState::MatchDone(scores, 1 - team_idx);
+    }
+
+    /// The timeout of the last turn, if it timed out
+    pub(crate) fn last_timeout(&self) -> Option<TurnTimeout> {
+        let record = self.history.last()?;
+        let timeout = record.timeouts.last()?;
+        // the timeout is followed by the action played for the player (except when forfeiting)
+        if timeout.nactions + 1 >= record.actions.len() {
+            Some(timeout.clone())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use seed::{*, prelude::*};
+use wasm_bindgen::JsValue;
+
+use core::srvcli::{LobbyInfo, PlayerId, CHAT_MAX_LEN};
+
+use crate::Msg;
+
+/// Chat panel, shared by the lobby and the game pages
+
+#[derive(Debug, Clone)]
+pub enum ChatMsg {
+    SetInput(String),
+    KeyDown(String),
+    Send,
+}
+
+#[derive(Debug, Clone)]
+struct ChatLine {
+    from: PlayerId,
+    text: String,
+    ts: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChatSt {
+    lines: Vec<ChatLine>,
+    input: String,
+}
+
+/// hh:mm of a timestamp (seconds since the UNIX epoch), in local time
+fn fmt_ts(ts: u64) -> String {
+    let date = js_sys::Date::new(&JsValue::from_f64((ts as f64) * 1000.0));
+    format!("{:02}:{:02}", date.get_hours(), date.get_minutes())
+}
+
+impl ChatSt {
+    /// Add a message we got from the server
+    pub fn add(&mut self, from: PlayerId, text: String, ts: u64) {
+        self.lines.push(ChatLine { from: from, text: text, ts: ts });
+    }
+
+    /// Update the chat state. Returns a message to send to the server, if any.
+    pub fn update_state(&mut self, msg: &ChatMsg) -> Option<String> {
+        match msg {
+            ChatMsg::SetInput(x) => {
+                self.input = x.clone();
+                None
+            },
+            ChatMsg::KeyDown(key) if key != "Enter" => None,
+            ChatMsg::KeyDown(_) | ChatMsg::Send => {
+                let text = std::mem::take(&mut self.input);
+                if text.trim().len() == 0 {
+                    None
+                } else {
+                    Some(text)
+                }
+            },
+        }
+    }
+
+    pub fn view(&self, lobby_info: &LobbyInfo) -> Node<Msg> {
+        let mut lines = div![attrs!{At::Class => "chat-lines"}];
+        for line in self.lines.iter() {
+            let name = lobby_info.get_player(line.from).map_or("?", |p| &p.name);
+            lines.add_child(p![
+                span![format!("[{}] ", fmt_ts(line.ts))],
+                b![format!("{}: ", name)],
+                span![line.text],
+            ]);
+        }
+
+        div![
+            attrs!{At::Class => "container chat"},
+            p!["Chat"],
+            lines,
+            input![
+                input_ev(Ev::Input, |x| Msg::Chat(ChatMsg::SetInput(x))),
+                keyboard_ev(Ev::KeyDown, |ev| Msg::Chat(ChatMsg::KeyDown(ev.key()))),
+                attrs!{
+                    At::Value => self.input,
+                    At::MaxLength => CHAT_MAX_LEN,
+                },
+            ],
+            button![simple_ev(Ev::Click, Msg::Chat(ChatMsg::Send)), "Send"],
+        ]
+    }
+}
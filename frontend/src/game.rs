@@ -13,19 +13,25 @@ use core::{
 
 use crate::{
     Msg, Model,
-    to_elem::{iter_to_elem, tpos_char, ToElem, },
+    to_elem::{iter_to_elem, tpos_char, turn_clock, timeout_msg, ToElem, },
     ws::WsEvent,
+    chat::{ChatSt, ChatMsg},
+    lobby::session_token_key,
 };
 
 /// Game state
 
 #[derive(Debug)]
 pub struct GameSt {
+    game_id: String,
     view: core::PlayerGameView,
     phase: GamePhase,
     lobby_info: LobbyInfo,
     wsocket: web_sys::WebSocket,
     tmp_error_msg: String,
+    connection_lost: bool,
+    closed: Option<String>, // the server closed the game (reason)
+    chat: ChatSt,
 }
 
 #[derive(Clone,Debug)]
@@ -56,6 +62,7 @@ enum GamePhase {
     PlayersDisconnected(Vec<PlayerId>),
     RoundDone,
     GameDone(Vec<(core::ScoreSheet, usize)>),
+    MatchDone(Vec<(core::ScoreSheet, usize)>, usize),
 }
 
 impl GamePhase {
@@ -70,7 +77,7 @@ impl GamePhase {
             MyTurn(DeclaringWith(x,_)) => Some(*x),
             MyTurn(CapturingWith(x,_)) => Some(*x),
             MyTurn(ActionIssued(_)) => None,
-            PlayersDisconnected(_) | RoundDone | GameDone(_) => None,
+            PlayersDisconnected(_) | RoundDone | GameDone(_) | MatchDone(_, _) => None,
         }
     }
 
@@ -86,7 +93,7 @@ impl GamePhase {
             MyTurn(DeclaringWith(_, None)) => false,
             MyTurn(DeclaringWith(_, Some(db))) => db.has_tentry(tentry),
             MyTurn(CapturingWith(_, cb)) => cb.has_tentry(tentry),
-            PlayersDisconnected(_) | RoundDone | GameDone(_) => false,
+            PlayersDisconnected(_) | RoundDone | GameDone(_) | MatchDone(_, _) => false,
         }
     }
 }
@@ -118,6 +125,10 @@ impl From<(&LobbyInfo, &core::PlayerGameView)> for GamePhase {
             core::GameState::GameDone(ref sheets)  => {
                 GamePhase::GameDone(sheets.clone())
             },
+
+            core::GameState::MatchDone(ref sheets, winner)  => {
+                GamePhase::MatchDone(sheets.clone(), winner)
+            },
         }
     }
 }
@@ -126,14 +137,24 @@ impl From<(&LobbyInfo, &core::PlayerGameView)> for GamePhase {
 
 impl GameSt {
 
-    pub fn new(wsocket: web_sys::WebSocket, lobby_info: LobbyInfo, pview: core::PlayerGameView) -> GameSt {
+    pub fn new(
+        wsocket: web_sys::WebSocket,
+        game_id: String,
+        lobby_info: LobbyInfo,
+        pview: core::PlayerGameView,
+        chat: ChatSt,
+    ) -> GameSt {
         let phase: GamePhase = (&lobby_info, &pview).into();
         GameSt {
+            game_id: game_id,
             view: pview,
             phase: phase,
             lobby_info: lobby_info,
             wsocket: wsocket,
             tmp_error_msg: "".into(),
+            connection_lost: false,
+            closed: None,
+            chat: chat,
         }
     }
 
@@ -151,7 +172,7 @@ impl GameSt {
         }
     }
 
-    fn invalid_action(&mut self, err: String) {
+    fn invalid_action(&mut self, err: core::ActionError) {
         assert!(self.myturn());
         // reset phase
         let user_msg = div![
@@ -210,7 +231,7 @@ impl GameSt {
                         Some(MyTurn(CardSelected(*x)))
                     },
 
-                    PlayersDisconnected(_) | RoundDone | GameDone(_) => None,
+                    PlayersDisconnected(_) | RoundDone | GameDone(_) | MatchDone(_, _) => None,
                 };
 
                 if let Some(x) = new_phase {
@@ -231,6 +252,7 @@ impl GameSt {
                     RoundDone => None,
                     PlayersDisconnected(_) => None,
                     GameDone(_) => None,
+                    MatchDone(_, _) => None,
 
                     MyTurn(DeclaringWith(_cidx, Some(db))) => {
                         if is_selected {
@@ -238,8 +260,8 @@ impl GameSt {
                             db.reset()
                         } else {
                             let res = db.add_table_entry(te);
-                            if let Err(errstr) = res {
-                                self.tmp_error_msg = errstr;
+                            if let Err(err) = res {
+                                self.tmp_error_msg = err.to_string();
                             }
                         }
                         None
@@ -251,8 +273,8 @@ impl GameSt {
                             cb.reset()
                         } else {
                             let res = cb.add_table_entry(te);
-                            if let Err(errstr) = res {
-                                self.tmp_error_msg = errstr;
+                            if let Err(err) = res {
+                                self.tmp_error_msg = err.to_string();
                             }
                         }
                         None
@@ -268,14 +290,14 @@ impl GameSt {
 
             // User selected a card to lay down
             InGameMsg::LayDown(cidx) => {
-                let cc = self.view.own_hand.cards[*cidx].clone();
+                let cc = self.view.get_hand_card(*cidx);
                 let action = core::PlayerAction::LayDown(cc);
                 self.issue_action_validate(action);
                 return None;
             },
 
             InGameMsg::CaptureWith(cidx) => {
-                let card = self.view.own_hand.cards[*cidx].clone();
+                let card = self.view.get_hand_card(*cidx);
                 let bld = core::CaptureActionBuilder::new(&card);
                 self.phase = GamePhase::MyTurn(TurnProgress::CapturingWith(*cidx, bld));
                 return None;
@@ -292,9 +314,9 @@ impl GameSt {
                     _ => unimplemented!(),
                 };
 
-                let card = &self.view.own_hand.cards[cidx];
+                let card = &self.view.get_hand_card(cidx);
                 let valid_sum = self.view.iter_hand_cards()
-                    .find(|c| *c != card && c.rank.0 == *sum)
+                    .find(|c| c != card && c.rank.0 == *sum)
                     .is_some();
 
                 if !valid_sum {
@@ -303,7 +325,7 @@ impl GameSt {
                 }
 
                 match core::DeclActionBuilder::new(card, *sum) {
-                    Err(x) => self.tmp_error_msg = x,
+                    Err(x) => self.tmp_error_msg = x.to_string(),
                     Ok(db) => self.phase = GamePhase::MyTurn(TurnProgress::DeclaringWith(cidx, Some(db))),
                 }
 
@@ -359,7 +381,33 @@ impl GameSt {
                 return None;
             }
 
+            // NB: we only get these when registering, and we are already past that
+            ServerMsg::SessionToken(_) | ServerMsg::QuickMatchWaiting { .. } | ServerMsg::QuickMatch(_) => {
+                return None;
+            }
+
+            ServerMsg::Chat { from, text, ts } => {
+                self.chat.add(from, text, ts);
+                return None;
+            }
+
+            // only for spectators
+            ServerMsg::SpectatorUpdate(_) => {
+                error!("Got unexpected spectator update");
+                return None;
+            }
+
+        }
+    }
+
+    pub fn handle_chat_msg(&mut self, msg: &ChatMsg) -> Option<Model> {
+        let text = self.chat.update_state(msg)?;
+        let req = serde_json::to_string(&ClientMsg::Chat(text)).unwrap();
+        if let Err(_x) = self.wsocket.send_with_str(&req) {
+            error!("Failed to send data to server");
+            self.connection_lost = true;
         }
+        None
     }
 
     pub fn handle_ws_event(&mut self, ev: &WsEvent, _orders: &mut impl Orders<Msg>) -> Option<Model> {
@@ -373,9 +421,24 @@ impl GameSt {
                 self.handle_server_message(srv_msg)
             },
 
-            // TODO: proper error handling
-            // (WsEvent::WsClose(_), _) => _,
-            _ => unimplemented!(),
+            // The game is gone (e.g., it timed out), so the session token is no longer good
+            WsEvent::WsClose(ce) if ce.code() == 4444 => {
+                if let Some(storage) = seed::storage::get_storage() {
+                    storage.remove_item(&session_token_key(&self.game_id)).unwrap_or(());
+                }
+                self.closed = Some(ce.reason());
+                None
+            },
+
+            // The session token is kept in local storage, so reloading the page will get us back
+            // into the game.
+            WsEvent::WsClose(_) | WsEvent::WsError(_) => {
+                log!(format!("Game {}: lost connection to server", self.game_id));
+                self.connection_lost = true;
+                None
+            },
+
+            WsEvent::WsConnected(_) => None,
         };
 
         None
@@ -474,7 +537,7 @@ impl GameSt {
         let selected_card_idx = self.phase.get_hand_selected_card();
         for (cidx, card) in self.view.enum_hand_cards() {
             let selected: bool = selected_card_idx.map_or(false, |sidx| sidx == cidx);
-            let mut c_div = self.mk_hand_card_div(card, selected);
+            let mut c_div = self.mk_hand_card_div(&card, selected);
             c_div.add_listener(
                 simple_ev(Ev::Click, Msg::InGame(InGameMsg::ClickHandCard(cidx)))
             );
@@ -492,7 +555,7 @@ impl GameSt {
         div![
             attrs!{At::Class => "container"},
             //p!["Hand"],
-            p![format!("Hand (total: {} -- you might have to scroll down)", self.view.own_hand.ncards())],
+            p![format!("Hand (total: {} -- you might have to scroll down)", self.view.own_hand.len())],
             div![hand_attrs, cards],
         ]
     }
@@ -505,9 +568,9 @@ impl GameSt {
         }
 
         div![
-            p!["The game cannot continue :("],
             p!["The following players are disconnected:"],
             ul,
+            p!["Waiting for them to reconnect..."],
         ]
     }
 
@@ -525,6 +588,7 @@ impl GameSt {
             GamePhase::MyTurn(TurnProgress::CapturingWith(cidx, cb)) => self.view_capture(*cidx, cb),
             GamePhase::MyTurn(TurnProgress::ActionIssued(_a)) => p!["Issued action. Waiting for server."],
             GamePhase::GameDone(scores) => self.view_score(scores),
+            GamePhase::MatchDone(scores, _) => self.view_score(scores),
             GamePhase::RoundDone => p!["Round done! Wait for new cards."],
             GamePhase::PlayersDisconnected(ps) => self.view_players_disconnected(ps),
         };
@@ -550,71 +614,51 @@ impl GameSt {
             } else {
                 attrs!{At::Class => "inactive-player"}
             };
-            players.add_child(span!(attrs, format!("{} {} ", c, player.name)));
+            let bot = if player.bot { " (bot)" } else { "" };
+            players.add_child(span!(attrs, format!("{} {}{} ", c, player.name, bot)));
+            if Some(tpos) == tpos_active {
+                players.add_child(turn_clock(self.view.turn_deadline));
+            }
+        }
+        if self.lobby_info.spectators.len() > 0 {
+            players.add_child(span![format!("(watching: {})", self.lobby_info.spectators.join(", "))]);
         }
         players
     }
 
     fn view_last_action(&self) -> Node<Msg> {
-        let la = match self.view.last_action.as_ref() {
-            None => return div![""],
-            Some(x) => x,
-        };
-
-        let mut act_elem = match &la.action {
-            core::PlayerAction::LayDown(c) => span!["laid down ", c.to_elem(),],
-            core::PlayerAction::Capture(ca) if la.xeri => {
-                let mut table_cards = ca.get_table_cards();
-                span!["made a «ξερή» capturing ",
-                      iter_to_elem("", table_cards.drain(..), ""),
-                      " with ",
-                      ca.handcard.to_elem()
+        let mut ret = match self.view.last_action.as_ref() {
+            None => div![""],
+            Some(la) => {
+                let pname = self.lobby_info.player_from_tpos(la.player).unwrap().name.clone();
+                div![
+                    span![format!("Last action from {}: ", pname)],
+                    la.to_elem(),
                 ]
             },
-            core::PlayerAction::Capture(ca) => {
-                let mut table_cards = ca.get_table_cards();
-                span!["captured ",
-                      iter_to_elem("", table_cards.drain(..), ""),
-                      " with ",
-                      ca.handcard.to_elem()
-                ]
-            },
-            core::PlayerAction::Declare(da) => {
-                match da.get_decl() {
-                    None => span![
-                        format!("created a declaration of value {} with ", da.value()),
-                        da.handcard().to_elem(),
-                    ],
-                    Some(decl) if decl.value() < da.value() => span![
-                        format!("raised a declaration from {} to {} with ", decl.value(), da.value()),
-                        da.handcard().to_elem(),
-                    ],
-                    Some(decl) if decl.value() == da.value() => span![
-                        format!("added to a declaration of value {} a ", da.value()),
-                        da.handcard().to_elem(),
-                    ],
-                    _ => panic!("Invalid decl"),
-
-                }
-            }
         };
 
-        if la.forced_cards.len() > 0 {
-            act_elem.add_child(
-                iter_to_elem(" (forced table cards:", la.forced_cards.iter().cloned(), ")")
-            );
+        if let Some(timeout) = &self.view.last_timeout {
+            let pname = self.lobby_info.player_from_tpos(timeout.player).unwrap().name.clone();
+            ret.add_child(p![timeout_msg(timeout, &pname)]);
         }
-        act_elem.add_child(span!["."]);
 
-        let pname = self.lobby_info.player_from_tpos(la.player).unwrap().name.clone();
-        div![
-            span![format!("Last action from {}: ", pname)],
-            act_elem
-        ]
+        ret
     }
 
     pub fn view(&self) -> Node<Msg> {
-        match self.phase {
+        if let Some(reason) = &self.closed {
+            return div![p![format!("The game was closed: {}", reason)]];
+        }
+
+        if self.connection_lost {
+            return div![
+                p!["Connection to the server was lost :("],
+                p!["Reload the page to get back to the game."],
+            ];
+        }
+
+        let body = match self.phase {
             GamePhase::MyTurn(_) |
             GamePhase::OthersTurn(_) |
             GamePhase::RoundDone |
@@ -643,7 +687,34 @@ impl GameSt {
                 div![h3!["Game done!"], last_action, phase, cont]
 
             }
-        }
+
+            GamePhase::MatchDone(_, winner) => {
+                let phase = self.view_phase();
+                let last_action = self.view_last_action();
+
+                let cont = if self.lobby_info.am_i_admin() {
+                    let msg = Msg::InGame(InGameMsg::ContinueGame);
+                    let button = button![simple_ev(Ev::Click, msg), "New match"];
+                    p![button]
+                } else {
+                    p![""]
+                };
+
+                let winners = self.team_names(winner);
+                div![h3![format!("Match done! Winner(s): {}", winners)], last_action, phase, cont]
+            }
+        };
+
+        div![body, self.chat.view(&self.lobby_info)]
+    }
+
+    fn team_names(&self, team: usize) -> String {
+        self.lobby_info
+            .team_tpos(team)
+            .iter()
+            .map( |tpos| self.lobby_info.player_from_tpos(*tpos).unwrap().name.clone())
+            .collect::<Vec<_>>()
+            .join(", ")
     }
 
     fn view_score(&self, sheets: &Vec<(core::ScoreSheet, usize)>) -> Node<Msg> {
@@ -651,13 +722,7 @@ impl GameSt {
         let mut game_rows  = vec![tr![th!["player(s)"], th!["score"], th!["points"]]];
         let mut total_rows = vec![tr![th!["player(s)"], th!["score"]]];
         for (i, (ss, total_score)) in sheets.iter().enumerate() {
-            let team_str = self.lobby_info
-                .team_tpos(i)
-                .iter()
-                //.map( |tpos| format!("{} ({})", self.lobby_info.player_from_tpos(*tpos).unwrap().name, tpos_char(*tpos)))
-                .map( |tpos| self.lobby_info.player_from_tpos(*tpos).unwrap().name.clone())
-                .collect::<Vec<_>>()
-                .join(", ");
+            let team_str = self.team_names(i);
 
             let players_td = td![team_str];
             let points_td = td![b![ss.score.to_string()], attrs!{At::Class => "score"}];
@@ -680,7 +745,7 @@ impl GameSt {
     }
 
     fn view_selected_card(&self, cidx: usize) -> Node<Msg> {
-        let card = &self.view.own_hand.cards[cidx];
+        let card = &self.view.get_hand_card(cidx);
         let span = card.to_elem();
         let mut div = div![];
         {
@@ -708,11 +773,11 @@ impl GameSt {
     }
 
     fn view_declaration(&self, cidx: usize, ts: &Option<core::DeclActionBuilder>) -> Node<Msg> {
-        let card = &self.view.own_hand.cards[cidx];
+        let card = &self.view.get_hand_card(cidx);
 
         // NB: we can use that to filter user choices.
         let _sum_set = self.view.iter_hand_cards()
-            .filter(|c| *c != card && !c.rank.is_figure() && c.rank.0 > card.rank.0)
+            .filter(|c| c != card && !c.rank.is_figure() && c.rank.0 > card.rank.0)
             .map(|c| c.rank.0)
             .collect::<std::collections::BTreeSet<u8>>();
 
@@ -773,7 +838,7 @@ impl GameSt {
     }
 
     fn view_capture(&self, cidx: usize, cb: &core::CaptureActionBuilder) -> Node<Msg> {
-        let card = &self.view.own_hand.cards[cidx];
+        let card = &self.view.get_hand_card(cidx);
         let mut div = div![
             p!["Capturing with ",
                card.to_elem(),
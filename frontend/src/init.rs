@@ -6,69 +6,128 @@
 
 // Initial page (where you can create a game)
 
-use std::convert::TryFrom;
 use seed::{*, prelude::*};
 
-use core::srvcli::{CreateRep, CreateReq, CreateReqDebug};
+use core::srvcli::{CreateRep, CreateReq, CreateReqDebug, GameListing};
+use core::game::DEFAULT_TARGET_SCORE;
+use core::{RuleSet, TurnTimer, TimeoutPolicy};
 use crate::{
     DEFAULT_NR_PLAYERS, Model, Msg,
     lobby::{LobbySt},
-    to_elem::{ToElem},
 };
 
+/// Points a team loses when a player's turn is skipped (see TimeoutPolicy::Skip)
+const SKIP_PENALTY: usize = 5;
+
 fn get_create_game_req_url() -> impl Into<std::borrow::Cow<'static, str>> {
     "/creategame"
 }
 
+fn get_list_games_req_url() -> impl Into<std::borrow::Cow<'static, str>> {
+    "/games"
+}
+
 #[derive(Clone,Debug)]
 pub enum InitMsg {
     StartGame,
     StartGameReply(seed::ResponseDataResult<CreateRep>),
     SetPlayerCount(String),
+    SetTargetScore(String),
+    ToggleRule(RuleToggle),
+    SetXeriScore(String),
+    SetTableSize(String),
+    SetTurnTimer(String),
+    SetTimeoutPolicy(String),
+    TogglePublic,
     SetPlayerName(String),
-    DebugHandCards(String),
-    DebugTableCards(String),
+    FetchGames,
+    FetchGamesReply(seed::ResponseDataResult<Vec<GameListing>>),
+    QuickMatch,
+    DebugGame(String),
+}
+
+/// Boolean options of the game variant
+#[derive(Clone,Copy,Debug)]
+pub enum RuleToggle {
+    ForcedCaptures,
+    ForcedDeclarations,
+    XeriFigureDouble,
+    MajorityBonus,
 }
 
 #[derive(Debug)]
 pub struct InitSt {
     /// Number of players
     pub nplayers: u8,
+    /// Score for winning the match (None: no limit)
+    pub target_score: Option<usize>,
+    /// Game variant
+    pub rules: RuleSet,
+    /// Time limit for each turn in seconds (None: no limit)
+    pub turn_secs: Option<u64>,
+    /// What happens when a player runs out of time
+    pub timeout_policy: TimeoutPolicy,
+    /// List the game in the public games
+    pub public: bool,
+    /// Public games that wait for players
+    pub games: Vec<GameListing>,
     /// Error when trying to start a game
     pub start_game_err: Option<String>,
     pub player_name: String,
 
-    pub debug_hand: String,
-    pub debug_table: String,
+    /// Initial position for debug games (see core::repr::GameRepr)
+    pub debug_game: String,
 }
 
 impl InitSt {
 
-    fn mk_create_req(&self) -> CreateReq {
-
-        let debug = if self.nplayers == 1 {
+    /// Create request, as configured by the user (debug position is not verified)
+    fn create_req(&self) -> CreateReq {
+        let debug = if self.debug_game.len() > 0 {
             Some(CreateReqDebug {
-                hand_s: self.debug_hand.clone(),
-                table_s: self.debug_table.clone(),
+                game_s: self.debug_game.clone(),
             })
         } else { None };
 
-        let mut ret = CreateReq {
+        CreateReq {
             nplayers: self.nplayers,
+            target_score: self.target_score,
+            rules: self.rules.clone(),
+            turn_timer: self.turn_timer(),
+            public: self.public,
             debug: debug,
-        };
+        }
+    }
+
+    fn turn_timer(&self) -> Option<TurnTimer> {
+        self.turn_secs.map(|secs| TurnTimer { secs: secs, policy: self.timeout_policy })
+    }
+
+    fn mk_create_req(&self) -> CreateReq {
+        let mut ret = self.create_req();
 
         // verify that debug strings are correct
         ret.verify_debug();
 
         if let (Some(dbg), Some(storage)) = (&ret.debug, &seed::storage::get_storage()) {
-            seed::storage::store_data(&storage, "debug_hand_cards", &dbg.hand_s);
-            seed::storage::store_data(&storage, "debug_table_cards", &dbg.table_s);
+            seed::storage::store_data(&storage, "debug_game", &dbg.game_s);
         }
 
         ret
     }
 
+    fn check_player_name(&self) -> Result<(), String> {
+        if self.player_name.len() == 0 {
+            return Err(format!("Please select a non-empty name"));
+        }
+
+        if !self.player_name.chars().all(char::is_alphanumeric) {
+            return Err(format!("Please only use alphanumeric characters for the name"));
+        }
+
+        Ok(())
+    }
+
     pub fn update_state(&mut self, msg: &InitMsg, orders: &mut impl Orders<Msg>) -> Option<Model> {
         // log!(format!("*************** {:?}", msg));
 
@@ -80,7 +139,9 @@ impl InitSt {
                         let ret = LobbySt::new(rep.game_id.clone(), self.player_name.clone(), orders,);
                         match ret {
                             Ok(st) => {
-                                seed::push_route(seed::Url::new(vec!["ingame"]));
+                                // NB: reloading the page will use the session token to reconnect
+                                let join_q = format!("join={}", rep.game_id);
+                                seed::push_route(seed::Url::new(Vec::<&str>::new()).search(&join_q));
                                 return Some(Model::InLobby(st));
                             },
                             Err(x) => {
@@ -99,13 +160,18 @@ impl InitSt {
             },
 
             InitMsg::StartGame => {
-                if self.player_name.len() == 0 {
-                    self.start_game_err = Some(format!("Please select a non-empty name"));
+                if let Err(x) = self.check_player_name() {
+                    self.start_game_err = Some(x);
+                    return None;
+                }
+
+                if let Err(x) = self.rules.validate(self.nplayers as usize) {
+                    self.start_game_err = Some(x);
                     return None;
                 }
 
-                if !self.player_name.chars().all(char::is_alphanumeric) {
-                    self.start_game_err = Some(format!("Please only use alphanumeric characters for the name"));
+                if let Some(Err(x)) = self.turn_timer().map(|t| t.validate(self.nplayers as usize)) {
+                    self.start_game_err = Some(x);
                     return None;
                 }
 
@@ -128,16 +194,87 @@ impl InitSt {
                 } else {}
             },
 
-            InitMsg::SetPlayerName(x) => {
-                self.player_name = x.to_string();
+            InitMsg::SetTargetScore(x) => {
+                self.target_score = x.parse::<usize>().ok();
+            },
+
+            InitMsg::ToggleRule(toggle) => {
+                let opt = match toggle {
+                    RuleToggle::ForcedCaptures => &mut self.rules.forced_captures,
+                    RuleToggle::ForcedDeclarations => &mut self.rules.forced_declarations,
+                    RuleToggle::XeriFigureDouble => &mut self.rules.xeri_figure_double,
+                    RuleToggle::MajorityBonus => &mut self.rules.majority_bonus,
+                };
+                *opt = !*opt;
+            },
+
+            InitMsg::SetXeriScore(x) => {
+                if let Ok(val) = x.parse::<usize>() {
+                    self.rules.xeri_score = val;
+                }
+            },
+
+            InitMsg::SetTableSize(x) => {
+                if let Ok(val) = x.parse::<usize>() {
+                    self.rules.table_size = val;
+                }
             },
 
-            InitMsg::DebugHandCards(x) => {
-                self.debug_hand = x.clone();
+            InitMsg::SetTurnTimer(x) => {
+                self.turn_secs = x.parse::<u64>().ok();
             },
 
-            InitMsg::DebugTableCards(x) => {
-                self.debug_table = x.clone();
+            InitMsg::SetTimeoutPolicy(x) => {
+                self.timeout_policy = match x.as_str() {
+                    "skip" => TimeoutPolicy::Skip { penalty: SKIP_PENALTY },
+                    "forfeit" => TimeoutPolicy::Forfeit,
+                    _ => TimeoutPolicy::AutoPlay,
+                };
+            },
+
+            InitMsg::TogglePublic => {
+                self.public = !self.public;
+            },
+
+            InitMsg::FetchGames => {
+                let req = Request::new(get_list_games_req_url().into())
+                    .fetch_json_data( |o| Msg::Init(InitMsg::FetchGamesReply(o)));
+                orders.perform_cmd(req);
+            },
+
+            InitMsg::FetchGamesReply(result) => {
+                match result {
+                    Ok(games) => self.games = games.clone(),
+                    Err(x) => log!(format!("Error fetching public games: {:?}", x)),
+                }
+            },
+
+            InitMsg::QuickMatch => {
+                if let Err(x) = self.check_player_name() {
+                    self.start_game_err = Some(x);
+                    return None;
+                }
+
+                if self.nplayers != 2 && self.nplayers != 4 {
+                    self.start_game_err = Some("Quick match is for 2 or 4 players".to_string());
+                    return None;
+                }
+
+                match LobbySt::new_quick_match(self.nplayers, self.player_name.clone(), orders) {
+                    Ok(st) => return Some(Model::InLobby(st)),
+                    Err(x) => {
+                        self.start_game_err = Some("Could not join quick match".to_string());
+                        log!(format!("Error joining quick match: {:?}", x));
+                    }
+                }
+            },
+
+            InitMsg::SetPlayerName(x) => {
+                self.player_name = x.to_string();
+            },
+
+            InitMsg::DebugGame(x) => {
+                self.debug_game = x.clone();
             },
         };
 
@@ -172,38 +309,202 @@ impl InitSt {
         ]
     }
 
-    fn debug_options(&self) -> Node<Msg> {
+    fn select_target_score(&self) -> Node<Msg> {
+        let get_option = |txt: &str, val: Option<usize>| {
+            let val_s = val.map_or("none".to_string(), |x| x.to_string());
+            if val == self.target_score {
+                option![txt, attrs!{At::Value => val_s, At::Selected => "true"} ]
+            } else {
+                option![txt, attrs!{At::Value => val_s,} ]
+            }
+        };
 
-        let no_debug = self.debug_hand.len() == 0 && self.debug_table.len() == 0;
-        let debug_hand = core::Deck::try_from(self.debug_hand.as_str()).map_or(span![], |x| x.to_elem());
-        let debug_table = core::Deck::try_from(self.debug_table.as_str()).map_or(span![], |x| x.to_elem());
+        div![
+            label!["Play until: ", attrs! {At::For => "sel-target-score" }],
+            select![
+                get_option("31 points", Some(31)),
+                get_option("61 points", Some(DEFAULT_TARGET_SCORE)),
+                get_option("101 points", Some(101)),
+                get_option("no limit", None),
+                input_ev(Ev::Input, |x| Msg::Init(InitMsg::SetTargetScore(x))),
+                attrs!{At::Id => "sel-target-score"},
+            ],
+        ]
+    }
 
-        let mut div = div![
-            p!["Debug (use S,C,H,D for suit and 1-9,T,J,Q,K for rank; e.g.: HK C5)"],
+    fn select_turn_timer(&self) -> Node<Msg> {
+        let get_option = |txt: &str, val: &str, selected: bool| {
+            if selected {
+                option![txt, attrs!{At::Value => val, At::Selected => "true"} ]
+            } else {
+                option![txt, attrs!{At::Value => val,} ]
+            }
+        };
+
+        let mut ret = div![
+            label!["Time per turn: ", attrs! {At::For => "sel-turn-timer" }],
+            select![
+                get_option("no limit", "none", self.turn_secs.is_none()),
+                get_option("30 seconds", "30", self.turn_secs == Some(30)),
+                get_option("60 seconds", "60", self.turn_secs == Some(60)),
+                input_ev(Ev::Input, |x| Msg::Init(InitMsg::SetTurnTimer(x))),
+                attrs!{At::Id => "sel-turn-timer"},
+            ],
+        ];
+
+        if self.turn_secs.is_some() {
+            let skip_txt = format!("skip the turn (-{} points)", SKIP_PENALTY);
+            ret.add_child(span![
+                label![" When time runs out: ", attrs! {At::For => "sel-timeout-policy" }],
+                select![
+                    get_option("play automatically", "auto", self.timeout_policy == TimeoutPolicy::AutoPlay),
+                    get_option(&skip_txt, "skip", self.timeout_policy == TimeoutPolicy::Skip { penalty: SKIP_PENALTY }),
+                    get_option("forfeit the match", "forfeit", self.timeout_policy == TimeoutPolicy::Forfeit),
+                    input_ev(Ev::Input, |x| Msg::Init(InitMsg::SetTimeoutPolicy(x))),
+                    attrs!{At::Id => "sel-timeout-policy"},
+                ],
+            ]);
+        }
+
+        ret
+    }
+
+    fn rule_options(&self) -> Node<Msg> {
+        let checkbox = |txt: &str, id: &str, checked: bool, toggle: RuleToggle| {
             p![
-                label!["Table cards: ", attrs!{At::For => "set-dbg-table" }],
                 input![
-                    input_ev(Ev::Input, |x| Msg::Init(InitMsg::DebugTableCards(x))),
+                    simple_ev(Ev::Click, Msg::Init(InitMsg::ToggleRule(toggle))),
                     attrs!{
-                        At::Id => "set-dbg-table"
-                        At::Value => self.debug_table,
+                        At::Type => "checkbox",
+                        At::Id => id,
+                        At::Checked => checked.as_at_value(),
                     }
-                ]
+                ],
+                label![txt, attrs!{At::For => id}],
+            ]
+        };
+
+        let select = |txt: &str, id: &str, opts: &[usize], curr: usize| {
+            let mut options = vec![];
+            for val in opts.iter() {
+                let mut attrs = attrs!{At::Value => val.to_string()};
+                if *val == curr {
+                    attrs.add(At::Selected, "true");
+                }
+                options.push(option![val.to_string(), attrs]);
+            }
+            (label![txt, attrs!{At::For => id}], options)
+        };
+
+        let (xeri_label, xeri_opts) = select("Points per xeri: ", "sel-xeri-score", &[10, 5, 0], self.rules.xeri_score);
+        let (table_label, table_opts) = select("Initial table cards: ", "sel-table-size", &[4, 0, 8], self.rules.table_size);
+
+        div![
+            p!["Rules:"],
+            checkbox("forced captures", "chk-forced-captures", self.rules.forced_captures, RuleToggle::ForcedCaptures),
+            checkbox("forced declarations", "chk-forced-decls", self.rules.forced_declarations, RuleToggle::ForcedDeclarations),
+            checkbox("xeri with a figure counts double", "chk-xeri-figure", self.rules.xeri_figure_double, RuleToggle::XeriFigureDouble),
+            checkbox("points for having the most cards", "chk-majority", self.rules.majority_bonus, RuleToggle::MajorityBonus),
+            p![
+                xeri_label,
+                select![
+                    xeri_opts,
+                    input_ev(Ev::Input, |x| Msg::Init(InitMsg::SetXeriScore(x))),
+                    attrs!{At::Id => "sel-xeri-score"},
+                ],
+            ],
+            p![
+                table_label,
+                select![
+                    table_opts,
+                    input_ev(Ev::Input, |x| Msg::Init(InitMsg::SetTableSize(x))),
+                    attrs!{At::Id => "sel-table-size"},
+                ],
+            ],
+        ]
+    }
+
+    fn public_option(&self) -> Node<Msg> {
+        p![
+            input![
+                simple_ev(Ev::Click, Msg::Init(InitMsg::TogglePublic)),
+                attrs!{
+                    At::Type => "checkbox",
+                    At::Id => "chk-public",
+                    At::Checked => self.public.as_at_value(),
+                }
+            ],
+            label!["list the game in the public games (anyone can join)", attrs!{At::For => "chk-public"}],
+        ]
+    }
+
+    fn public_games(&self) -> Node<Msg> {
+        let refresh = button![
+            simple_ev(Ev::Click, Msg::Init(InitMsg::FetchGames)),
+            "refresh",
+        ];
+
+        let mut ret = div![
+            h2!["Public games"],
+            p![
+                button![
+                    simple_ev(Ev::Click, Msg::Init(InitMsg::QuickMatch)),
+                    format!("Quick match ({} players)", self.nplayers),
+                    style![St::MarginRight => px(10)],
+                ],
+                "play with whoever else is waiting (set your name and the number of players above)",
             ],
+        ];
+
+        if self.games.len() == 0 {
+            ret.add_child(p!["No public games are waiting for players. ", refresh]);
+            return ret;
+        }
+
+        let mut rows = vec![tr![th!["created by"], th!["players"], th!["play until"], th!["time per turn"], th![""]]];
+        for g in self.games.iter() {
+            let target = g.target_score.map_or("no limit".to_string(), |x| format!("{} points", x));
+            let timer = g.turn_timer.map_or("no limit".to_string(), |t| format!("{} seconds", t.secs));
+            let join_href = format!("/?join={}", g.game_id);
+            rows.push(tr![
+                td![g.admin],
+                td![format!("{}/{}", g.nplayers - g.free_seats, g.nplayers)],
+                td![target],
+                td![timer],
+                td![a!["join", attrs!{At::Href => join_href}]],
+            ]);
+        }
+        ret.add_child(table![rows]);
+        ret.add_child(p![refresh]);
+        ret
+    }
+
+    fn debug_options(&self) -> Node<Msg> {
+
+        let mut div = div![
+            p!["Debug (use S,C,H,D for suit and 1-9,T,J,Q,K for rank)"],
+            p!["Position: hands (separated by |) / table / deck, optionally followed by / turn, first player, \
+                and team that captured last (default: P0 P0 T0). \
+                The deck is dealt from the end. E.g.: D5 S4 | H2 CK / HT S5 / HA H3 / P1 P1 T0"],
             p![
-                label!["Hand cards: ", attrs!{At::For => "set-dbg-hand" }],
+                label!["Position: ", attrs!{At::For => "set-dbg-game" }],
                 input![
-                    input_ev(Ev::Input, |x| Msg::Init(InitMsg::DebugHandCards(x))),
+                    input_ev(Ev::Input, |x| Msg::Init(InitMsg::DebugGame(x))),
                     attrs!{
-                        At::Id => "set-dbg-hand"
-                        At::Value => self.debug_hand,
+                        At::Id => "set-dbg-game"
+                        At::Value => self.debug_game,
+                        At::Size => 60,
                     },
                 ],
             ],
         ];
 
-        if !no_debug {
-            div.add_child(p![span!["Table: "], debug_table, span![", Hand: "], debug_hand]);
+        if self.debug_game.len() > 0 {
+            let req = self.create_req();
+            match req.check_debug_game() {
+                Ok(()) => div.add_child(p!["Valid position"]),
+                Err(e) => div.add_child(p![format!("Invalid position: {}", e)]),
+            };
         }
 
         div
@@ -240,7 +541,7 @@ impl InitSt {
             "."
         ];
         let p_variant = p![
-            "The variant implemented is one with \"forced captures\" and \"forced declarations\".",
+            "The default variant is one with \"forced captures\" and \"forced declarations\". You can change the rules when creating a game.",
         ];
         let p_gh = p![
             "Feel free check the code, report issues, or add suggestions on ",
@@ -262,11 +563,13 @@ impl InitSt {
             h2!["Create new game"],
             self.set_name(),
             self.select_nplayers(),
+            self.select_target_score(),
+            self.select_turn_timer(),
+            self.rule_options(),
+            self.public_option(),
         ];
 
-        if self.nplayers == 1 {
-            ret.add_child(self.debug_options());
-        }
+        ret.add_child(self.debug_options());
 
         ret.add_child(
             button![
@@ -281,6 +584,7 @@ impl InitSt {
             ret.add_child(p![format!("Error: {}", x)]);
         }
 
+        ret.add_child(self.public_games());
         ret.add_child(self.footer());
         ret
     }
@@ -9,6 +9,7 @@ use seed::{*, prelude::*};
 use crate::{
     Model, Msg,
     lobby::{LobbySt, },
+    spectate::{SpectateSt, },
 };
 
 /// Join state
@@ -16,6 +17,8 @@ use crate::{
 #[derive(Debug, Clone)]
 pub enum JoinMsg {
     JoinGame,
+    WatchGame,
+    ToggleReveal,
     SetPlayerName(String),
 }
 
@@ -24,19 +27,29 @@ pub struct JoinSt {
     pub game_id: String,
     pub player_name: String,
     pub join_game_err: Option<String>,
+    /// when watching, reveal the hands after each game
+    pub reveal: bool,
 }
 
 impl JoinSt {
+    fn check_name(&mut self) -> bool {
+        if self.player_name.len() == 0 {
+            self.join_game_err = Some(format!("Please select a non-empty name"));
+            return false;
+        }
+
+        if !self.player_name.chars().all(char::is_alphanumeric) {
+            self.join_game_err = Some(format!("Please only use alphanumeric characters for the name"));
+            return false;
+        }
+
+        true
+    }
+
     pub fn update_state(&mut self, msg: &JoinMsg, orders: &mut impl Orders<Msg>) -> Option<Model> {
         match msg {
             JoinMsg::JoinGame => {
-                if self.player_name.len() == 0 {
-                    self.join_game_err = Some(format!("Please select a non-empty name"));
-                    return None;
-                }
-
-                if !self.player_name.chars().all(char::is_alphanumeric) {
-                    self.join_game_err = Some(format!("Please only use alphanumeric characters for the name"));
+                if !self.check_name() {
                     return None;
                 }
                 Some(Model::InLobby(LobbySt::new(
@@ -45,6 +58,22 @@ impl JoinSt {
                     orders
                 ).unwrap()))
             },
+            JoinMsg::WatchGame => {
+                if !self.check_name() {
+                    return None;
+                }
+                match SpectateSt::new(self.game_id.clone(), self.player_name.clone(), self.reveal, orders) {
+                    Ok(st) => Some(Model::Spectating(st)),
+                    Err(x) => {
+                        self.join_game_err = Some(x);
+                        None
+                    },
+                }
+            },
+            JoinMsg::ToggleReveal => {
+                self.reveal = !self.reveal;
+                None
+            },
             JoinMsg::SetPlayerName(name) => {
                 self.player_name = name.to_string();
                 None
@@ -74,6 +103,22 @@ impl JoinSt {
                 "Join!",
                 style![St::MarginRight => px(10)],
             ],
+            button![
+                simple_ev(Ev::Click, Msg::Join(JoinMsg::WatchGame)),
+                "Watch",
+                style![St::MarginRight => px(10)],
+            ],
+            p![
+                input![
+                    simple_ev(Ev::Click, Msg::Join(JoinMsg::ToggleReveal)),
+                    attrs!{
+                        At::Type => "checkbox",
+                        At::Id => "chk-reveal",
+                        At::Checked => self.reveal.as_at_value(),
+                    }
+                ],
+                label!["when watching, show the cards of every player after each game", attrs!{At::For => "chk-reveal"}],
+            ],
         ];
 
         if let Some(x) = &self.join_game_err {
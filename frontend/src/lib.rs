@@ -11,6 +11,8 @@ mod init;
 mod lobby;
 mod join;
 mod game;
+mod spectate;
+mod chat;
 
 use seed::{*, prelude::*};
 
@@ -18,6 +20,8 @@ use init::{InitMsg, InitSt};
 use lobby::{LobbyMsg, LobbySt};
 use join::{JoinSt, JoinMsg};
 use game::{GameSt, InGameMsg};
+use spectate::SpectateSt;
+use chat::ChatMsg;
 use ws::{WsEvent};
 
 const DEFAULT_NR_PLAYERS: u8 = 2;
@@ -32,6 +36,7 @@ pub enum Model {
     Join(JoinSt),
     InLobby(LobbySt),
     InGame(GameSt),
+    Spectating(SpectateSt),
     Error(String),
 }
 
@@ -41,7 +46,10 @@ pub enum Msg {
     Join(JoinMsg),
     InGame(InGameMsg),
     Lobby(LobbyMsg),
+    Chat(ChatMsg),
     Ws(WsEvent),
+    /// Sent every second, so that the turn clock is updated
+    Tick,
     Reload,
     Error(String),
 }
@@ -59,12 +67,21 @@ fn update(msg: Msg, mut model: &mut Model, orders: &mut impl Orders<Msg>) {
     //log(format!("update => {:?}", msg));
     let upd_ret = match (&mut model, msg) {
         (_, Msg::Error(x)) => Some(Model::Error(x)),
+        (&mut Model::InGame(_), Msg::Tick)             => None,
+        (&mut Model::Spectating(_), Msg::Tick)         => None,
+        (_, Msg::Tick) => {
+            orders.skip();
+            None
+        },
         (&mut Model::Init(st), Msg::Init(ref msg))     => st.update_state(msg, orders),
         (&mut Model::Join(st), Msg::Join(ref msg))     => st.update_state(msg, orders),
         (&mut Model::InLobby(st), Msg::Lobby(ref msg)) => st.update_state(msg, orders),
         (&mut Model::InLobby(st), Msg::Ws(ref msg))    => st.handle_ws_event(msg, orders),
+        (&mut Model::InLobby(st), Msg::Chat(ref msg))  => st.handle_chat_msg(msg),
         (&mut Model::InGame(st), Msg::InGame(ref msg)) => st.update_state(msg),
         (&mut Model::InGame(st), Msg::Ws(ref msg))     => st.handle_ws_event(msg, orders),
+        (&mut Model::InGame(st), Msg::Chat(ref msg))   => st.handle_chat_msg(msg),
+        (&mut Model::Spectating(st), Msg::Ws(ref msg)) => st.handle_ws_event(msg, orders),
         // The first load will trigger a new route which will issue a reload message, which we
         // ignore when we are on init or join state.
         (&mut Model::Init(_), Msg::Reload)             => None,
@@ -76,7 +93,10 @@ fn update(msg: Msg, mut model: &mut Model, orders: &mut impl Orders<Msg>) {
         (&mut Model::Error(_), Msg::Ws(_))             => None,
         (&mut Model::Init(_), Msg::Ws(_))              => None,
         (&mut Model::Join(_), Msg::Ws(_))              => None,
-        (_, Msg::Reload)                               => Some(Model::default()),
+        (_, Msg::Reload)                               => {
+            orders.send_msg(Msg::Init(InitMsg::FetchGames));
+            Some(Model::default())
+        },
         (s,m) => {
             error(format!("Invalid message {:?} for current model {:?}", m, s));
             Some(Model::Error("Error: something went wrong...".to_string()))
@@ -95,6 +115,7 @@ fn view(model: &Model) -> impl View<Msg> {
         Model::Join(st) => st.view(),
         Model::InGame(st) => st.view(),
         Model::InLobby(st) => st.view(),
+        Model::Spectating(st) => st.view(),
     }
 }
 
@@ -124,40 +145,62 @@ impl Default for Model {
         let player_name = get_string_from_storage("player_name");
         let initst = InitSt {
             nplayers: DEFAULT_NR_PLAYERS,
+            target_score: Some(core::game::DEFAULT_TARGET_SCORE),
+            rules: core::RuleSet::default(),
+            turn_secs: None,
+            timeout_policy: core::TimeoutPolicy::AutoPlay,
+            public: false,
+            games: vec![],
             player_name: player_name,
             start_game_err: None,
-            debug_hand: "".to_string(),
-            debug_table: "".to_string(),
+            debug_game: "".to_string(),
         };
         Model::Init(initst)
     }
 }
 
-fn after_mount(_url: Url, _orders: &mut impl Orders<Msg>) -> AfterMount<Model> {
+fn after_mount(_url: Url, orders: &mut impl Orders<Msg>) -> AfterMount<Model> {
     let href = web_sys::window().unwrap().location().href().expect("href not found");
     let url = url::Url::parse(&href).expect("invalid url");
     let join_game_id = url.query_pairs().find(|(k,_v)| k == "join").map(|(_k,v)| v);
 
     let player_name = get_string_from_storage("player_name");
-    let debug_hand = get_string_from_storage("debug_hand_cards");
-    let debug_table = get_string_from_storage("debug_table_cards");
+    let debug_game = get_string_from_storage("debug_game");
     log(format!("Starting..."));
 
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    seed::set_interval(Box::new(move || app.update(msg_mapper(Msg::Tick))), 1000);
+
     if let Some(game_id) = join_game_id {
+        // we have been in this game before (e.g., the page was reloaded): reconnect directly
+        if lobby::get_session_token(&game_id).is_some() && player_name.len() > 0 {
+            match LobbySt::new(game_id.to_string(), player_name.clone(), orders) {
+                Ok(st) => return AfterMount::new(Model::InLobby(st)),
+                Err(x) => log!(format!("Error reconnecting to game: {:?}", x)),
+            }
+        }
+
         let joinst = JoinSt {
             game_id: game_id.to_string(),
             player_name: player_name,
             join_game_err: None,
+            reveal: false,
         };
         AfterMount::new(Model::Join(joinst))
     } else {
         let initst = InitSt {
             nplayers: DEFAULT_NR_PLAYERS,
+            target_score: Some(core::game::DEFAULT_TARGET_SCORE),
+            rules: core::RuleSet::default(),
+            turn_secs: None,
+            timeout_policy: core::TimeoutPolicy::AutoPlay,
+            public: false,
+            games: vec![],
             player_name: player_name,
             start_game_err: None,
-            debug_hand: debug_hand,
-            debug_table: debug_table,
+            debug_game: debug_game,
         };
+        orders.send_msg(Msg::Init(InitMsg::FetchGames));
         AfterMount::new(Model::Init(initst))
     }
 }
@@ -9,18 +9,22 @@ use web_sys;
 use wasm_bindgen::{JsCast, closure::Closure};
 
 use core::srvcli::{LobbyInfo, ServerMsg, ClientMsg, PlayerTpos};
+use core::BotKind;
 
 use crate::{
     Model, Msg,
     to_elem::{tpos_char},
     ws::WsEvent,
     game::{GameSt, },
+    chat::{ChatSt, ChatMsg},
 };
 
 #[derive(Debug,Clone)]
 pub enum LobbyMsg {
     IssueStart,
     SwapTpos(PlayerTpos, PlayerTpos),
+    AddBot(BotKind),
+    RemoveBot(PlayerTpos),
 }
 
 /// Internal lobby state
@@ -43,10 +47,13 @@ impl Default for State {
 
 #[derive(Debug)]
 pub struct LobbySt {
-    /// game identifier
+    /// game identifier (empty for quick-match players, until the server picks a game)
     pub game_id: String,
     pub player_name: String,
     state: State,
+    chat: ChatSt,
+    /// how long the server keeps quick-match players in the queue (seconds)
+    quick_match_wait: Option<u64>,
 }
 
 fn lobby_tpos_elem(lobby_info: &LobbyInfo, tpos: PlayerTpos) -> Node<Msg> {
@@ -102,6 +109,9 @@ fn lobby_info_view_players(lobby_info: &LobbyInfo) -> Node<Msg> {
             if player.admin {
                 vattrs.push("admin");
             }
+            if player.bot {
+                vattrs.push("bot");
+            }
 
             if !player.connected {
                 vattrs.push("disconnected \u{2718}");
@@ -109,9 +119,15 @@ fn lobby_info_view_players(lobby_info: &LobbyInfo) -> Node<Msg> {
 
             let td_tpos = td![lobby_tpos_elem(lobby_info, tpos)];
             let td_name = td![player.name];
-            let td_attrs = if vattrs.len() > 0 {
+            let mut td_attrs = if vattrs.len() > 0 {
                 td![format!("({})", vattrs.join(", "))]
             } else { td![""] };
+            if am_admin && player.bot {
+                td_attrs.add_child(button![
+                    simple_ev(Ev::Click, Msg::Lobby(LobbyMsg::RemoveBot(tpos))),
+                    "remove",
+                ]);
+            }
 
             if nplayers == 4 {
                 let td_team = td![
@@ -132,6 +148,21 @@ fn lobby_info_view_players(lobby_info: &LobbyInfo) -> Node<Msg> {
             table![player_rows, attrs!{At::Class => "lobby-players"}, ]
         ];
 
+        if am_admin && lobby_info.has_free_slots() {
+            let mut p = p!["Add bot: "];
+            for kind in BotKind::all() {
+                p.add_child(button![
+                    simple_ev(Ev::Click, Msg::Lobby(LobbyMsg::AddBot(*kind))),
+                    kind.to_string(),
+                ]);
+            }
+            div.add_child(p);
+        }
+
+        if lobby_info.spectators.len() > 0 {
+            div.add_child(p![format!("Watching: {}", lobby_info.spectators.join(", "))]);
+        }
+
         let disconnected = lobby_info.disconnected_players();
         if am_admin {
             let attrs = if !all_ready {
@@ -162,9 +193,9 @@ fn lobby_info_view_players(lobby_info: &LobbyInfo) -> Node<Msg> {
 
             //let hname = web_sys::window().expect("web_sys window").location().host().expect("location");
             div.add_child(div![
-                p!["The following players have left:"],
+                p!["The following players are disconnected:"],
                 ul,
-                p!["The game cannot start now :("],
+                p!["Waiting for them to reconnect..."],
                 p![a!["Start again", attrs!{At::Href => "/"}]],
             ]);
         }
@@ -197,17 +228,29 @@ pub fn get_server_message(msg: &web_sys::MessageEvent) -> Result<ServerMsg, Stri
     serde_json::from_str(&txt).map_err(|x| x.to_string())
 }
 
-pub fn get_lobby_update(msg: &web_sys::MessageEvent) -> Result<LobbyInfo, String> {
-    match get_server_message(msg)? {
-        ServerMsg::LobbyUpdate(x) => Ok(x),
-        _ => Err("Unexpected server message (not LobbyUpdate)".to_string()),
-    }
+/// local storage key for the session token of a game
+pub fn session_token_key(game_id: &str) -> String {
+    format!("session_{}", game_id)
+}
+
+pub fn get_session_token(game_id: &str) -> Option<String> {
+    let storage = seed::storage::get_storage()?;
+    storage.get_item(&session_token_key(game_id)).ok()?
 }
 
 impl LobbySt {
 
     pub fn view(&self) -> Node<Msg> {
         let body = match &self.state {
+            State::Initialized(_) if self.game_id.len() == 0 => {
+                match self.quick_match_wait {
+                    Some(secs) => p![format!(
+                        "Waiting for other players to join the quick match (for up to {} minutes)...",
+                        (secs + 59) / 60,
+                    )],
+                    None => p!["Contacting server..."],
+                }
+            },
             State::Initialized(_) => {
                 p!["Contacting server..."]
             },
@@ -229,6 +272,7 @@ impl LobbySt {
                 }
 
                 b.add_child(lobby_info_view_players(&li));
+                b.add_child(self.chat.view(&li));
                 b
             },
             State::Error(err) => {
@@ -267,6 +311,12 @@ impl LobbySt {
             LobbyMsg::SwapTpos(tpos1, tpos2) => {
                 serde_json::to_string(&ClientMsg::SwapTpos(*tpos1, *tpos2)).unwrap()
             },
+            LobbyMsg::AddBot(kind) => {
+                serde_json::to_string(&ClientMsg::AddBot(*kind)).unwrap()
+            },
+            LobbyMsg::RemoveBot(tpos) => {
+                serde_json::to_string(&ClientMsg::RemoveBot(*tpos)).unwrap()
+            },
         };
 
         if let Err(_x) = ws.send_with_str(&req) {
@@ -277,11 +327,26 @@ impl LobbySt {
         None
     }
 
+    pub fn handle_chat_msg(&mut self, msg: &ChatMsg) -> Option<Model> {
+        let text = self.chat.update_state(msg)?;
+        let ws = self.get_wsocket_mut()?;
+        let req = serde_json::to_string(&ClientMsg::Chat(text)).unwrap();
+        if let Err(_x) = ws.send_with_str(&req) {
+            error!("Failed to send data to server");
+            self.state = State::Error("Failed to contact server".to_string());
+        }
+        None
+    }
+
     pub fn handle_ws_event(&mut self, ev: &WsEvent, _orders: &mut impl Orders<Msg>) -> Option<Model> {
         // NB: once we fix the backend, we  can have a better explaination here.
         match ev {
             WsEvent::WsClose(ce) if ce.code() == 4444 => {
-                self.state = State::Error("Error: game no longer exists".to_string());
+                // NB: if we tried to reconnect, the session token is no longer good
+                if let Some(storage) = seed::storage::get_storage() {
+                    storage.remove_item(&session_token_key(&self.game_id)).unwrap_or(());
+                }
+                self.state = State::Error(format!("Error: {}", ce.reason()));
                 return None;
             }
             WsEvent::WsError(_) | WsEvent::WsClose(_) => {
@@ -301,17 +366,39 @@ impl LobbySt {
                 match ev {
                     // websocket connected. Just wait for the server's first message
                     WsEvent::WsConnected(_) => State::Initialized(ws),
-                    // The server's first message should be a LobbyUpdate. Once we get that, we
-                    // switch to the ready state.
+                    // The server sends a SessionToken for new players, and then a LobbyUpdate. Once
+                    // we get the latter, we switch to the ready state. Quick-match players first get
+                    // a QuickMatchWaiting while they are in the queue, and then the id of their game.
                     WsEvent::WsMessage(msg) => {
-                        match get_lobby_update(msg) {
+                        match get_server_message(msg) {
+                            Ok(ServerMsg::QuickMatchWaiting { timeout_secs }) => {
+                                self.quick_match_wait = Some(timeout_secs);
+                                State::Initialized(ws)
+                            },
+                            Ok(ServerMsg::QuickMatch(game_id)) => {
+                                // NB: reloading the page will use the session token to reconnect
+                                let join_q = format!("join={}", game_id);
+                                seed::push_route(seed::Url::new(Vec::<&str>::new()).search(&join_q));
+                                self.game_id = game_id;
+                                State::Initialized(ws)
+                            },
+                            Ok(ServerMsg::SessionToken(token)) => {
+                                if let Some(storage) = seed::storage::get_storage() {
+                                    seed::storage::store_data(&storage, &session_token_key(&self.game_id), &token);
+                                }
+                                State::Initialized(ws)
+                            },
+                            Ok(ServerMsg::LobbyUpdate(li)) => {
+                                State::Ready(ws, li)
+                            },
+                            Ok(x) => {
+                                error!(format!("Unexpected server message while expecting LobbyUpdate: {:?}", x));
+                                State::Error("Error contacting server".to_string())
+                            },
                             Err(x) => {
                                 error!(format!("Error while expecting LobbyUpdate: {}", x));
                                 State::Error("Error contacting server".to_string())
                             },
-                            Ok(li) => {
-                                State::Ready(ws, li)
-                            }
                         }
                     },
                     _ => State::Error("Something went wrong...".to_string())
@@ -331,8 +418,14 @@ impl LobbySt {
                                 State::Ready(ws, new_lobby_info)
                             }
 
+                            Ok(ServerMsg::Chat { from, text, ts }) => {
+                                self.chat.add(from, text, ts);
+                                State::Ready(ws, lobby_info)
+                            }
+
                             Ok(ServerMsg::GameUpdate(pview)) => {
-                                let game_st = GameSt::new(ws, lobby_info, pview);
+                                let chat = std::mem::take(&mut self.chat);
+                                let game_st = GameSt::new(ws, self.game_id.clone(), lobby_info, pview, chat);
                                 let new_model = Model::InGame(game_st);
                                 return Some(new_model)
                             }
@@ -358,13 +451,38 @@ impl LobbySt {
         player_name: String,
         orders: &mut impl Orders<Msg>
     ) -> Result<LobbySt, String> {
+        let mut path = format!("{}/{}", game_id, player_name);
+        // if we have been in this game before, try to get our seat back
+        if let Some(token) = get_session_token(&game_id) {
+            path.push_str(&format!("?token={}", token));
+        }
+        Self::connect(&path, game_id, player_name, orders)
+    }
+
+    /// Join a game with other players that wait for a game with the same number of players
+    pub fn new_quick_match(
+        nplayers: u8,
+        player_name: String,
+        orders: &mut impl Orders<Msg>
+    ) -> Result<LobbySt, String> {
+        let path = format!("quickmatch/{}/{}", nplayers, player_name);
+        Self::connect(&path, "".to_string(), player_name, orders)
+    }
+
+    /// Connect to the websocket at /ws/<path>
+    fn connect(
+        path: &str,
+        game_id: String,
+        player_name: String,
+        orders: &mut impl Orders<Msg>
+    ) -> Result<LobbySt, String> {
 
         // try to build the websocket URL
         let loc = web_sys::window().ok_or("Failed to get window")?.location();
         let proto = loc.protocol().map_err(|_| "Failed to get protocol".to_string())?;
         let ws_proto = if proto.starts_with("https") { "wss" } else { "ws" };
         let hname = loc.host().map_err(|_| "Failed to get host")?;
-        let ws_url = format!("{}://{}/ws/{}/{}", ws_proto, hname, game_id, player_name);
+        let ws_url = format!("{}://{}/ws/{}", ws_proto, hname, path);
 
         if let Some(storage) = seed::storage::get_storage() {
             seed::storage::store_data(&storage, "player_name", &player_name);
@@ -395,6 +513,8 @@ impl LobbySt {
             game_id: game_id,
             player_name: player_name,
             state: State::Initialized(ws),
+            chat: ChatSt::default(),
+            quick_match_wait: None,
         };
 
         Ok(ret)
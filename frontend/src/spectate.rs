@@ -0,0 +1,228 @@
+//
+// Kornilios Kourtis <kkourt@kkourt.io>
+//
+// vim: set expandtab softtabstop=4 tabstop=4 shiftwidth=4:
+//
+
+use seed::{*, prelude::*};
+use web_sys;
+
+use core::srvcli::{ServerMsg, SpectatorInfo, PlayerTpos};
+
+use crate::{
+    Model, Msg,
+    to_elem::{iter_to_elem, tpos_char, turn_clock, timeout_msg, ToElem},
+    ws::WsEvent,
+    lobby::{register_ws_handler, get_server_message},
+};
+
+/// Spectator state
+
+#[derive(Debug)]
+pub struct SpectateSt {
+    pub game_id: String,
+    wsocket: web_sys::WebSocket,
+    /// None until we hear from the server
+    info: Option<SpectatorInfo>,
+    error: Option<String>,
+}
+
+impl SpectateSt {
+
+    pub fn new(
+        game_id: String,
+        name: String,
+        reveal: bool,
+        orders: &mut impl Orders<Msg>
+    ) -> Result<SpectateSt, String> {
+        let loc = web_sys::window().ok_or("Failed to get window")?.location();
+        let proto = loc.protocol().map_err(|_| "Failed to get protocol".to_string())?;
+        let ws_proto = if proto.starts_with("https") { "wss" } else { "ws" };
+        let hname = loc.host().map_err(|_| "Failed to get host")?;
+        let ws_url = format!("{}://{}/ws/{}/watch/{}?reveal={}", ws_proto, hname, game_id, name, reveal);
+
+        let ws = web_sys::WebSocket::new(&ws_url).map_err(|_| "Failed to create websocket")?;
+        register_ws_handler(
+            web_sys::WebSocket::set_onopen,
+            |jv| Msg::Ws(WsEvent::WsConnected(jv)),
+            &ws, orders);
+
+        register_ws_handler(
+            web_sys::WebSocket::set_onclose,
+            |jv| Msg::Ws(WsEvent::WsClose(jv)),
+            &ws, orders);
+
+        register_ws_handler(
+            web_sys::WebSocket::set_onerror,
+            |jv| Msg::Ws(WsEvent::WsError(jv)),
+            &ws, orders);
+
+        register_ws_handler(
+            web_sys::WebSocket::set_onmessage,
+            |me| Msg::Ws(WsEvent::WsMessage(me)),
+            &ws, orders);
+
+        Ok(SpectateSt {
+            game_id: game_id,
+            wsocket: ws,
+            info: None,
+            error: None,
+        })
+    }
+
+    pub fn handle_ws_event(&mut self, ev: &WsEvent, _orders: &mut impl Orders<Msg>) -> Option<Model> {
+        match ev {
+            WsEvent::WsConnected(_) => (),
+            WsEvent::WsClose(ce) if ce.code() == 4444 => {
+                self.error = Some(format!("Error: {}", ce.reason()));
+            },
+            WsEvent::WsClose(_) | WsEvent::WsError(_) => {
+                self.error = Some("Connection to the server was lost :(".to_string());
+            },
+            WsEvent::WsMessage(msg) => {
+                match get_server_message(msg) {
+                    Ok(ServerMsg::SpectatorUpdate(info)) => self.info = Some(info),
+                    Ok(x) => error!("Got unexpected server message: {:?}", x),
+                    Err(x) => error!(format!("Error while expecting SpectatorUpdate: {}", x)),
+                }
+            },
+        }
+
+        None
+    }
+
+    fn player_name(&self, tpos: PlayerTpos) -> String {
+        self.info.as_ref()
+            .and_then(|info| info.players.iter().find(|p| p.tpos == tpos))
+            .map_or("?".to_string(), |p| p.name.clone())
+    }
+
+    fn team_names(&self, team: usize, nteams: usize) -> String {
+        let nplayers = self.info.as_ref().map_or(0, |info| info.nplayers);
+        (0..nplayers)
+            .filter(|i| (*i as usize) % nteams == team)
+            .map(|i| self.player_name(PlayerTpos(i)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn view_game(&self, info: &SpectatorInfo, gview: &core::SpectatorGameView) -> Node<Msg> {
+        let active_tpos = match gview.state {
+            core::GameState::NextTurn(tpos) => Some(tpos),
+            _ => None,
+        };
+
+        let mut players = div![p!["Players"]];
+        for p in info.players.iter() {
+            let attrs = if Some(p.tpos) == active_tpos {
+                attrs!{At::Class => "active-player"}
+            } else {
+                attrs!{At::Class => "inactive-player"}
+            };
+            let ncards = gview.player_decks_sz.get(p.tpos.0 as usize).cloned().unwrap_or(0);
+            let bot = if p.bot { " (bot)" } else { "" };
+            players.add_child(span![attrs, format!("{} {}{} [{} cards] ", tpos_char(p.tpos), p.name, bot, ncards)]);
+            if Some(p.tpos) == active_tpos {
+                players.add_child(turn_clock(gview.turn_deadline));
+            }
+        }
+
+        let table = div![
+            attrs!{At::Class => "container"},
+            p!["Table"],
+            iter_to_elem("", gview.table.entries.iter().cloned(), ""),
+        ];
+
+        let mut last_action = match &gview.last_action {
+            None => div![""],
+            Some(la) => div![
+                span![format!("Last action from {}: ", self.player_name(la.player))],
+                la.to_elem(),
+            ],
+        };
+        if let Some(timeout) = &gview.last_timeout {
+            last_action.add_child(p![timeout_msg(timeout, &self.player_name(timeout.player))]);
+        }
+
+        let nteams = gview.scores.len();
+        let mut scores = ul![];
+        for (i, score) in gview.scores.iter().enumerate() {
+            scores.add_child(li![format!("{}: {}", self.team_names(i, nteams), score)]);
+        }
+
+        let phase = match &gview.state {
+            core::GameState::NextTurn(tpos) => p![format!("Waiting for {} ({})", self.player_name(*tpos), tpos_char(*tpos))],
+            core::GameState::RoundDone => p!["Round done!"],
+            core::GameState::GameDone(sheets) | core::GameState::MatchDone(sheets, _) => {
+                let mut rows = vec![tr![th!["player(s)"], th!["points"], th![""]]];
+                for (i, (ss, _)) in sheets.iter().enumerate() {
+                    rows.push(tr![
+                        td![self.team_names(i, nteams)],
+                        td![b![ss.score.to_string()], attrs!{At::Class => "score"}],
+                        td![ss.to_elem()],
+                    ]);
+                }
+                div![h3!["Game done!"], table![rows]]
+            },
+        };
+
+        let mut ret = div![
+            players,
+            table,
+            last_action,
+            div![attrs!{At::Class => "container"}, phase],
+            p![format!("Remaining cards in the deck: {}", gview.main_deck_sz)],
+            div![p!["Match score"], scores],
+        ];
+
+        if let Some(hands) = &gview.revealed_hands {
+            let mut ul = ul![];
+            for (i, hand) in hands.iter().enumerate() {
+                let tpos = PlayerTpos(i as u8);
+                ul.add_child(li![format!("{} {}: ", tpos_char(tpos), self.player_name(tpos)), hand.to_elem()]);
+            }
+            ret.add_child(div![p!["Cards played in the last game"], ul]);
+        }
+
+        ret
+    }
+
+    pub fn view(&self) -> Node<Msg> {
+        let body = match (&self.error, &self.info) {
+            (Some(err), _) => p![err],
+            (None, None) => p!["Contacting server..."],
+            (None, Some(info)) => match &info.game {
+                Some(gview) => self.view_game(info, gview),
+                None => {
+                    let mut ul = ul![];
+                    for p in info.players.iter() {
+                        ul.add_child(li![format!("{} {}", tpos_char(p.tpos), p.name)]);
+                    }
+                    div![
+                        p![format!("Players ({}/{}):", info.players.len(), info.nplayers)],
+                        ul,
+                        p!["Waiting for the game to start"],
+                    ]
+                },
+            },
+        };
+
+        let watching = match &self.info {
+            Some(info) if info.spectators.len() > 0 => p![format!("Watching: {}", info.spectators.join(", "))],
+            _ => p![""],
+        };
+
+        div![
+            h2!["Watching game"],
+            body,
+            watching,
+        ]
+    }
+}
+
+// Dropping this does not close the websocket by default, apparently, so we should do it.
+impl Drop for SpectateSt {
+    fn drop(&mut self) {
+        self.wsocket.close().unwrap_or(())
+    }
+}
@@ -120,23 +120,74 @@ impl ToElem for core::CaptureActionBuilder {
     }
 }
 
+impl ToElem for core::actions::PerformedAction {
+    fn to_elem(&self) -> Node<Msg> {
+        let mut act_elem = match &self.action {
+            core::PlayerAction::LayDown(c) => span!["laid down ", c.to_elem(),],
+            core::PlayerAction::Capture(ca) if self.xeri => {
+                let mut table_cards = ca.get_table_cards();
+                span!["made a «ξερή» capturing ",
+                      iter_to_elem("", table_cards.drain(..), ""),
+                      " with ",
+                      ca.handcard.to_elem()
+                ]
+            },
+            core::PlayerAction::Capture(ca) => {
+                let mut table_cards = ca.get_table_cards();
+                span!["captured ",
+                      iter_to_elem("", table_cards.drain(..), ""),
+                      " with ",
+                      ca.handcard.to_elem()
+                ]
+            },
+            core::PlayerAction::Declare(da) => {
+                match da.get_decl() {
+                    None => span![
+                        format!("created a declaration of value {} with ", da.value()),
+                        da.handcard().to_elem(),
+                    ],
+                    Some(decl) if decl.value() < da.value() => span![
+                        format!("raised a declaration from {} to {} with ", decl.value(), da.value()),
+                        da.handcard().to_elem(),
+                    ],
+                    Some(decl) if decl.value() == da.value() => span![
+                        format!("added to a declaration of value {} a ", da.value()),
+                        da.handcard().to_elem(),
+                    ],
+                    _ => panic!("Invalid decl"),
+
+                }
+            }
+        };
+
+        if self.forced_cards.len() > 0 {
+            act_elem.add_child(
+                iter_to_elem(" (forced table cards:", self.forced_cards.iter().cloned(), ")")
+            );
+        }
+        act_elem.add_child(span!["."]);
+
+        act_elem
+    }
+}
+
 impl ToElem for core::ScoreSheet {
     fn to_elem(&self) -> Node<Msg> {
         let details = if self.score == 0 { span![""] } else {
             let mut nodes: Vec<Vec<Node<Msg>>> = vec![];
-            if self.has_the_cards() {
+            if self.cards_score > 0 {
                 nodes.push(
                     vec![
-                        span![format!("{} (cards: {})", core::scoring::NCARDS_SCORE, self.nr_cards)]
+                        span![format!("{} (cards: {})", self.cards_score, self.nr_cards)]
                     ]
                 )
             }
 
-            if self.nr_xeres > 0 {
+            if self.xeres_score > 0 {
                 nodes.push(
                     vec![
                         span![format!("{} ({} {})",
-                            self.nr_xeres*core::scoring::XERI_SCORE,
+                            self.xeres_score,
                             self.nr_xeres,
                             if self.nr_xeres == 1 {"ξερή"} else {"ξερές"},
 
@@ -168,6 +219,26 @@ impl ToElem for core::ScoreSheet {
     }
 }
 
+/// Time left for the current turn, given its deadline (see PlayerGameView::turn_deadline)
+pub fn turn_clock(deadline: Option<u64>) -> Node<Msg> {
+    match deadline {
+        None => span![""],
+        Some(ms) => {
+            let left = ((ms as f64 - js_sys::Date::now()) / 1000.0).ceil().max(0.0);
+            span![attrs!{At::Class => "turn-clock"}, format!("[{}s] ", left)]
+        },
+    }
+}
+
+/// Message for a player that ran out of time
+pub fn timeout_msg(timeout: &core::TurnTimeout, name: &str) -> String {
+    match timeout.policy {
+        core::TimeoutPolicy::AutoPlay => format!("{} ran out of time and played automatically.", name),
+        core::TimeoutPolicy::Skip { penalty } => format!("{} ran out of time and lost their turn (-{} points).", name, penalty),
+        core::TimeoutPolicy::Forfeit => format!("{} ran out of time and forfeited the match.", name),
+    }
+}
+
 /*
 fn tpos_char(tpos: srvcli::PlayerTpos) -> char {
     match tpos.0 {